@@ -79,6 +79,12 @@ export const configSchema = strictObject({
   prerenderMaxZoom: number(),
   prerenderDelayWhenExpiring: nullish(number()),
   maskPolygon: nullish(pipe(string(), nonEmpty())),
+  // label abbreviation rules (YAML); null = built-in rules
+  abbreviations: nullish(pipe(string(), nonEmpty())),
+  // POI style (YAML); null = built-in style
+  poiStyle: nullish(pipe(string(), nonEmpty())),
+  // GDAL block cache of the renderers in MB; null = GDAL default
+  gdalCacheMb: nullish(pipe(number(), integer(), minValue(1))),
 });
 
 export type Config = InferOutput<typeof configSchema>;
@@ -7,6 +7,9 @@ export type RendererConfig = {
   svgBase: string;
   dbPriority?: number;
   maskGeojsonPath: string;
+  abbreviationsPath?: string;
+  poiStylePath?: string;
+  gdalCacheMb?: number;
 };
 
 export type RenderResult = ReturnType<Renderer["render"]>;
@@ -52,7 +55,10 @@ const renderer = new Renderer(
   wd.hillshadingBase,
   wd.svgBase,
   wd.dbPriority,
-  wd.maskGeojsonPath
+  wd.maskGeojsonPath,
+  wd.abbreviationsPath,
+  wd.poiStylePath,
+  wd.gdalCacheMb
 );
 
 pp.postMessage({ type: "ready" } satisfies RenderResponse);
@@ -16,6 +16,9 @@ let rendererConfig: RendererConfig = {
   svgBase: config.dirs.svg,
   dbPriority: config.dbPriority ?? undefined,
   maskGeojsonPath: config.limits.polygon,
+  abbreviationsPath: config.abbreviations ?? undefined,
+  poiStylePath: config.poiStyle ?? undefined,
+  gdalCacheMb: config.gdalCacheMb ?? undefined,
 };
 
 export type WorkerRenderer = {
@@ -352,6 +352,8 @@ export async function exportMap(
       {
         shading: features.shading,
         contours: features.contours,
+        contourInterval: features.contourInterval,
+        slope: features.slope,
         bicycleRoutes: features.bicycleTrails,
         horseRoutes: features.horseTrails,
         hikingRoutes: features.hikingTrails,
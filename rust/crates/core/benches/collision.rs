@@ -0,0 +1,92 @@
+//! Label placement on a dense tile: every candidate box is tested against
+//! the placed ones and added if it does not collide. `linear` is the former
+//! scan over all placed boxes, kept for comparison.
+//!
+//! Run with `cargo bench --features bench`.
+
+use criterion::{BenchmarkId, Criterion, black_box, criterion_group, criterion_main};
+use geo::{Intersects, Rect};
+use maprender_core::Collision;
+
+/// Size of the area labels are placed in: a 256 px tile with the 1024 px
+/// buffer of the label queries.
+const AREA: f64 = 2304.0;
+
+/// Candidate label boxes of a dense tile (z17 town centre with housenumbers,
+/// POIs and street names).
+fn candidates(count: usize) -> Vec<Rect> {
+    // deterministic LCG so that runs are comparable
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+
+    let mut next = move || {
+        state = state
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+
+        (state >> 33) as f64 / (1u64 << 31) as f64
+    };
+
+    (0..count)
+        .map(|_| {
+            let x = next() * AREA;
+            let y = next() * AREA;
+            let width = next().mul_add(60.0, 12.0);
+            let height = next().mul_add(12.0, 10.0);
+
+            Rect::new((x, y), (x + width, y + height))
+        })
+        .collect()
+}
+
+fn place_indexed(candidates: &[Rect]) -> usize {
+    let mut collision = Collision::new(None, None);
+
+    let mut placed = 0;
+
+    for candidate in candidates {
+        if !collision.collides(candidate) {
+            collision.add(*candidate);
+
+            placed += 1;
+        }
+    }
+
+    placed
+}
+
+fn place_linear(candidates: &[Rect]) -> usize {
+    let mut items = Vec::<Rect>::new();
+
+    for candidate in candidates {
+        if !items.iter().any(|item| candidate.intersects(item)) {
+            items.push(*candidate);
+        }
+    }
+
+    items.len()
+}
+
+fn bench_collision(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collision");
+
+    for count in [500, 2_000, 10_000] {
+        let candidates = candidates(count);
+
+        group.bench_with_input(BenchmarkId::new("rtree", count), &candidates, |b, input| {
+            b.iter(|| place_indexed(black_box(input)));
+        });
+
+        group.bench_with_input(
+            BenchmarkId::new("linear", count),
+            &candidates,
+            |b, input| {
+                b.iter(|| place_linear(black_box(input)));
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_collision);
+criterion_main!(benches);
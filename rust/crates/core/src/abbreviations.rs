@@ -0,0 +1,403 @@
+use crate::geojson_utils::load_geometry_from_geojson;
+use crate::regex_replacer::{Replacement, replace};
+use geo::{BoundingRect, Contains, Geometry, Intersects, LinesIter, MultiPolygon, Point, Rect};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+const BUILTIN: &str = include_str!("../styles/abbreviations.yaml");
+
+#[derive(Debug, thiserror::Error)]
+pub enum AbbreviationsError {
+    #[error("Error reading {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Error parsing abbreviations: {0}")]
+    Parse(#[from] serde_yaml::Error),
+
+    #[error("Invalid pattern {pattern:?} for \"{language}/{key}\": {source}")]
+    Pattern {
+        language: String,
+        key: String,
+        pattern: String,
+        source: regex::Error,
+    },
+
+    #[error("Country \"{country}\" refers to unknown language \"{language}\"")]
+    UnknownLanguage { country: String, language: String },
+
+    #[error("Error loading boundary of country \"{country}\": {message}")]
+    Boundary { country: String, message: String },
+}
+
+#[derive(Deserialize)]
+struct AbbreviationsDef {
+    #[serde(default)]
+    default_languages: Vec<String>,
+    #[serde(default)]
+    countries: Vec<CountryDef>,
+    languages: HashMap<String, HashMap<String, Vec<(String, String)>>>,
+}
+
+#[derive(Deserialize)]
+struct CountryDef {
+    code: String,
+    languages: Vec<String>,
+    boundary: Option<PathBuf>,
+}
+
+struct Country {
+    languages: Vec<String>,
+    boundary: Option<(MultiPolygon, Rect)>,
+}
+
+/// Abbreviation rules keyed by language and label key (POI type or layer
+/// specific key), with the country boundaries used to pick the language.
+pub struct Abbreviations {
+    languages: HashMap<String, HashMap<String, Vec<Replacement>>>,
+    countries: Vec<Country>,
+    default_languages: Vec<String>,
+}
+
+impl Default for Abbreviations {
+    fn default() -> Self {
+        Self::parse(BUILTIN, None).expect("built-in abbreviations")
+    }
+}
+
+impl Abbreviations {
+    /// Rules abbreviating nothing.
+    pub(crate) fn empty() -> Self {
+        Self {
+            languages: HashMap::new(),
+            countries: Vec::new(),
+            default_languages: Vec::new(),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AbbreviationsError> {
+        let path = path.as_ref();
+
+        let input = read_to_string(path).map_err(|source| AbbreviationsError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Self::parse(&input, path.parent())
+    }
+
+    fn parse(input: &str, base: Option<&Path>) -> Result<Self, AbbreviationsError> {
+        let def: AbbreviationsDef = serde_yaml::from_str(input)?;
+
+        let mut languages = HashMap::with_capacity(def.languages.len());
+
+        for (language, keys) in def.languages {
+            let mut rules = HashMap::with_capacity(keys.len());
+
+            for (key, pairs) in keys {
+                let replacements = pairs
+                    .into_iter()
+                    .map(|(pattern, replacement)| match Regex::new(&pattern) {
+                        Ok(regex) => Ok((regex, replacement)),
+                        Err(source) => Err(AbbreviationsError::Pattern {
+                            language: language.clone(),
+                            key: key.clone(),
+                            pattern,
+                            source,
+                        }),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                rules.insert(key, replacements);
+            }
+
+            languages.insert(language, rules);
+        }
+
+        let mut countries = Vec::with_capacity(def.countries.len());
+
+        for country in def.countries {
+            if let Some(language) = country
+                .languages
+                .iter()
+                .find(|language| !languages.contains_key(*language))
+            {
+                return Err(AbbreviationsError::UnknownLanguage {
+                    country: country.code,
+                    language: language.clone(),
+                });
+            }
+
+            let boundary = country
+                .boundary
+                .map(|path| {
+                    load_boundary(&base.map_or(path.clone(), |base| base.join(&path))).map_err(
+                        |message| AbbreviationsError::Boundary {
+                            country: country.code.clone(),
+                            message,
+                        },
+                    )
+                })
+                .transpose()?;
+
+            countries.push(Country {
+                languages: country.languages,
+                boundary,
+            });
+        }
+
+        Ok(Self {
+            languages,
+            countries,
+            default_languages: def.default_languages,
+        })
+    }
+
+    /// Prepares the language lookup for a tile so that only countries touching
+    /// the tile are tested and tiles lying inside a single country need no
+    /// point-in-polygon tests at all.
+    pub fn for_tile(&self, bbox: Rect) -> TileAbbreviations<'_> {
+        let _span = tracy_client::span!("abbreviations::for_tile");
+
+        let mut inside = None;
+        let mut border = Vec::new();
+
+        for country in &self.countries {
+            let Some((boundary, bounding_rect)) = &country.boundary else {
+                continue;
+            };
+
+            if !bounding_rect.intersects(&bbox) {
+                continue;
+            }
+
+            if boundary.lines_iter().any(|line| line.intersects(&bbox)) {
+                border.push(country);
+            } else if boundary.contains(&Point::from(bbox.center())) {
+                inside = Some(country);
+
+                break;
+            }
+        }
+
+        TileAbbreviations {
+            abbreviations: self,
+            inside,
+            border,
+        }
+    }
+
+    fn rules(&self, language: &str, key: &str) -> Option<&[Replacement]> {
+        self.languages
+            .get(language)
+            .and_then(|rules| rules.get(key))
+            .map(Vec::as_slice)
+    }
+}
+
+fn load_boundary(path: &Path) -> Result<(MultiPolygon, Rect), String> {
+    let boundary = match load_geometry_from_geojson(path)? {
+        Geometry::Polygon(polygon) => MultiPolygon::from(polygon),
+        Geometry::MultiPolygon(multi_polygon) => multi_polygon,
+        _ => return Err(format!("{}: expected a (multi)polygon", path.display())),
+    };
+
+    let bounding_rect = boundary
+        .bounding_rect()
+        .ok_or_else(|| format!("{}: empty geometry", path.display()))?;
+
+    Ok((boundary, bounding_rect))
+}
+
+pub struct TileAbbreviations<'a> {
+    abbreviations: &'a Abbreviations,
+    inside: Option<&'a Country>,
+    border: Vec<&'a Country>,
+}
+
+impl TileAbbreviations<'_> {
+    fn languages_at(&self, point: &Point) -> &[String] {
+        if let Some(country) = self.inside {
+            return &country.languages;
+        }
+
+        self.border
+            .iter()
+            .find(|country| {
+                country
+                    .boundary
+                    .as_ref()
+                    .is_some_and(|(boundary, _)| boundary.contains(point))
+            })
+            .map_or(&self.abbreviations.default_languages, |country| {
+                &country.languages
+            })
+    }
+
+    /// Abbreviates `name` of a feature of kind `key` located at `point`
+    /// (in EPSG:3857) by the rules of the first of the languages there which
+    /// abbreviate it, as a label is in one language.
+    pub fn replace<'n>(&self, point: &Point, key: &str, name: &'n str) -> Cow<'n, str> {
+        for language in self.languages_at(point) {
+            if let Some(rules) = self.abbreviations.rules(language, key)
+                && let Cow::Owned(updated) = replace(name, rules)
+            {
+                return Cow::Owned(updated);
+            }
+        }
+
+        Cow::Borrowed(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xyz::lon_lat_to_epsg3857;
+    use std::fs;
+
+    const RULES: &str = r"
+default_languages: [sk]
+
+countries:
+  - code: at
+    languages: [de, sk]
+    boundary: at.geojson
+
+languages:
+  sk:
+    church:
+      - ['^[Kk]ostol\b *', '']
+  de:
+    church:
+      - ['^[Kk]irche\b *', '']
+";
+
+    /// Rules with the boundary of `at` around 0–10° of longitude and
+    /// latitude, written to a directory unique to the `test`.
+    fn with_boundary(test: &str) -> Abbreviations {
+        let dir = std::env::temp_dir().join(format!("abbreviations-{test}-{}", std::process::id()));
+
+        fs::create_dir_all(&dir).unwrap();
+
+        fs::write(
+            dir.join("at.geojson"),
+            r#"{"type": "Polygon", "coordinates": [[[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]]]}"#,
+        )
+        .unwrap();
+
+        let abbreviations = Abbreviations::parse(RULES, Some(&dir)).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        abbreviations
+    }
+
+    fn point(lon: f64, lat: f64) -> Point {
+        Point::from(lon_lat_to_epsg3857(lon, lat))
+    }
+
+    fn bbox(min_lon: f64, min_lat: f64, max_lon: f64, max_lat: f64) -> Rect {
+        Rect::new(
+            lon_lat_to_epsg3857(min_lon, min_lat),
+            lon_lat_to_epsg3857(max_lon, max_lat),
+        )
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let err = Abbreviations::parse("languages: {sk: {church: [['(', '']]}}", None)
+            .err()
+            .unwrap();
+
+        assert!(
+            matches!(&err, AbbreviationsError::Pattern { language, key, pattern, .. }
+                if language == "sk" && key == "church" && pattern == "("),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn unknown_language() {
+        let err = Abbreviations::parse(
+            "{countries: [{code: at, languages: [de]}], languages: {sk: {}}}",
+            None,
+        )
+        .err()
+        .unwrap();
+
+        assert!(
+            matches!(&err, AbbreviationsError::UnknownLanguage { country, language }
+                if country == "at" && language == "de"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn default_languages_outside_boundaries() {
+        let abbreviations = with_boundary("default");
+
+        let tile = abbreviations.for_tile(bbox(20.0, 20.0, 21.0, 21.0));
+
+        let at = point(20.5, 20.5);
+
+        assert_eq!(tile.replace(&at, "church", "Kostol sv. Anny"), "sv. Anny");
+        assert_eq!(
+            tile.replace(&at, "church", "Kirche St. Anna"),
+            "Kirche St. Anna"
+        );
+        assert_eq!(
+            tile.replace(&at, "chapel", "Kostol sv. Anny"),
+            "Kostol sv. Anny"
+        );
+    }
+
+    #[test]
+    fn languages_by_point() {
+        let abbreviations = with_boundary("by_point");
+
+        // inside the country and across its border
+        for tile in [
+            abbreviations.for_tile(bbox(4.0, 4.0, 5.0, 5.0)),
+            abbreviations.for_tile(bbox(9.0, 4.0, 11.0, 5.0)),
+        ] {
+            let at = point(4.5, 4.5);
+
+            assert_eq!(tile.replace(&at, "church", "Kirche St. Anna"), "St. Anna");
+            assert_eq!(tile.replace(&at, "church", "Kostol sv. Anny"), "sv. Anny");
+        }
+
+        let tile = abbreviations.for_tile(bbox(9.0, 4.0, 11.0, 5.0));
+
+        assert_eq!(
+            tile.replace(&point(10.5, 4.5), "church", "Kirche St. Anna"),
+            "Kirche St. Anna"
+        );
+    }
+
+    #[test]
+    fn built_in_rules_are_slovak_without_boundaries() {
+        let abbreviations = Abbreviations::default();
+
+        let tile = abbreviations.for_tile(bbox(11.0, 47.0, 12.0, 48.0));
+
+        let at = point(11.5, 47.5);
+
+        assert_eq!(
+            tile.replace(&at, "school", "Základná škola Hlavná"),
+            "ZŠ Hlavná"
+        );
+        assert_eq!(
+            tile.replace(&at, "church", "Pfarrkirche Sankt Anna"),
+            "Pfarrkirche Sankt Anna"
+        );
+    }
+}
@@ -1,75 +1,206 @@
-use crate::colors::ContextExt;
-use cairo::Context;
-use geo::{Coord, Intersects, Rect};
+use crate::debug_overlay::DebugOverlay;
+use crate::utfgrid::{FeatureId, UtfGrid};
+use geo::{Coord, Geometry, LineString, Polygon, Rect};
+use rstar::{AABB, RTree, primitives::GeomWithData, primitives::Rectangle};
+use std::cell::RefCell;
 
-const DEBUG: bool = false;
+/// Placed box indexed by its bounding rectangle, with its index (as returned
+/// by [`Collision::add`]).
+type Item = GeomWithData<Rectangle<[f64; 2]>, (usize, OrientedRect)>;
 
+/// Rectangle rotated around its centre, e.g. a glyph of a label along a line.
+#[derive(Debug, Clone, Copy)]
+pub struct OrientedRect {
+    center: Coord,
+    half_width: f64,
+    half_height: f64,
+    /// Rotation in radians.
+    angle: f64,
+}
+
+impl OrientedRect {
+    pub fn new(center: Coord, width: f64, height: f64, angle: f64) -> Self {
+        Self {
+            center,
+            half_width: width / 2.0,
+            half_height: height / 2.0,
+            angle,
+        }
+    }
+
+    /// Unit vectors along the width and the height.
+    fn axes(&self) -> [Coord; 2] {
+        let (sin, cos) = self.angle.sin_cos();
+
+        [Coord { x: cos, y: sin }, Coord { x: -sin, y: cos }]
+    }
+
+    pub fn corners(&self) -> [Coord; 4] {
+        let [u, v] = self.axes();
+
+        let u = u * self.half_width;
+        let v = v * self.half_height;
+
+        [
+            self.center - u - v,
+            self.center + u - v,
+            self.center + u + v,
+            self.center - u + v,
+        ]
+    }
+
+    pub fn bounding_rect(&self) -> Rect {
+        let (sin, cos) = self.angle.sin_cos();
+
+        let rx = self
+            .half_width
+            .mul_add(cos.abs(), self.half_height * sin.abs());
+        let ry = self
+            .half_width
+            .mul_add(sin.abs(), self.half_height * cos.abs());
+
+        Rect::new(
+            (self.center.x - rx, self.center.y - ry),
+            (self.center.x + rx, self.center.y + ry),
+        )
+    }
+
+    pub fn to_polygon(self) -> Polygon {
+        Polygon::new(LineString::from(self.corners().to_vec()), vec![])
+    }
+
+    /// Tests intersection by the separating axis theorem. Touching rectangles
+    /// intersect.
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.axes().into_iter().chain(other.axes()).all(|axis| {
+            let (min_a, max_a) = self.project(axis);
+            let (min_b, max_b) = other.project(axis);
+
+            min_a <= max_b && min_b <= max_a
+        })
+    }
+
+    fn project(&self, axis: Coord) -> (f64, f64) {
+        let [u, v] = self.axes();
+
+        let center = dot(self.center, axis);
+
+        let radius = self
+            .half_width
+            .mul_add(dot(u, axis).abs(), self.half_height * dot(v, axis).abs());
+
+        (center - radius, center + radius)
+    }
+
+    pub const fn grow(self, by: f64) -> Self {
+        Self {
+            half_width: self.half_width + by,
+            half_height: self.half_height + by,
+            ..self
+        }
+    }
+}
+
+impl From<Rect> for OrientedRect {
+    fn from(rect: Rect) -> Self {
+        Self::new(rect.center(), rect.width(), rect.height(), 0.0)
+    }
+}
+
+fn dot(a: Coord, b: Coord) -> f64 {
+    a.x.mul_add(b.x, a.y * b.y)
+}
+
+/// Boxes of placed labels and icons, indexed by an R-tree so that testing a
+/// candidate does not scan every placed box.
 pub struct Collision<'a> {
-    items: Vec<Rect>,
-    context: Option<&'a Context>,
+    items: RTree<Item>,
+    debug: Option<&'a RefCell<DebugOverlay>>,
+    hit_areas: Option<&'a RefCell<UtfGrid>>,
+    feature: Option<FeatureId>,
+    /// Boxes of features, filled as hit areas by [`Self::fill_hit_areas`].
+    hit_boxes: Vec<(FeatureId, OrientedRect)>,
 }
 
 const EPSILON: f64 = 0.001;
 
 impl<'a> Collision<'a> {
-    pub const fn new(context: Option<&'a Context>) -> Self {
+    pub fn new(
+        debug: Option<&'a RefCell<DebugOverlay>>,
+        hit_areas: Option<&'a RefCell<UtfGrid>>,
+    ) -> Self {
         Self {
-            items: vec![],
-            context,
+            items: RTree::new(),
+            debug,
+            hit_areas,
+            feature: None,
+            hit_boxes: Vec::new(),
         }
     }
 
+    /// Sets the feature to which items added from now on belong as hit areas.
+    pub fn set_feature(&mut self, feature: Option<FeatureId>) {
+        self.feature = feature;
+    }
+
     pub fn add(&mut self, item: Rect) -> usize {
-        self.items.push(Rect::new(
-            Coord {
-                x: item.min().x - EPSILON,
-                y: item.min().y - EPSILON,
-            },
-            Coord {
-                x: item.max().x + EPSILON,
-                y: item.max().y + EPSILON,
-            },
+        self.add_oriented(item.into())
+    }
+
+    pub fn add_oriented(&mut self, item: OrientedRect) -> usize {
+        if self.hit_areas.is_some()
+            && let Some(feature) = self.feature
+        {
+            self.hit_boxes.push((feature, item));
+        }
+
+        let index = self.items.size();
+
+        let grown = item.grow(EPSILON);
+
+        self.items.insert(GeomWithData::new(
+            Rectangle::from_aabb(envelope(&grown.bounding_rect())),
+            (index, grown),
         ));
 
-        if DEBUG && let Some(context) = self.context {
-            context.rectangle(
-                item.min().x.into(),
-                item.min().y.into(),
-                item.width().into(),
-                item.height().into(),
-            );
-
-            context.save().expect("context saved");
-            context.set_source_color_a((0, 255, 0), 0.5);
-            context.set_line_width(1.0);
-            context.stroke().unwrap();
-            context.restore().expect("context restored");
+        if let Some(debug) = self.debug {
+            debug.borrow_mut().accepted(item);
         }
 
-        self.items.len() - 1
+        index
+    }
+
+    /// Fills the hit areas of the boxes added for features. Called once the
+    /// labels are painted, so that the areas of layers painted under them do
+    /// not cover them.
+    pub fn fill_hit_areas(&self) -> cairo::Result<()> {
+        if let Some(hit_areas) = self.hit_areas {
+            let hit_areas = hit_areas.borrow();
+
+            for (feature, item) in &self.hit_boxes {
+                hit_areas.fill_geometry(*feature, &Geometry::Polygon(item.to_polygon()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Iterates the placed boxes.
+    pub fn boxes(&self) -> impl Iterator<Item = &OrientedRect> {
+        self.items.iter().map(|item| &item.data.1)
     }
 
     pub fn collides(&self, bb: &Rect) -> bool {
+        self.collides_oriented(&(*bb).into())
+    }
+
+    pub fn collides_oriented(&self, item: &OrientedRect) -> bool {
         let _span = tracy_client::span!("collision::collides");
 
-        let intersects = self.items.iter().any(|item| bb.intersects(item));
+        let intersects = self.intersects_any(item, None);
 
-        if DEBUG
-            && intersects
-            && let Some(context) = self.context
-        {
-            context.rectangle(
-                bb.min().x.into(),
-                bb.min().y.into(),
-                bb.width().into(),
-                bb.height().into(),
-            );
-
-            context.save().expect("context saved");
-            context.set_source_color_a((255, 0, 0), 0.2);
-            context.set_line_width(1.0);
-            context.stroke().unwrap();
-            context.restore().expect("context restored");
+        if intersects && let Some(debug) = self.debug {
+            debug.borrow_mut().rejected(*item);
         }
 
         intersects
@@ -78,9 +209,120 @@ impl<'a> Collision<'a> {
     pub fn collides_with_exclusion(&self, bbox: &Rect, exclude: usize) -> bool {
         let _span = tracy_client::span!("collision::collides");
 
+        let item = (*bbox).into();
+
+        let intersects = self.intersects_any(&item, Some(exclude));
+
+        if intersects && let Some(debug) = self.debug {
+            debug.borrow_mut().rejected(item);
+        }
+
+        intersects
+    }
+
+    fn intersects_any(&self, item: &OrientedRect, exclude: Option<usize>) -> bool {
         self.items
-            .iter()
-            .enumerate()
-            .any(|(idx, item)| idx != exclude && bbox.intersects(item))
+            .locate_in_envelope_intersecting(&envelope(&item.bounding_rect()))
+            .any(|placed| {
+                let (index, shape) = &placed.data;
+
+                // bounding rectangles of axis-aligned ones are exact
+                Some(*index) != exclude
+                    && ((shape.angle == 0.0 && item.angle == 0.0) || shape.intersects(item))
+            })
+    }
+}
+
+fn envelope(rect: &Rect) -> AABB<[f64; 2]> {
+    AABB::from_corners([rect.min().x, rect.min().y], [rect.max().x, rect.max().y])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Collision, OrientedRect};
+    use geo::{Coord, Rect};
+    use std::f64::consts::FRAC_PI_4;
+
+    fn rect(x: f64, y: f64, width: f64, height: f64, angle: f64) -> OrientedRect {
+        OrientedRect::new(Coord { x, y }, width, height, angle)
+    }
+
+    fn assert_close(actual: Coord, expected: (f64, f64)) {
+        assert!(
+            (actual.x - expected.0).abs() < 1e-9 && (actual.y - expected.1).abs() < 1e-9,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn corners_of_rotated() {
+        let corners = rect(10.0, 20.0, 4.0, 2.0, FRAC_PI_4 * 2.0).corners();
+
+        assert_close(corners[0], (11.0, 18.0));
+        assert_close(corners[1], (11.0, 22.0));
+        assert_close(corners[2], (9.0, 22.0));
+        assert_close(corners[3], (9.0, 18.0));
+    }
+
+    #[test]
+    fn overlapping() {
+        assert!(rect(0.0, 0.0, 4.0, 2.0, 0.0).intersects(&rect(1.0, 1.0, 4.0, 2.0, 0.0)));
+        assert!(rect(0.0, 0.0, 4.0, 2.0, 0.3).intersects(&rect(1.0, 0.5, 4.0, 2.0, -0.6)));
+    }
+
+    #[test]
+    fn touching() {
+        let a = rect(0.0, 0.0, 2.0, 2.0, 0.0);
+
+        assert!(a.intersects(&rect(2.0, 0.0, 2.0, 2.0, 0.0)));
+        assert!(a.intersects(&rect(2.0, 2.0, 2.0, 2.0, 0.0)));
+        assert!(!a.intersects(&rect(2.1, 0.0, 2.0, 2.0, 0.0)));
+    }
+
+    #[test]
+    fn separated_on_diagonal_axis() {
+        // diamonds with overlapping bounding rectangles, apart along x = y
+        let a = rect(0.0, 0.0, 2.0, 2.0, FRAC_PI_4);
+        let b = rect(1.5, 1.5, 2.0, 2.0, FRAC_PI_4);
+
+        assert!(a.bounding_rect().min().x < b.bounding_rect().max().x);
+        assert!(b.bounding_rect().min().y < a.bounding_rect().max().y);
+
+        assert!(!a.intersects(&b));
+        assert!(!b.intersects(&a));
+
+        assert!(a.intersects(&rect(1.0, 1.0, 2.0, 2.0, FRAC_PI_4)));
+    }
+
+    #[test]
+    fn axis_aligned_and_rotated() {
+        let square = rect(0.0, 0.0, 2.0, 2.0, 0.0);
+
+        // a corner of the diamond pokes into the square
+        assert!(square.intersects(&rect(2.2, 0.0, 2.0, 2.0, FRAC_PI_4)));
+
+        // only the bounding rectangles overlap
+        assert!(!square.intersects(&rect(2.2, 2.2, 2.0, 2.0, FRAC_PI_4)));
+    }
+
+    #[test]
+    fn collides_with_oriented_boxes() {
+        let mut collision = Collision::new(None, None);
+
+        let index = collision.add_oriented(rect(0.0, 0.0, 2.0, 2.0, FRAC_PI_4));
+
+        // in the bounding rectangle of the diamond, off its edge
+        assert!(!collision.collides(&Rect::new((0.9, 0.9), (1.3, 1.3))));
+        assert!(!collision.collides_oriented(&rect(1.5, 1.5, 2.0, 2.0, FRAC_PI_4)));
+
+        assert!(collision.collides(&Rect::new((0.5, 0.5), (1.0, 1.0))));
+        assert!(collision.collides_oriented(&rect(1.0, 0.0, 1.0, 0.5, 0.2)));
+
+        assert!(!collision.collides_with_exclusion(&Rect::new((-0.5, -0.5), (0.5, 0.5)), index));
+
+        collision.add(Rect::new((3.0, -1.0), (5.0, 1.0)));
+
+        assert!(collision.collides(&Rect::new((4.0, 0.0), (6.0, 2.0))));
+        assert!(!collision.collides(&Rect::new((5.1, 0.0), (6.0, 2.0))));
     }
 }
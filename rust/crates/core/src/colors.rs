@@ -75,6 +75,7 @@ pub const LANDFILL: Color = hsl_to_rgb(0, 30, 75);
 pub const MILITARY: Color = hsl_to_rgb(0, 96, 39);
 pub const NONE: Color = hsl_to_rgb(0, 100, 100);
 pub const ORCHARD: Color = hsl_to_rgb(90, 75, 85);
+pub const GARDEN_STROKE: Color = hsl_to_rgb(0, 0, 0);
 pub const PARKING_STROKE: Color = hsl_to_rgb(0, 30, 75);
 pub const PARKING: Color = hsl_to_rgb(0, 20, 88);
 pub const PIER: Color = hsl_to_rgb(0, 0, 0);
@@ -116,6 +117,10 @@ pub const SOLAR_FG: Color = hsl_to_rgb(250, 57, 76);
 pub const TREE: Color = hsl_to_rgb(120, 100, 31);
 pub const DAM_LINE: Color = hsl_to_rgb(0, 0, 40);
 pub const SOLAR_PLANT_BORDER: Color = hsl_to_rgb(250, 60, 50);
+pub const SLOPE_30: Color = hsl_to_rgb(55, 100, 50);
+pub const SLOPE_35: Color = hsl_to_rgb(30, 100, 50);
+pub const SLOPE_40: Color = hsl_to_rgb(0, 100, 45);
+pub const SLOPE_45: Color = hsl_to_rgb(285, 70, 45);
 
 pub trait ContextExt {
     fn set_source_color(&self, color: Color);
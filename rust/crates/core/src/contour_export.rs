@@ -0,0 +1,135 @@
+use crate::layers::contours::{self, DEFAULT_TABLE};
+use crate::layers::hillshading_datasets::HillshadingDatasets;
+use crate::xyz::{bbox_size_in_pixels, epsg3857_to_lon_lat, lon_lat_to_epsg3857};
+use geo::{
+    BooleanOps, BoundingRect, Coord, LineString, MapCoords, MultiLineString, MultiPolygon, Rect,
+};
+use geojson::{Feature, FeatureCollection, Geometry, JsonObject, Value};
+
+/// Largest exported area in tiles of the requested zoom.
+const MAX_TILES: u64 = 64;
+
+/// Zoom from which contours are drawn.
+const MIN_ZOOM: u32 = 12;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ContourExportError {
+    #[error("Contours are exported from zoom {MIN_ZOOM}")]
+    ZoomTooLow,
+
+    #[error("Bounding box is larger than {MAX_TILES} tiles at zoom {0}")]
+    BboxTooLarge(u32),
+
+    #[error("DB error: {0}")]
+    Postgres(#[from] postgres::Error),
+}
+
+/// Contour lines within the WGS84 `bbox` as a GeoJSON feature collection
+/// with `height_m`, from the same tables and simplified as drawn at `zoom`.
+/// Each area has a single source: the table of the dataset on top there,
+/// clipped to its coverage, and outside all datasets the table of the
+/// fallback or the default one.
+pub fn export_contours(
+    client: &mut postgres::Client,
+    hillshading_datasets: Option<&HillshadingDatasets>,
+    bbox: Rect,
+    zoom: u32,
+) -> Result<String, ContourExportError> {
+    let _span = tracy_client::span!("export_contours");
+
+    if zoom < MIN_ZOOM {
+        return Err(ContourExportError::ZoomTooLow);
+    }
+
+    let bbox = Rect::new(
+        lon_lat_to_epsg3857(bbox.min().x, bbox.min().y),
+        lon_lat_to_epsg3857(bbox.max().x, bbox.max().y),
+    );
+
+    let size = bbox_size_in_pixels(bbox, zoom as f64);
+
+    if size.width as u64 * size.height as u64 > MAX_TILES * 256 * 256 {
+        return Err(ContourExportError::BboxTooLarge(zoom));
+    }
+
+    let mut sources: Vec<(String, MultiPolygon)> = Vec::new();
+
+    let mut covered = MultiPolygon::new(Vec::new());
+
+    let mut outside_table = None;
+
+    if let Some(datasets) = hillshading_datasets {
+        for (dataset, area) in datasets.top_areas(bbox) {
+            covered = covered.union(&dataset.area(bbox));
+
+            // contours traced from elevation are not exported
+            if let Some(table) = dataset.contours {
+                add_source(&mut sources, table, area);
+            }
+        }
+
+        outside_table = datasets
+            .fallback(bbox)
+            .and_then(|fallback| fallback.contours);
+    }
+
+    add_source(
+        &mut sources,
+        outside_table.unwrap_or_else(|| DEFAULT_TABLE.to_string()),
+        bbox.to_polygon().difference(&covered),
+    );
+
+    let mut features = Vec::new();
+
+    for (table, area) in &sources {
+        let Some(area_bbox) = area.bounding_rect() else {
+            continue;
+        };
+
+        for (height, geometry) in contours::query_lines(client, area_bbox, zoom, Some(table))? {
+            for geometry in area.clip(&MultiLineString::new(vec![geometry]), false) {
+                let geometry: LineString = geometry.map_coords(|coord| {
+                    let Coord { x, y } = epsg3857_to_lon_lat(coord);
+
+                    // ~0.1 m
+                    Coord {
+                        x: (x * 1e6).round() / 1e6,
+                        y: (y * 1e6).round() / 1e6,
+                    }
+                });
+
+                let mut properties = JsonObject::new();
+
+                properties.insert("height_m".into(), height.into());
+
+                features.push(Feature {
+                    bbox: None,
+                    geometry: Some(Geometry::new(Value::from(&geometry))),
+                    id: None,
+                    properties: Some(properties),
+                    foreign_members: None,
+                });
+            }
+        }
+    }
+
+    let collection = FeatureCollection {
+        bbox: None,
+        features,
+        foreign_members: None,
+    };
+
+    Ok(serde_json::to_string(&collection).expect("serialized contours"))
+}
+
+/// Adds `area` to the area of `table`, so that each table is queried once.
+fn add_source(sources: &mut Vec<(String, MultiPolygon)>, table: String, area: MultiPolygon) {
+    if area.0.is_empty() {
+        return;
+    }
+
+    match sources.iter_mut().find(|(other, _)| *other == table) {
+        Some((_, other_area)) => *other_area = other_area.union(&area),
+        None => sources.push((table, area)),
+    }
+}
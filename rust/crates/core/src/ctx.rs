@@ -1,7 +1,16 @@
-use crate::{projectable::TileProjector, size::Size};
+use crate::{
+    abbreviations::TileAbbreviations,
+    debug_overlay::DebugOverlay,
+    labels::LabelMask,
+    mvt::MvtTile,
+    projectable::TileProjector,
+    size::Size,
+    utfgrid::{FeatureId, UtfGrid},
+};
 use cairo::Context;
-use geo::Rect;
-use postgres::types::ToSql;
+use geo::{Geometry, Rect};
+use postgres::{Row, types::ToSql};
+use std::cell::{OnceCell, RefCell};
 
 pub struct SqlParams {
     params: Vec<Box<dyn ToSql + Sync>>,
@@ -29,6 +38,15 @@ pub struct Ctx<'a> {
     pub size: Size<u32>,
     pub zoom: u32,
     pub tile_projector: TileProjector,
+    pub abbreviations: TileAbbreviations<'a>,
+    /// Feature hit areas, collected only when rendering a UTFGrid.
+    pub hit_areas: Option<&'a RefCell<UtfGrid>>,
+    /// Vector tile collecting layer rows, set only when rendering MVT.
+    pub mvt: Option<&'a RefCell<MvtTile>>,
+    /// Debug information collected when requested.
+    pub debug: Option<&'a RefCell<DebugOverlay>>,
+    /// Boxes of the placed labels, set once the labels are placed.
+    pub label_mask: OnceCell<LabelMask>,
 }
 
 impl Ctx<'_> {
@@ -36,6 +54,62 @@ impl Ctx<'_> {
         self.bbox.width() / self.size.width as f64
     }
 
+    /// Registers feature `osm_id` of `layer` for hit testing. Returns `None`
+    /// if hit areas are not collected.
+    pub fn hit_feature(
+        &self,
+        layer: &str,
+        osm_id: i64,
+        attributes: &[(&str, &str)],
+    ) -> Option<FeatureId> {
+        self.hit_areas.map(|hit_areas| {
+            hit_areas
+                .borrow_mut()
+                .add_feature(layer, osm_id, attributes)
+        })
+    }
+
+    /// Registers feature `osm_id` of `layer` with the hit area of `geom`
+    /// already projected to the tile.
+    pub fn hit_area(
+        &self,
+        layer: &str,
+        osm_id: i64,
+        attributes: &[(&str, &str)],
+        geom: &Geometry,
+    ) -> cairo::Result<()> {
+        if let Some(hit_areas) = self.hit_areas {
+            let mut hit_areas = hit_areas.borrow_mut();
+
+            let feature = hit_areas.add_feature(layer, osm_id, attributes);
+
+            hit_areas.fill_geometry(feature, geom)?;
+        }
+
+        Ok(())
+    }
+
+    /// When rendering a vector tile, adds `rows` to its `layer` instead of
+    /// drawing them and returns `true`.
+    pub fn emit_rows(&self, layer: &str, rows: &[Row], attributes: &[&str]) -> bool {
+        let Some(mvt) = self.mvt else {
+            return false;
+        };
+
+        mvt.borrow_mut().add_rows(layer, rows, attributes);
+
+        true
+    }
+
+    /// Erases `amount` (0 to 1) of what has been drawn to the current group
+    /// under the placed labels.
+    pub fn knock_out_labels(&self, amount: f64) -> cairo::Result<()> {
+        match self.label_mask.get() {
+            Some(label_mask) => label_mask.knock_out(self.context, amount),
+            None => Ok(()),
+        }
+    }
+
     pub fn bbox_query_params(&self, buffer_from_param: Option<f64>) -> SqlParams {
         let min = self.bbox.min();
         let max = self.bbox.max();
@@ -0,0 +1,181 @@
+use crate::collision::OrientedRect;
+use crate::colors::{self, ContextExt};
+use crate::draw::create_pango_layout::{FontAndLayoutOptions, create_pango_layout_with_attrs};
+use crate::size::Size;
+use bitflags::bitflags;
+use cairo::Context;
+use pangocairo::pango::Alignment;
+use std::time::{Duration, Instant};
+
+/// Width of the band along the tile edges in which labels may also be drawn
+/// by the neighbouring tiles.
+const TILE_BUFFER: f64 = 64.0;
+
+bitflags! {
+  /// Debug information drawn over the tile.
+  #[derive(Debug, Clone, Copy, Default)]
+  pub struct DebugFlags: u32 {
+      /// Accepted (green) and rejected (red) label collision boxes.
+      const COLLISION = 0b0000_0001;
+      /// Tile boundary and the buffer along it.
+      const TILES = 0b0000_0010;
+      /// Render time of every layer.
+      const TIMINGS = 0b0000_0100;
+  }
+}
+
+impl DebugFlags {
+    /// Parses a comma separated list of `collision`, `tiles` and `timings`.
+    /// Unknown names are ignored.
+    pub fn parse(value: &str) -> Self {
+        value
+            .split(',')
+            .map(|name| match name.trim() {
+                "collision" => Self::COLLISION,
+                "tiles" => Self::TILES,
+                "timings" => Self::TIMINGS,
+                _ => Self::empty(),
+            })
+            .collect()
+    }
+}
+
+/// Debug information collected while rendering a tile and drawn over it at
+/// the end.
+pub struct DebugOverlay {
+    flags: DebugFlags,
+    accepted: Vec<OrientedRect>,
+    rejected: Vec<OrientedRect>,
+    timings: Vec<(&'static str, Duration)>,
+    lap_start: Instant,
+}
+
+impl DebugOverlay {
+    pub fn new(flags: DebugFlags) -> Self {
+        Self {
+            flags,
+            accepted: Vec::new(),
+            rejected: Vec::new(),
+            timings: Vec::new(),
+            lap_start: Instant::now(),
+        }
+    }
+
+    /// Records a label box added to the collision detection.
+    pub fn accepted(&mut self, rect: OrientedRect) {
+        if self.flags.contains(DebugFlags::COLLISION) {
+            self.accepted.push(rect);
+        }
+    }
+
+    /// Records a label box rejected by the collision detection.
+    pub fn rejected(&mut self, rect: OrientedRect) {
+        if self.flags.contains(DebugFlags::COLLISION) {
+            self.rejected.push(rect);
+        }
+    }
+
+    /// Attributes the time since the previous lap to `layer`. Layers rendered
+    /// in several passes sum up.
+    pub fn lap(&mut self, layer: &'static str) {
+        let now = Instant::now();
+
+        let elapsed = now - self.lap_start;
+
+        self.lap_start = now;
+
+        if let Some((_, duration)) = self.timings.iter_mut().find(|(name, _)| *name == layer) {
+            *duration += elapsed;
+        } else {
+            self.timings.push((layer, elapsed));
+        }
+    }
+
+    pub fn draw(&self, context: &Context, size: Size<u32>) -> cairo::Result<()> {
+        let _span = tracy_client::span!("debug_overlay::draw");
+
+        context.save()?;
+
+        context.set_line_width(1.0);
+
+        if self.flags.contains(DebugFlags::TILES) {
+            let width = size.width as f64;
+            let height = size.height as f64;
+
+            context.rectangle(0.0, 0.0, width, height);
+            context.rectangle(
+                TILE_BUFFER,
+                TILE_BUFFER,
+                TILE_BUFFER.mul_add(-2.0, width),
+                TILE_BUFFER.mul_add(-2.0, height),
+            );
+            context.set_fill_rule(cairo::FillRule::EvenOdd);
+            context.set_source_color_a((255, 0, 255), 0.1);
+            context.fill()?;
+
+            context.rectangle(0.5, 0.5, width - 1.0, height - 1.0);
+            context.set_source_color((255, 0, 255));
+            context.stroke()?;
+        }
+
+        for (rects, color, alpha) in [
+            (&self.rejected, (255, 0, 0), 0.2),
+            (&self.accepted, (0, 255, 0), 0.5),
+        ] {
+            for rect in rects {
+                let [first, rest @ ..] = rect.corners();
+
+                context.move_to(first.x, first.y);
+
+                for corner in rest {
+                    context.line_to(corner.x, corner.y);
+                }
+
+                context.close_path();
+            }
+
+            context.set_source_color_a(color, alpha);
+            context.stroke()?;
+        }
+
+        if self.flags.contains(DebugFlags::TIMINGS) {
+            let total: Duration = self.timings.iter().map(|(_, duration)| *duration).sum();
+
+            let text = self
+                .timings
+                .iter()
+                .chain([&("total", total)])
+                .map(|(layer, duration)| {
+                    format!("{layer}: {:.1} ms", duration.as_secs_f64() * 1000.0)
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let layout = create_pango_layout_with_attrs(
+                context,
+                &text,
+                None,
+                &FontAndLayoutOptions {
+                    size: 9.0,
+                    ..FontAndLayoutOptions::default()
+                },
+            );
+
+            layout.set_width(-1);
+            layout.set_alignment(Alignment::Left);
+
+            context.move_to(4.0, 4.0);
+
+            pangocairo::functions::layout_path(context, &layout);
+
+            context.set_source_color(colors::WHITE);
+            context.set_line_width(3.0);
+            context.stroke_preserve()?;
+
+            context.set_source_color(colors::BLACK);
+            context.fill()?;
+        }
+
+        context.restore()
+    }
+}
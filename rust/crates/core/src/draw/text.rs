@@ -181,7 +181,7 @@ pub fn draw_text_with_attrs(
 
         if let Some(collision) = collision {
             for item in items {
-                let _ = collision.add(item);
+                collision.add(item);
             }
         }
 
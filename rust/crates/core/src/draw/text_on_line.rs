@@ -1,5 +1,5 @@
 use crate::{
-    collision::Collision,
+    collision::{Collision, OrientedRect},
     colors::{self, Color, ContextExt},
     draw::{
         create_pango_layout::{FontAndLayoutOptions, create_layout_checked},
@@ -8,7 +8,7 @@ use crate::{
 };
 use cairo::Context;
 use geo::Vector2DOps;
-use geo::{Coord, Distance, Euclidean, InterpolatePoint, LineString, Rect};
+use geo::{Coord, Distance, Euclidean, InterpolatePoint, LineString};
 use pangocairo::{
     functions::glyph_string_path,
     pango::{Font, GlyphItem, GlyphString, Layout, SCALE},
@@ -729,7 +729,7 @@ pub fn draw_text_on_line(
     } else {
         label_offsets(total_length, repeat.span, spacing_use, align_mode)
     };
-    let mut new_collision_bboxes: Vec<Rect<f64>> = Vec::new();
+    let mut new_collision_bboxes: Vec<OrientedRect> = Vec::new();
 
     if offsets.is_empty() {
         return Ok(false);
@@ -781,7 +781,7 @@ pub fn draw_text_on_line(
 
             let mut cursor = prepared.cursor_start;
             let mut label_placements = Vec::new();
-            let mut glyph_bboxes: Vec<Rect<f64>> = Vec::new();
+            let mut glyph_bboxes: Vec<OrientedRect> = Vec::new();
             let mut glyph_span_ends: Vec<f64> = Vec::new();
 
             let label_advance_scale = advance_scale;
@@ -905,18 +905,8 @@ pub fn draw_text_on_line(
                 let angle =
                     normalize_angle(weighted_tangent.y.atan2(weighted_tangent.x) + flip_offset);
 
-                // Track an axis-aligned bbox for the rotated glyph.
-                let hw = logical_w / 2.0;
-                let hh = logical_h / 2.0;
-                let cos = angle.cos().abs();
-                let sin = angle.sin().abs();
-                let rx = hw.mul_add(cos, hh * sin);
-                let ry = hw.mul_add(sin, hh * cos);
-
-                glyph_bboxes.push(Rect::new(
-                    (pos.x - rx, pos.y - ry),
-                    (pos.x + rx, pos.y + ry),
-                ));
+                // Rotated box of the glyph cluster so that labels can pack densely along curves.
+                glyph_bboxes.push(OrientedRect::new(pos, logical_w, logical_h, angle));
                 glyph_span_ends.push(span_end);
 
                 label_placements.push((glyph_string.clone(), font.clone(), pos, angle));
@@ -932,7 +922,7 @@ pub fn draw_text_on_line(
                 && let Some((idx, _)) = glyph_bboxes
                     .iter()
                     .enumerate()
-                    .find(|(_, bb)| col.collides(bb))
+                    .find(|(_, bb)| col.collides_oriented(bb))
             {
                 if retries > 0 {
                     retries -= 1;
@@ -962,7 +952,7 @@ pub fn draw_text_on_line(
                 new_collision_bboxes.extend(glyph_bboxes);
             } else if let Some(col) = collision.as_deref_mut() {
                 for bb in glyph_bboxes {
-                    let _ = col.add(bb);
+                    col.add_oriented(bb);
                 }
             }
 
@@ -976,7 +966,7 @@ pub fn draw_text_on_line(
         && let Some(col) = collision
     {
         for bb in new_collision_bboxes.into_iter() {
-            let _ = col.add(bb);
+            col.add_oriented(bb);
         }
     }
 
@@ -0,0 +1,363 @@
+use crate::layers::hillshading_datasets::{DatasetKind, HillshadingDatasets};
+use crate::xyz::lon_lat_to_epsg3857;
+use gdal::{Dataset, errors::GdalError};
+use geo::{Coord, Distance, Haversine, InterpolatePoint, LineString, Point};
+use serde::Serialize;
+use xmltree::{Element, XMLNode};
+
+/// Most points of a profile: lines are thinned to half as many vertices and
+/// long lines sampled more sparsely, to give at most the other half.
+const MAX_PROFILE_POINTS: usize = 10_000;
+
+/// Elevation change ignored by the ascent and descent of a profile, so that
+/// the noise of the elevation model doesn't add up.
+const CLIMB_THRESHOLD: f64 = 3.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ElevationError {
+    #[error("GDAL error: {0}")]
+    Gdal(#[from] GdalError),
+
+    #[error("Error parsing GPX: {0}")]
+    GpxParse(#[from] xmltree::ParseError),
+
+    #[error("Invalid GPX point: {0}")]
+    GpxPoint(String),
+
+    #[error("GPX has no track or route points")]
+    EmptyGpx,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProfilePoint {
+    /// Distance from the start in metres.
+    pub distance: f64,
+    pub lon: f64,
+    pub lat: f64,
+    /// Metres, `None` where no dataset has data.
+    pub elevation: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ElevationProfile {
+    pub points: Vec<ProfilePoint>,
+    /// Total ascent in metres.
+    pub ascent: f64,
+    /// Total descent in metres.
+    pub descent: f64,
+}
+
+impl ElevationProfile {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serialized elevation profile")
+    }
+}
+
+/// Elevations in metres at WGS84 `coords`, from the elevation dataset which
+/// would shade the place: the highest priority one with data there that no
+/// dataset better than it replaces, or else the fallback.
+pub fn elevations(
+    datasets: &mut HillshadingDatasets,
+    coords: &[Coord],
+) -> Result<Vec<Option<f64>>, ElevationError> {
+    let _span = tracy_client::span!("elevation::elevations");
+
+    coords
+        .iter()
+        .map(|coord| elevation_at(datasets, lon_lat_to_epsg3857(coord.x, coord.y)))
+        .collect()
+}
+
+/// Samples the elevation along the WGS84 `line` every `spacing` metres and at
+/// its vertices.
+pub fn elevation_profile(
+    datasets: &mut HillshadingDatasets,
+    line: &LineString,
+    spacing: f64,
+) -> Result<ElevationProfile, ElevationError> {
+    let _span = tracy_client::span!("elevation::elevation_profile");
+
+    let length = haversine_length(line);
+
+    let line = &thin(line, length, MAX_PROFILE_POINTS / 2);
+
+    // every segment has at most one sample more than its length allows
+    let spacing = spacing.max(length / (MAX_PROFILE_POINTS / 2) as f64);
+
+    let mut samples: Vec<(f64, Point)> = Vec::new();
+
+    let mut distance = 0.0;
+
+    for segment in line.lines() {
+        let (start, end) = (segment.start_point(), segment.end_point());
+
+        let segment_length = Haversine.distance(start, end);
+
+        let steps = (segment_length / spacing).ceil().max(1.0) as usize;
+
+        let first = if samples.is_empty() { 0 } else { 1 };
+
+        for step in first..=steps {
+            let ratio = step as f64 / steps as f64;
+
+            samples.push((
+                ratio.mul_add(segment_length, distance),
+                Haversine.point_at_ratio_between(start, end, ratio),
+            ));
+        }
+
+        distance += segment_length;
+    }
+
+    if samples.is_empty()
+        && let Some(&coord) = line.0.first()
+    {
+        samples.push((0.0, coord.into()));
+    }
+
+    let mut points = Vec::with_capacity(samples.len());
+
+    for (distance, point) in samples {
+        points.push(ProfilePoint {
+            distance,
+            lon: point.x(),
+            lat: point.y(),
+            elevation: elevation_at(datasets, lon_lat_to_epsg3857(point.x(), point.y()))?,
+        });
+    }
+
+    let mut ascent = 0.0;
+    let mut descent = 0.0;
+    let mut reference: Option<f64> = None;
+
+    for elevation in points.iter().filter_map(|point| point.elevation) {
+        let Some(last) = reference else {
+            reference = Some(elevation);
+            continue;
+        };
+
+        let change = elevation - last;
+
+        if change.abs() >= CLIMB_THRESHOLD {
+            if change > 0.0 {
+                ascent += change;
+            } else {
+                descent -= change;
+            }
+
+            reference = Some(elevation);
+        }
+    }
+
+    Ok(ElevationProfile {
+        points,
+        ascent,
+        descent,
+    })
+}
+
+fn haversine_length(line: &LineString) -> f64 {
+    line.lines()
+        .map(|segment| Haversine.distance(segment.start_point(), segment.end_point()))
+        .sum()
+}
+
+/// `line` of `length` metres reduced to at most `max_vertices` (at least 2)
+/// vertices: the ends and the first vertex after every
+/// `length / (max_vertices - 2)` metres.
+fn thin(line: &LineString, length: f64, max_vertices: usize) -> LineString {
+    let (Some(&first), Some(&last)) = (line.0.first(), line.0.last()) else {
+        return line.clone();
+    };
+
+    if line.0.len() <= max_vertices {
+        return line.clone();
+    }
+
+    let interval = length / max_vertices.saturating_sub(2).max(1) as f64;
+
+    let mut coords = vec![first];
+
+    let mut distance = 0.0;
+    let mut next = interval;
+
+    for segment in line.lines() {
+        distance += Haversine.distance(segment.start_point(), segment.end_point());
+
+        if distance >= next && distance < length {
+            coords.push(segment.end);
+
+            next = distance + interval;
+        }
+    }
+
+    coords.push(last);
+
+    LineString::new(coords)
+}
+
+/// Track points of a GPX document, or its route points if it has no track,
+/// joined to a single WGS84 line.
+pub fn parse_gpx(input: &[u8]) -> Result<LineString, ElevationError> {
+    let root = Element::parse(input)?;
+
+    let mut coords = Vec::new();
+
+    collect_gpx_points(&root, "trkpt", &mut coords)?;
+
+    if coords.is_empty() {
+        collect_gpx_points(&root, "rtept", &mut coords)?;
+    }
+
+    if coords.is_empty() {
+        return Err(ElevationError::EmptyGpx);
+    }
+
+    Ok(LineString::new(coords))
+}
+
+fn collect_gpx_points(
+    element: &Element,
+    name: &str,
+    coords: &mut Vec<Coord>,
+) -> Result<(), ElevationError> {
+    for child in &element.children {
+        let XMLNode::Element(child) = child else {
+            continue;
+        };
+
+        if child.name != name {
+            collect_gpx_points(child, name, coords)?;
+            continue;
+        }
+
+        let attribute = |key: &str| {
+            child
+                .attributes
+                .get(key)
+                .and_then(|value| value.trim().parse::<f64>().ok())
+                .ok_or_else(|| ElevationError::GpxPoint(format!("{name} without a valid {key}")))
+        };
+
+        coords.push(Coord {
+            x: attribute("lon")?,
+            y: attribute("lat")?,
+        });
+    }
+
+    Ok(())
+}
+
+/// Elevation at the EPSG:3857 `coord` by the rules of [`elevations`].
+fn elevation_at(
+    datasets: &mut HillshadingDatasets,
+    coord: Coord,
+) -> Result<Option<f64>, ElevationError> {
+    let bbox = geo::Rect::new(coord, coord);
+
+    let covering: Vec<_> = datasets
+        .covering(bbox)
+        .into_iter()
+        .filter(|dataset| dataset.kind == DatasetKind::Elevation)
+        .collect();
+
+    let mut values = Vec::with_capacity(covering.len());
+
+    for dataset in &covering {
+        let value = match datasets.get(&dataset.name) {
+            Some(gdal_dataset) => sample(gdal_dataset, coord)?,
+            None => None,
+        };
+
+        values.push((dataset.name.as_str(), value));
+    }
+
+    let has_data = |name: &str| {
+        values
+            .iter()
+            .any(|(other, value)| *other == name && value.is_some())
+    };
+
+    // datasets are in ascending priority
+    for (dataset, (_, value)) in covering.iter().zip(&values).rev() {
+        if let Some(value) = value
+            && !dataset.replaced_by.iter().any(|name| has_data(name))
+        {
+            return Ok(Some(*value));
+        }
+    }
+
+    let Some(fallback) = datasets
+        .fallback(bbox)
+        .filter(|dataset| dataset.kind == DatasetKind::Elevation)
+    else {
+        return Ok(None);
+    };
+
+    match datasets.get(&fallback.name) {
+        Some(gdal_dataset) => sample(gdal_dataset, coord),
+        None => Ok(None),
+    }
+}
+
+/// Bilinear elevation of a single band dataset at the EPSG:3857 `coord`,
+/// nearest next to missing data.
+fn sample(dataset: &Dataset, coord: Coord) -> Result<Option<f64>, ElevationError> {
+    let [gt_x_off, gt_x_width, _, gt_y_off, _, gt_y_height] = dataset.geo_transform()?;
+
+    let (raster_width, raster_height) = dataset.raster_size();
+
+    // relative to the centre of the top left pixel
+    let px = (coord.x - gt_x_off) / gt_x_width - 0.5;
+    let py = (coord.y - gt_y_off) / gt_y_height - 0.5;
+
+    if px < -0.5 || py < -0.5 || px > raster_width as f64 - 0.5 || py > raster_height as f64 - 0.5 {
+        return Ok(None);
+    }
+
+    let x0 = (px.floor().max(0.0) as usize).min(raster_width.saturating_sub(2));
+    let y0 = (py.floor().max(0.0) as usize).min(raster_height.saturating_sub(2));
+
+    let window_width = raster_width.min(2);
+    let window_height = raster_height.min(2);
+
+    let band = dataset.rasterband(1)?;
+
+    let mut buffer = vec![0f64; window_width * window_height];
+
+    band.read_into_slice::<f64>(
+        (x0 as isize, y0 as isize),
+        (window_width, window_height),
+        (window_width, window_height),
+        &mut buffer,
+        None,
+    )?;
+
+    if let Some(no_data) = band.no_data_value() {
+        for value in &mut buffer {
+            if *value == no_data {
+                *value = f64::NAN;
+            }
+        }
+    }
+
+    let fx = (px - x0 as f64).clamp(0.0, (window_width - 1) as f64);
+    let fy = (py - y0 as f64).clamp(0.0, (window_height - 1) as f64);
+
+    let at = |x: usize, y: usize| {
+        buffer[y.min(window_height - 1) * window_width + x.min(window_width - 1)]
+    };
+
+    let [v00, v10, v01, v11] = [at(0, 0), at(1, 0), at(0, 1), at(1, 1)];
+
+    let value = if [v00, v10, v01, v11].iter().any(|value| value.is_nan()) {
+        at(fx.round() as usize, fy.round() as usize)
+    } else {
+        let top = (v10 - v00).mul_add(fx, v00);
+        let bottom = (v11 - v01).mul_add(fx, v01);
+
+        (bottom - top).mul_add(fy, top)
+    };
+
+    Ok((!value.is_nan()).then_some(value))
+}
@@ -7,4 +7,8 @@ pub enum ImageFormat {
     Jpeg,
     Pdf,
     Svg,
+    /// UTFGrid JSON of feature hit areas.
+    UtfGrid,
+    /// Mapbox Vector Tile of the layer query rows.
+    Mvt,
 }
@@ -0,0 +1,136 @@
+use crate::collision::{Collision, OrientedRect};
+use crate::utfgrid::FeatureId;
+use cairo::Context;
+
+/// Priorities of label candidates across layers. Higher ones are placed
+/// first, so that e.g. a hamlet name does not block a town name of a layer
+/// rendered later.
+pub mod priority {
+    pub const CITY: u32 = 100;
+    pub const TOWN: u32 = 90;
+    pub const NATIONAL_PARK: u32 = 85;
+    pub const VILLAGE: u32 = 80;
+    /// Hamlets, suburbs and smaller places.
+    pub const PLACE: u32 = 65;
+    /// POI icons; prominent peaks get a bonus.
+    pub const FEATURE: u32 = 60;
+    pub const SPECIAL_PARK: u32 = 55;
+    /// POI names, placed after the icons of equally important POIs.
+    pub const FEATURE_LABEL: u32 = 52;
+    pub const WATER_AREA: u32 = 50;
+    pub const RIVER: u32 = 45;
+    pub const BUILDING: u32 = 42;
+    pub const PROTECTED_AREA: u32 = 40;
+    pub const LANDCOVER: u32 = 35;
+    pub const LOCALITY: u32 = 30;
+    pub const HIGHWAY: u32 = 25;
+    pub const STREAM: u32 = 22;
+    pub const ROUTE: u32 = 20;
+    pub const AERIALWAY: u32 = 20;
+    pub const HOUSENUMBER: u32 = 10;
+}
+
+type Place<'a> = Box<dyn FnOnce(&mut Collision) -> cairo::Result<()> + 'a>;
+
+struct Candidate<'a> {
+    priority: u32,
+    feature: Option<FeatureId>,
+    place: Place<'a>,
+}
+
+/// Label candidates submitted by the label layers and placed all together
+/// by priority instead of by the order of the layers.
+#[derive(Default)]
+pub struct Labels<'a> {
+    candidates: Vec<Candidate<'a>>,
+}
+
+impl<'a> Labels<'a> {
+    /// Queues a candidate. `place` tries its alternative positions against
+    /// the collision boxes placed so far and draws it at the first free one.
+    /// Boxes it adds become hit areas of `feature`.
+    pub fn submit(
+        &mut self,
+        priority: u32,
+        feature: Option<FeatureId>,
+        place: impl FnOnce(&mut Collision) -> cairo::Result<()> + 'a,
+    ) {
+        self.candidates.push(Candidate {
+            priority,
+            feature,
+            place: Box::new(place),
+        });
+    }
+
+    /// Places the candidates from the highest priority, those of equal
+    /// priority in the order of submission. Placed labels never overlap, so
+    /// drawing them in this order instead of by layer looks the same.
+    pub fn place(mut self, collision: &mut Collision) -> cairo::Result<()> {
+        let _span = tracy_client::span!("labels::place");
+
+        self.candidates
+            .sort_by_key(|candidate| std::cmp::Reverse(candidate.priority));
+
+        for candidate in self.candidates {
+            collision.set_feature(candidate.feature);
+
+            (candidate.place)(collision)?;
+        }
+
+        collision.set_feature(None);
+
+        Ok(())
+    }
+}
+
+/// Distance by which linework is knocked out around the label boxes, which
+/// already include the halo.
+const MASK_BUFFER: f64 = 1.5;
+
+/// Boxes of the placed labels and icons, under which contours, hatching and
+/// route lines are knocked out so that the text stays readable.
+pub struct LabelMask {
+    boxes: Vec<OrientedRect>,
+}
+
+impl LabelMask {
+    pub fn new(collision: &Collision) -> Self {
+        Self {
+            boxes: collision
+                .boxes()
+                .map(|rect| rect.grow(MASK_BUFFER))
+                .collect(),
+        }
+    }
+
+    /// Erases `amount` (0 to 1) of what has been drawn to the current group
+    /// under the labels.
+    pub fn knock_out(&self, context: &Context, amount: f64) -> cairo::Result<()> {
+        if self.boxes.is_empty() {
+            return Ok(());
+        }
+
+        let _span = tracy_client::span!("labels::knock_out");
+
+        context.save()?;
+
+        for rect in &self.boxes {
+            let [first, rest @ ..] = rect.corners();
+
+            context.move_to(first.x, first.y);
+
+            for corner in rest {
+                context.line_to(corner.x, corner.y);
+            }
+
+            context.close_path();
+        }
+
+        context.set_fill_rule(cairo::FillRule::Winding);
+        context.set_operator(cairo::Operator::DestOut);
+        context.set_source_rgba(0.0, 0.0, 0.0, amount);
+        context.fill()?;
+
+        context.restore()
+    }
+}
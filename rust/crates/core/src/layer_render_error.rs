@@ -23,6 +23,12 @@ pub enum LayerRenderError {
 
     #[error("Cairo borrow error: {0}")]
     CairoBorrowError(#[from] cairo::BorrowError),
+
+    #[error("Hillshading dataset {0} is unknown or can't be opened")]
+    UnavailableDataset(String),
+
+    #[error("Unsupported hillshading raster: {0}")]
+    UnsupportedRaster(String),
 }
 
 pub type LayerRenderResult = Result<(), LayerRenderError>;
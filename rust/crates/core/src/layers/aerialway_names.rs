@@ -1,17 +1,17 @@
 use crate::{
-    collision::Collision,
     colors,
     ctx::Ctx,
     draw::{
         offset_line::offset_line_string,
         text_on_line::{Align, Distribution, Repeat, TextOnLineOptions, draw_text_on_line},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_line_string},
 };
 use postgres::Client;
 
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("aerialway_names::render");
 
     let sql = concat!(
@@ -21,6 +21,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(512.0)).as_params())?;
 
+    if ctx.emit_rows("aerialway_names", &rows, &["name"]) {
+        return Ok(());
+    }
+
     let options = TextOnLineOptions {
         distribution: Distribution::Align {
             align: Align::Center,
@@ -31,13 +35,17 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
     };
 
     for row in rows {
-        let name: &str = row.get("name");
+        let name: String = row.get("name");
 
         let geom = geometry_line_string(&row).project_to_tile(&ctx.tile_projector);
 
         let geom = offset_line_string(&geom, 10.0);
 
-        draw_text_on_line(ctx.context, &geom, name, Some(collision), &options)?;
+        labels.submit(priority::AERIALWAY, None, move |collision| {
+            draw_text_on_line(ctx.context, &geom, &name, Some(collision), &options)?;
+
+            Ok(())
+        });
     }
 
     Ok(())
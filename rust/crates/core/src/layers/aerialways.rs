@@ -16,6 +16,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(10.0)).as_params())?;
 
+    if ctx.emit_rows("aerialways", &rows, &["type"]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     context.save()?;
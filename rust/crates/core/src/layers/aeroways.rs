@@ -24,6 +24,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(12.0)).as_params())?;
 
+    if ctx.emit_rows("aeroways", &rows, &["type"]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     context.save()?;
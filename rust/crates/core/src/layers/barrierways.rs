@@ -20,6 +20,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
         .query(sql, &ctx.bbox_query_params(Some(8.0)).as_params())
         ?;
 
+    if ctx.emit_rows("barrierways", &rows, &["type"]) {
+        return Ok(());
+    }
+
     for row in rows {
         let context = ctx.context;
 
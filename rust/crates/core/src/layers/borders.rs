@@ -17,6 +17,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(10.0)).as_params())?;
 
+    if ctx.emit_rows("borders", &rows, &[]) {
+        return Ok(());
+    }
+
     ctx.context.push_group();
 
     let context = ctx.context;
@@ -20,6 +20,11 @@ pub fn render(ctx: &Ctx, client: &mut Client, mask: bool) -> LayerRenderResult {
         .query(query, &ctx.bbox_query_params(None).as_params())
         ?;
 
+    // the mask pass only cuts contours
+    if !mask && ctx.emit_rows("bridge_areas", &rows, &[]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     context.save()?;
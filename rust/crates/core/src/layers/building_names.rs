@@ -1,13 +1,13 @@
 use crate::{
-    collision::Collision,
     ctx::Ctx,
     draw::text::{TextOptions, draw_text},
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_point},
 };
 use postgres::Client;
 
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("building_names::render");
 
     let sql = "
@@ -37,14 +37,26 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(1024.0)).as_params())?;
 
+    if ctx.emit_rows("building_names", &rows, &["name"]) {
+        return Ok(());
+    }
+
     for row in rows {
-        draw_text(
-            ctx.context,
-            Some(collision),
-            &geometry_point(&row).project_to_tile(&ctx.tile_projector),
-            row.get("name"),
-            &TextOptions::default(),
-        )?;
+        let point = geometry_point(&row).project_to_tile(&ctx.tile_projector);
+
+        let name: String = row.get("name");
+
+        labels.submit(priority::BUILDING, None, move |collision| {
+            draw_text(
+                ctx.context,
+                Some(collision),
+                &point,
+                &name,
+                &TextOptions::default(),
+            )?;
+
+            Ok(())
+        });
     }
 
     Ok(())
@@ -19,6 +19,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
         .query(sql, &ctx.bbox_query_params(None).as_params())
         ?;
 
+    if ctx.emit_rows("buildings", &rows, &["type"]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     context.save()?;
@@ -8,31 +8,205 @@ use crate::{
             Align, Distribution, Repeat, TextOnLineOptions, Upright, draw_text_on_line,
         },
     },
-    layer_render_error::LayerRenderResult,
+    layer_render_error::{LayerRenderError, LayerRenderResult},
+    layers::dem::Elevation,
     projectable::{TileProjectable, geometry_line_string},
 };
+use geo::{LineString, Rect, Simplify};
 use postgres::Client;
 
-pub fn render(ctx: &Ctx, client: &mut Client, country: Option<&str>) -> LayerRenderResult {
+/// Contour interval requested by the client.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContourInterval {
+    Metres(u32),
+    Feet(u32),
+}
+
+const METRES_PER_FOOT: f64 = 0.3048;
+
+/// Contour table of the areas without a dataset table.
+pub const DEFAULT_TABLE: &str = "cont_dmr_split";
+
+/// Tolerance of the simplification of traced contours in pixels.
+const SIMPLIFY_TOLERANCE: f64 = 0.5;
+
+/// Most contour levels traced in a tile; finer intervals are coarsened five
+/// times until within it, so that index contours stay index contours.
+const MAX_LEVELS: f64 = 200.0;
+
+impl ContourInterval {
+    /// Parses e.g. `20`, `20m` or `50ft`. Zero, values above `i32::MAX` and
+    /// other values are `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+
+        let interval = if let Some(feet) = value.strip_suffix("ft") {
+            Self::Feet(feet.parse().ok()?)
+        } else {
+            Self::Metres(value.strip_suffix('m').unwrap_or(value).parse().ok()?)
+        };
+
+        (1..=i32::MAX as u32)
+            .contains(&interval.step())
+            .then_some(interval)
+    }
+
+    const fn step(self) -> u32 {
+        match self {
+            Self::Metres(step) | Self::Feet(step) => step,
+        }
+    }
+
+    /// Metres per unit.
+    const fn unit(self) -> f64 {
+        match self {
+            Self::Metres(_) => 1.0,
+            Self::Feet(_) => METRES_PER_FOOT,
+        }
+    }
+
+    /// Interval of contours traced from elevation when none is requested,
+    /// matching the density of the contour tables.
+    const fn for_zoom(zoom: u32) -> Self {
+        Self::Metres(match zoom {
+            13 | 14 => 20,
+            _ => 10,
+        })
+    }
+}
+
+/// Where contours come from.
+pub enum ContourSource<'a> {
+    /// Contour table, [`DEFAULT_TABLE`] if `None`.
+    Table(Option<&'a str>),
+    /// Traced from elevation at the requested interval.
+    Elevation(&'a Elevation, Option<ContourInterval>),
+}
+
+/// Contour line in tile pixels.
+struct Contour {
+    /// Elevation in the unit of the interval.
+    height: i32,
+    width: f64,
+    labelled: bool,
+    geometry: LineString,
+}
+
+pub fn render(ctx: &Ctx, client: &mut Client, source: ContourSource) -> LayerRenderResult {
     let _span = tracy_client::span!("contours::render");
 
-    let context = ctx.context;
+    if ctx.zoom < 12 {
+        return Ok(());
+    }
+
+    let contours = match source {
+        ContourSource::Table(table) => {
+            let Some(contours) = query(ctx, client, table)? else {
+                return Ok(());
+            };
+
+            contours
+        }
+        ContourSource::Elevation(elevation, interval) => {
+            // vector tiles carry the contour tables only
+            if ctx.mvt.is_some() {
+                return Ok(());
+            }
+
+            trace(
+                ctx,
+                elevation,
+                interval.unwrap_or(ContourInterval::for_zoom(ctx.zoom)),
+            )
+        }
+    };
+
+    draw(ctx, &contours)
+}
+
+/// Contours of `table`, `None` if the rows were emitted to a vector tile.
+fn query(
+    ctx: &Ctx,
+    client: &mut Client,
+    table: Option<&str>,
+) -> Result<Option<Vec<Contour>>, LayerRenderError> {
     let zoom = ctx.zoom;
 
-    if zoom < 12 {
-        return Ok(());
+    let mut params = ctx.bbox_query_params(Some(8.0));
+
+    params.push(simplify_factor(zoom));
+
+    let query_params = params.as_params();
+
+    let rows = client.query(&query_sql(zoom, table), &query_params)?;
+
+    if ctx.emit_rows("contours", &rows, &["height_m"]) {
+        return Ok(None);
     }
 
-    let simplify_factor: f64 = match zoom {
+    Ok(Some(
+        rows.iter()
+            .map(|row| {
+                let height: i16 = row.get("height_m");
+
+                Contour {
+                    height: height as i32,
+                    width: row.get("width"),
+                    labelled: match zoom {
+                        13..=14 => height % 100 == 0,
+                        15.. => height % 50 == 0,
+                        _ => false,
+                    },
+                    geometry: geometry_line_string(row).project_to_tile(&ctx.tile_projector),
+                }
+            })
+            .collect(),
+    ))
+}
+
+/// Contours of `table` drawn at `zoom` in `bbox`, as height in metres and
+/// EPSG:3857 geometry.
+pub fn query_lines(
+    client: &mut Client,
+    bbox: Rect,
+    zoom: u32,
+    table: Option<&str>,
+) -> Result<Vec<(i16, LineString)>, postgres::Error> {
+    let min = bbox.min();
+    let max = bbox.max();
+
+    let rows = client.query(
+        &query_sql(zoom, table),
+        &[
+            &min.x,
+            &min.y,
+            &max.x,
+            &max.y,
+            &0.0f64,
+            &simplify_factor(zoom),
+        ],
+    )?;
+
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("height_m"), geometry_line_string(row)))
+        .collect())
+}
+
+/// Area tolerance of `ST_SimplifyVW` at `zoom`.
+const fn simplify_factor(zoom: u32) -> f64 {
+    match zoom {
         ..=12 => 2000.0,
         13 => 1000.0,
         14 => 200.0,
         15 => 50.0,
         _ => 0.0,
-    };
-
-    context.save()?;
+    }
+}
 
+/// Query of the contours drawn at `zoom` in the bbox `$1`…`$4` expanded by
+/// `$5`, simplified by `$6`.
+fn query_sql(zoom: u32, table: Option<&str>) -> String {
     // TODO measure performance impact of simplification, if it makes something faster
     let width_case = match zoom {
         12 => "CASE WHEN height_m % 50 = 0 THEN 0.2 ELSE 0.0 END",
@@ -53,7 +227,7 @@ pub fn render(ctx: &Ctx, client: &mut Client, country: Option<&str>) -> LayerRen
         }
     };
 
-    let sql = format!(
+    format!(
         "WITH contours AS (
             SELECT
                 ST_SimplifyVW(wkb_geometry, $6) AS geometry,
@@ -64,49 +238,107 @@ pub fn render(ctx: &Ctx, client: &mut Client, country: Option<&str>) -> LayerRen
                 wkb_geometry && ST_Expand(ST_MakeEnvelope($1, $2, $3, $4, 3857), $5)
         )
         SELECT geometry, height_m, width FROM contours WHERE width > 0",
-        &(if let Some(country) = country {
-            format!("contour_{country}_split")
-        } else {
-            "cont_dmr_split".to_string()
-        })
-    );
+        table.unwrap_or(DEFAULT_TABLE)
+    )
+}
 
-    let mut params = ctx.bbox_query_params(Some(8.0));
+/// Traces contours every `interval` and thicker index contours every fifth
+/// one, which are the only ones at zoom 12 and are labelled from zoom 13.
+fn trace(ctx: &Ctx, elevation: &Elevation, interval: ContourInterval) -> Vec<Contour> {
+    let _span = tracy_client::span!("contours::trace");
 
-    params.push(simplify_factor);
+    let Some((min, max)) = elevation.range() else {
+        return Vec::new();
+    };
 
-    let query_params = params.as_params();
+    let zoom = ctx.zoom;
+
+    let unit = interval.unit();
 
-    let rows = client.query(&sql, &query_params)?;
+    let elevation = elevation.smoothed();
 
-    for row in rows {
-        let height: i16 = row.get("height_m");
+    let mut contours = Vec::new();
 
-        let width: f64 = row.get("width");
+    for (index, height) in levels(min, max, interval) {
+        let major = index % 5 == 0;
 
-        let labels = match zoom {
-            13..=14 => height % 100 == 0,
-            15.. => height % 50 == 0,
-            _ => false,
+        let width = match (zoom, major) {
+            (..=12, false) => continue,
+            (..=12, true) => 0.2,
+            (13 | 14, false) => 0.2,
+            (13 | 14, true) => 0.4,
+            (_, false) => 0.3,
+            (_, true) => 0.6,
         };
 
-        let geom = geometry_line_string(&row).project_to_tile(&ctx.tile_projector);
+        for geometry in elevation.isolines(height as f64 * unit) {
+            // less points for the spline, which smooths the rest
+            let geometry = geometry.simplify(&SIMPLIFY_TOLERANCE);
+
+            contours.push(Contour {
+                height,
+                width,
+                labelled: major && zoom >= 13,
+                geometry,
+            });
+        }
+    }
+
+    contours
+}
+
+/// Levels as `(index, height)` between `min` and `max` metres, every
+/// `interval` or a fifth-multiple of it so that there are at most
+/// [`MAX_LEVELS`]. Heights out of `i32` are left out, e.g. of bogus
+/// elevations.
+fn levels(min: f32, max: f32, interval: ContourInterval) -> Vec<(i32, i32)> {
+    let unit = interval.unit();
+
+    let mut step = i64::from(interval.step());
+
+    while step <= i64::from(i32::MAX) && f64::from(max - min) / unit / step as f64 > MAX_LEVELS {
+        step *= 5;
+    }
+
+    if step > i64::from(i32::MAX) {
+        return Vec::new();
+    }
+
+    let first = (f64::from(min) / unit / step as f64).ceil() as i64;
+    let last = (f64::from(max) / unit / step as f64).floor() as i64;
+
+    (first..=last)
+        .filter_map(|index| {
+            let height = i32::try_from(index.checked_mul(step)?).ok()?;
+
+            Some((index as i32, height))
+        })
+        .collect()
+}
+
+fn draw(ctx: &Ctx, contours: &[Contour]) -> LayerRenderResult {
+    let context = ctx.context;
+
+    context.save()?;
+
+    for contour in contours {
+        let geom = &contour.geometry;
 
         context.set_dash(&[], 0.0);
 
-        context.set_line_width(width);
+        context.set_line_width(contour.width);
 
         context.set_source_color(colors::CONTOUR);
 
-        path_smooth_bezier_spline(context, &geom, 1.0);
+        path_smooth_bezier_spline(context, geom, 1.0);
 
         context.stroke()?;
 
-        if labels {
+        if contour.labelled {
             let _drawn = draw_text_on_line(
                 context,
-                &geom,
-                &format!("{}", height),
+                geom,
+                &format!("{}", contour.height),
                 None,
                 &TextOnLineOptions {
                     flo: FontAndLayoutOptions::default(),
@@ -126,3 +358,60 @@ pub fn render(ctx: &Ctx, client: &mut Client, country: Option<&str>) -> LayerRen
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ContourInterval, levels};
+
+    #[test]
+    fn parse_interval() {
+        assert_eq!(
+            ContourInterval::parse("20"),
+            Some(ContourInterval::Metres(20))
+        );
+        assert_eq!(
+            ContourInterval::parse(" 20m "),
+            Some(ContourInterval::Metres(20))
+        );
+        assert_eq!(
+            ContourInterval::parse("50ft"),
+            Some(ContourInterval::Feet(50))
+        );
+        assert_eq!(
+            ContourInterval::parse("2147483647"),
+            Some(ContourInterval::Metres(2_147_483_647))
+        );
+
+        for value in ["0", "0ft", "-10", "2147483648", "4294967295ft", "10km", ""] {
+            assert_eq!(ContourInterval::parse(value), None, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn levels_in_range() {
+        assert_eq!(
+            levels(95.0, 160.0, ContourInterval::Metres(20)),
+            vec![(5, 100), (6, 120), (7, 140), (8, 160)]
+        );
+
+        // at most MAX_LEVELS, at a fifth-multiple of the interval
+        assert_eq!(
+            levels(0.0, 10_000.0, ContourInterval::Metres(10)).len(),
+            201
+        );
+    }
+
+    #[test]
+    fn levels_of_bogus_elevations() {
+        // heights beyond i32 are not traced instead of overflowing
+        assert!(levels(1e10, 1e10, ContourInterval::Metres(10)).is_empty());
+        assert!(levels(-1e10, -1e10, ContourInterval::Feet(10)).is_empty());
+
+        // only those of them in `i32`
+        let levels = levels(-1e10, 1e10, ContourInterval::Metres(10));
+
+        assert_eq!(levels.first(), Some(&(-4, -1_953_125_000)));
+        assert_eq!(levels.last(), Some(&(4, 1_953_125_000)));
+        assert_eq!(levels.len(), 9);
+    }
+}
@@ -60,6 +60,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(128.0)).as_params())?;
 
+    if ctx.emit_rows("country_names", &rows, &["name", "name:en"]) {
+        return Ok(());
+    }
+
     for row in rows {
         let name: &str = row.get("name");
 
@@ -19,6 +19,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
         .query(sql, &ctx.bbox_query_params(Some(8.0)).as_params())
         ?;
 
+    if ctx.emit_rows("cutlines", &rows, &[]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     context.save()?;
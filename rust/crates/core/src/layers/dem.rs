@@ -0,0 +1,676 @@
+use crate::{
+    colors::{self, Color},
+    ctx::Ctx,
+    layer_render_error::LayerRenderError,
+    layers::raster_level::RasterLevel,
+    xyz::ground_scale,
+};
+use cairo::{Format, ImageSurface};
+use gdal::{Dataset, raster::ResampleAlg};
+use geo::{Coord, LineString, Rect};
+use std::collections::HashMap;
+
+/// Elevation in metres sampled at the pixels of the tile, extended by a
+/// border so that neighbourhood operations work at the tile edges.
+pub struct Elevation {
+    /// Width including the border.
+    pub width: usize,
+    /// Height including the border.
+    pub height: usize,
+    pub border: usize,
+    /// Row major, `NaN` where there is no data.
+    pub data: Vec<f32>,
+    /// Ground distance between neighbouring samples in metres.
+    pub cell_size: f64,
+    /// Samples per tile pixel.
+    pub scale: f64,
+}
+
+/// Directions of the horizon scanned for the sky-view factor.
+const SKY_VIEW_DIRECTIONS: usize = 8;
+
+/// Edge of the sample grid crossed by an isoline: vertical (to the sample
+/// below) or not (to the sample on the right), and its first sample.
+type GridEdge = (bool, usize, usize);
+
+impl Elevation {
+    pub fn get(&self, x: usize, y: usize) -> f32 {
+        self.data[y * self.width + x]
+    }
+
+    /// Elevation change per ground metre eastwards and northwards by the
+    /// Horn method, `None` at the border or next to missing data.
+    pub fn gradient(&self, x: usize, y: usize) -> Option<(f64, f64)> {
+        if x == 0 || y == 0 || x + 1 >= self.width || y + 1 >= self.height {
+            return None;
+        }
+
+        let z = |dx: usize, dy: usize| self.get(x + dx - 1, y + dy - 1) as f64;
+
+        let [a, b, c] = [z(0, 0), z(1, 0), z(2, 0)];
+        let [d, f] = [z(0, 1), z(2, 1)];
+        let [g, h, i] = [z(0, 2), z(1, 2), z(2, 2)];
+
+        let dz_dx = ((c + 2.0 * f + i) - (a + 2.0 * d + g)) / (8.0 * self.cell_size);
+
+        // rows go southwards
+        let dz_dy = ((a + 2.0 * b + c) - (g + 2.0 * h + i)) / (8.0 * self.cell_size);
+
+        (dz_dx.is_finite() && dz_dy.is_finite()).then_some((dz_dx, dz_dy))
+    }
+
+    /// Slope in degrees.
+    pub fn slope(&self, x: usize, y: usize, z_factor: f64) -> Option<f64> {
+        self.gradient(x, y)
+            .map(|(dz_dx, dz_dy)| (z_factor * dz_dx.hypot(dz_dy)).atan().to_degrees())
+    }
+
+    /// Paints the samples inside the border to an image of the tile size,
+    /// colouring each by `pixel` (colour and opacity, `None` for transparent).
+    pub fn to_surface(
+        &self,
+        mut pixel: impl FnMut(usize, usize) -> Option<(Color, f64)>,
+    ) -> Result<ImageSurface, LayerRenderError> {
+        let width = self.width - 2 * self.border;
+        let height = self.height - 2 * self.border;
+
+        let mut data = vec![0u8; width * height * 4];
+
+        for y in 0..height {
+            for x in 0..width {
+                let Some(((r, g, b), alpha)) = pixel(x + self.border, y + self.border) else {
+                    continue;
+                };
+
+                let alpha = alpha.clamp(0.0, 1.0);
+
+                let premultiply = |value: i64| (value as f64 * alpha).round() as u8;
+
+                let index = (y * width + x) * 4;
+
+                data[index] = premultiply(b);
+                data[index + 1] = premultiply(g);
+                data[index + 2] = premultiply(r);
+                data[index + 3] = (alpha * 255.0).round() as u8;
+            }
+        }
+
+        Ok(ImageSurface::create_for_data(
+            data,
+            Format::ARgb32,
+            width as i32,
+            height as i32,
+            width as i32 * 4,
+        )?)
+    }
+
+    /// Lowest and highest elevation, `None` if there is no data.
+    pub fn range(&self) -> Option<(f32, f32)> {
+        self.data
+            .iter()
+            .filter(|value| !value.is_nan())
+            .fold(None, |range, &value| {
+                Some(range.map_or((value, value), |(min, max): (f32, f32)| {
+                    (min.min(value), max.max(value))
+                }))
+            })
+    }
+
+    /// Nearest sample at the grid position, `None` outside of the grid.
+    fn get_nearest(&self, x: f64, y: f64) -> Option<f32> {
+        let (x, y) = (x.round(), y.round());
+
+        (x >= 0.0 && y >= 0.0 && x < self.width as f64 && y < self.height as f64)
+            .then(|| self.get(x as usize, y as usize))
+    }
+
+    /// Whether the terrain towards light from `azimuth` and `altitude` in
+    /// degrees casts a shadow on the sample, with elevation exaggerated by
+    /// `z_factor`. The ray is marched until it rises above `max_height` or
+    /// leaves the grid, so shadows are as long as the border allows.
+    pub fn in_shadow(
+        &self,
+        x: usize,
+        y: usize,
+        azimuth: f64,
+        altitude: f64,
+        z_factor: f64,
+        max_height: f32,
+    ) -> bool {
+        let height = self.get(x, y);
+
+        if height.is_nan() {
+            return false;
+        }
+
+        let (sin_az, cos_az) = azimuth.to_radians().sin_cos();
+
+        // elevation of the ray gained per sample
+        let rise = altitude.to_radians().tan() * self.cell_size / z_factor;
+
+        let mut ray = height as f64;
+
+        for step in 1.. {
+            ray += rise;
+
+            if ray > max_height as f64 {
+                return false;
+            }
+
+            // rows go southwards
+            let Some(terrain) = self.get_nearest(
+                sin_az.mul_add(step as f64, x as f64),
+                cos_az.mul_add(-(step as f64), y as f64),
+            ) else {
+                return false;
+            };
+
+            if terrain as f64 > ray {
+                return true;
+            }
+        }
+
+        false
+    }
+
+    /// Share of the sky visible from the sample, 1 on flat terrain and less
+    /// in valleys and gullies, from the horizon in [`SKY_VIEW_DIRECTIONS`]
+    /// directions within `radius` samples.
+    pub fn sky_view(&self, x: usize, y: usize, radius: usize, z_factor: f64) -> f64 {
+        let height = self.get(x, y);
+
+        if height.is_nan() {
+            return 1.0;
+        }
+
+        let occlusion: f64 = (0..SKY_VIEW_DIRECTIONS)
+            .map(|direction| {
+                let (dx, dy) = (std::f64::consts::TAU * direction as f64
+                    / SKY_VIEW_DIRECTIONS as f64)
+                    .sin_cos();
+
+                let mut horizon = 0.0f64;
+
+                for step in 1..=radius {
+                    let Some(terrain) = self.get_nearest(
+                        dx.mul_add(step as f64, x as f64),
+                        dy.mul_add(step as f64, y as f64),
+                    ) else {
+                        break;
+                    };
+
+                    if terrain.is_nan() {
+                        continue;
+                    }
+
+                    horizon = horizon
+                        .max((terrain - height) as f64 * z_factor / (step as f64 * self.cell_size));
+                }
+
+                // sine of the horizon angle
+                horizon / horizon.hypot(1.0)
+            })
+            .sum();
+
+        1.0 - occlusion / SKY_VIEW_DIRECTIONS as f64
+    }
+
+    /// Averages every sample with its neighbours, which removes the noise of
+    /// the terrain from isolines.
+    pub fn smoothed(&self) -> Self {
+        let mut data = self.data.clone();
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.get(x, y).is_nan() {
+                    continue;
+                }
+
+                let (sum, count) = (y.saturating_sub(1)..(y + 2).min(self.height))
+                    .flat_map(|ny| {
+                        (x.saturating_sub(1)..(x + 2).min(self.width)).map(move |nx| (nx, ny))
+                    })
+                    .map(|(nx, ny)| self.get(nx, ny))
+                    .filter(|value| !value.is_nan())
+                    .fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+
+                data[y * self.width + x] = sum / count as f32;
+            }
+        }
+
+        Self { data, ..*self }
+    }
+
+    /// Traces the isolines of `level` by marching squares, in tile pixels.
+    /// Lines end where data is missing.
+    pub fn isolines(&self, level: f64) -> Vec<LineString> {
+        let level = level as f32;
+
+        let above = |x: usize, y: usize| self.get(x, y) >= level;
+
+        let mut segments = Vec::<[GridEdge; 2]>::new();
+
+        for y in 0..self.height - 1 {
+            for x in 0..self.width - 1 {
+                let corners = [
+                    self.get(x, y),
+                    self.get(x + 1, y),
+                    self.get(x + 1, y + 1),
+                    self.get(x, y + 1),
+                ];
+
+                if corners.iter().any(|value| value.is_nan()) {
+                    continue;
+                }
+
+                let case = (above(x, y) as u8) << 3
+                    | (above(x + 1, y) as u8) << 2
+                    | (above(x + 1, y + 1) as u8) << 1
+                    | above(x, y + 1) as u8;
+
+                let top = (false, x, y);
+                let bottom = (false, x, y + 1);
+                let left = (true, x, y);
+                let right = (true, x + 1, y);
+
+                // saddles are resolved by the average of the corners
+                let center_above = corners.iter().sum::<f32>() / 4.0 >= level;
+
+                match case {
+                    1 | 14 => segments.push([left, bottom]),
+                    2 | 13 => segments.push([bottom, right]),
+                    3 | 12 => segments.push([left, right]),
+                    4 | 11 => segments.push([top, right]),
+                    6 | 9 => segments.push([top, bottom]),
+                    7 | 8 => segments.push([left, top]),
+                    5 if center_above => segments.extend([[left, top], [bottom, right]]),
+                    5 => segments.extend([[top, right], [left, bottom]]),
+                    10 if center_above => segments.extend([[top, right], [left, bottom]]),
+                    10 => segments.extend([[left, top], [bottom, right]]),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut by_edge = HashMap::<GridEdge, Vec<usize>>::new();
+
+        for (index, segment) in segments.iter().enumerate() {
+            for edge in segment {
+                by_edge.entry(*edge).or_default().push(index);
+            }
+        }
+
+        let point = |(vertical, x, y): GridEdge| {
+            let (nx, ny) = if vertical { (x, y + 1) } else { (x + 1, y) };
+
+            let a = self.get(x, y);
+            let b = self.get(nx, ny);
+
+            let t = ((level - a) / (b - a)) as f64;
+
+            let sample_x = (nx as f64 - x as f64).mul_add(t, x as f64);
+            let sample_y = (ny as f64 - y as f64).mul_add(t, y as f64);
+
+            Coord {
+                x: (sample_x - self.border as f64 + 0.5) / self.scale,
+                y: (sample_y - self.border as f64 + 0.5) / self.scale,
+            }
+        };
+
+        let mut used = vec![false; segments.len()];
+
+        // open lines are traced from their ends, the rest are closed
+        let starts: Vec<_> = by_edge
+            .iter()
+            .filter(|(_, indices)| indices.len() == 1)
+            .map(|(edge, indices)| (*edge, indices[0]))
+            .chain(
+                segments
+                    .iter()
+                    .enumerate()
+                    .map(|(index, segment)| (segment[0], index)),
+            )
+            .collect();
+
+        let mut lines = Vec::new();
+
+        for (start, first) in starts {
+            if used[first] {
+                continue;
+            }
+
+            let mut coords = vec![point(start)];
+
+            let mut edge = start;
+            let mut index = first;
+
+            loop {
+                used[index] = true;
+
+                let [a, b] = segments[index];
+
+                edge = if a == edge { b } else { a };
+
+                coords.push(point(edge));
+
+                let Some(&next) = by_edge[&edge].iter().find(|&&next| !used[next]) else {
+                    break;
+                };
+
+                index = next;
+            }
+
+            lines.push(LineString::new(coords));
+        }
+
+        lines
+    }
+
+    /// Opaque where there is data.
+    pub fn data_mask(&self) -> Result<ImageSurface, LayerRenderError> {
+        self.to_surface(|x, y| (!self.get(x, y).is_nan()).then_some((colors::WHITE, 1.0)))
+    }
+}
+
+/// Reads the elevation of the tile at `raster_scale` from a single band
+/// dataset, with `border` extra samples on every side. Returns `None` if the
+/// dataset has no data there.
+pub fn read_elevation(
+    dataset: &Dataset,
+    ctx: &Ctx,
+    raster_scale: f64,
+    border: usize,
+) -> Result<Option<Elevation>, LayerRenderError> {
+    let tile_width = (ctx.size.width as f64 * raster_scale) as usize;
+    let tile_height = (ctx.size.height as f64 * raster_scale) as usize;
+
+    let elevation = read_elevation_grid(dataset, ctx.bbox, tile_width, tile_height, border)?;
+
+    Ok(elevation.map(|elevation| Elevation {
+        scale: raster_scale,
+        ..elevation
+    }))
+}
+
+/// Reads the elevation of `bbox` sampled to a `tile_width` × `tile_height`
+/// grid with `border` extra samples on every side.
+pub fn read_elevation_grid(
+    dataset: &Dataset,
+    bbox: Rect,
+    tile_width: usize,
+    tile_height: usize,
+    border: usize,
+) -> Result<Option<Elevation>, LayerRenderError> {
+    let _span = tracy_client::span!("dem::read_elevation");
+
+    let width = tile_width + 2 * border;
+    let height = tile_height + 2 * border;
+
+    // EPSG:3857 units per sample
+    let pixel_size = bbox.width() / tile_width as f64;
+
+    let level = RasterLevel::for_pixel_size(dataset, pixel_size)?;
+
+    let [gt_x_off, gt_x_width, _, gt_y_off, _, gt_y_height] = level.geo_transform;
+
+    let (raster_width, raster_height) = level.size;
+
+    // raster position of the top left corner of the sample grid and the
+    // raster pixels per sample
+    let origin_x = (border as f64).mul_add(-pixel_size, bbox.min().x - gt_x_off) / gt_x_width;
+    let origin_y = (border as f64).mul_add(pixel_size, bbox.max().y - gt_y_off) / gt_y_height;
+    let step_x = pixel_size / gt_x_width;
+    let step_y = pixel_size / -gt_y_height;
+
+    let window_x = origin_x.floor().max(0.0) as isize;
+    let window_y = origin_y.floor().max(0.0) as isize;
+
+    let window_end_x =
+        ((width as f64).mul_add(step_x, origin_x).ceil() as isize).min(raster_width as isize);
+    let window_end_y =
+        ((height as f64).mul_add(step_y, origin_y).ceil() as isize).min(raster_height as isize);
+
+    if window_end_x <= window_x || window_end_y <= window_y {
+        return Ok(None);
+    }
+
+    let window_width = (window_end_x - window_x) as usize;
+    let window_height = (window_end_y - window_y) as usize;
+
+    // downsampled by GDAL from the chosen level, never upsampled
+    let buffer_width = ((window_width as f64 / step_x).ceil() as usize).clamp(1, window_width);
+    let buffer_height = ((window_height as f64 / step_y).ceil() as usize).clamp(1, window_height);
+
+    let band = level.band(1)?;
+
+    let no_data = band.no_data_value();
+
+    let mut buffer = vec![0f32; buffer_width * buffer_height];
+
+    band.read_into_slice::<f32>(
+        (window_x, window_y),
+        (window_width, window_height),
+        (buffer_width, buffer_height),
+        &mut buffer,
+        Some(ResampleAlg::Average),
+    )?;
+
+    if let Some(no_data) = no_data {
+        for value in &mut buffer {
+            if *value as f64 == no_data {
+                *value = f32::NAN;
+            }
+        }
+    }
+
+    // raster pixels per buffer pixel
+    let buffer_scale_x = window_width as f64 / buffer_width as f64;
+    let buffer_scale_y = window_height as f64 / buffer_height as f64;
+
+    let sample = |bx: f64, by: f64| -> f32 {
+        if bx < -0.5
+            || by < -0.5
+            || bx > buffer_width as f64 - 0.5
+            || by > buffer_height as f64 - 0.5
+        {
+            return f32::NAN;
+        }
+
+        let bx = bx.clamp(0.0, (buffer_width - 1) as f64);
+        let by = by.clamp(0.0, (buffer_height - 1) as f64);
+
+        let x0 = bx.floor() as usize;
+        let y0 = by.floor() as usize;
+        let x1 = (x0 + 1).min(buffer_width - 1);
+        let y1 = (y0 + 1).min(buffer_height - 1);
+
+        let fx = (bx - x0 as f64) as f32;
+        let fy = (by - y0 as f64) as f32;
+
+        let at = |x: usize, y: usize| buffer[y * buffer_width + x];
+
+        let [v00, v10, v01, v11] = [at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1)];
+
+        if [v00, v10, v01, v11].iter().any(|value| value.is_nan()) {
+            // nearest at data edges
+            return at(bx.round() as usize, by.round() as usize);
+        }
+
+        let top = (v10 - v00).mul_add(fx, v00);
+        let bottom = (v11 - v01).mul_add(fx, v01);
+
+        (bottom - top).mul_add(fy, top)
+    };
+
+    let mut data = vec![f32::NAN; width * height];
+
+    let mut has_data = false;
+
+    for y in 0..height {
+        let raster_y = (y as f64 + 0.5).mul_add(step_y, origin_y);
+        let by = (raster_y - window_y as f64) / buffer_scale_y - 0.5;
+
+        for x in 0..width {
+            let raster_x = (x as f64 + 0.5).mul_add(step_x, origin_x);
+            let bx = (raster_x - window_x as f64) / buffer_scale_x - 0.5;
+
+            let value = sample(bx, by);
+
+            has_data |= !value.is_nan();
+
+            data[y * width + x] = value;
+        }
+    }
+
+    if !has_data {
+        return Ok(None);
+    }
+
+    Ok(Some(Elevation {
+        width,
+        height,
+        border,
+        data,
+        cell_size: pixel_size * ground_scale(bbox.center().y),
+        scale: 1.0,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Elevation;
+    use geo::Coord;
+
+    fn elevation(width: usize, height: usize, value: impl Fn(usize, usize) -> f32) -> Elevation {
+        Elevation {
+            width,
+            height,
+            border: 0,
+            data: (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| value(x, y))
+                .collect(),
+            cell_size: 1.0,
+            scale: 1.0,
+        }
+    }
+
+    /// Every corner configuration of a cell: the isolines cross exactly the
+    /// edges between a corner above and one below the level, at their middle.
+    #[test]
+    fn isolines_case_table() {
+        // clockwise from the top left, as in the case index
+        let corners = [(0, 0), (1, 0), (1, 1), (0, 1)];
+
+        for case in 0..16u8 {
+            let above = |corner: usize| case & (8 >> corner) != 0;
+
+            let cell = elevation(2, 2, |x, y| {
+                let corner = corners.iter().position(|&c| c == (x, y)).unwrap();
+
+                if above(corner) { 1.0 } else { 0.0 }
+            });
+
+            let lines = cell.isolines(0.5);
+
+            let expected_lines = match case {
+                0 | 15 => 0,
+                5 | 10 => 2,
+                _ => 1,
+            };
+
+            assert_eq!(lines.len(), expected_lines, "case {case}");
+
+            let mut crossed: Vec<_> = (0..4)
+                .filter(|&corner| above(corner) != above((corner + 1) % 4))
+                .map(|corner| {
+                    let (ax, ay) = corners[corner];
+                    let (bx, by) = corners[(corner + 1) % 4];
+
+                    // in tile pixels, samples are at pixel centres
+                    ((ax + bx) as f64 / 2.0 + 0.5, (ay + by) as f64 / 2.0 + 0.5)
+                })
+                .collect();
+
+            let mut ends: Vec<_> = lines
+                .iter()
+                .flat_map(|line| {
+                    assert_eq!(line.0.len(), 2, "case {case}");
+
+                    line.0.iter().map(|coord| (coord.x, coord.y))
+                })
+                .collect();
+
+            crossed.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            ends.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+            assert_eq!(ends, crossed, "case {case}");
+        }
+    }
+
+    /// Segments of all cells are stitched into one line across the grid.
+    #[test]
+    fn isolines_stitch_open_line() {
+        let ramp = elevation(6, 5, |x, _| x as f32);
+
+        let lines = ramp.isolines(2.25);
+
+        assert_eq!(lines.len(), 1);
+
+        let line = &lines[0];
+
+        assert_eq!(line.0.len(), 5);
+
+        for coord in &line.0 {
+            assert!((coord.x - 2.75).abs() < 1e-9);
+        }
+
+        let mut ys: Vec<_> = line.0.iter().map(|coord| coord.y).collect();
+
+        // traced from either end
+        if ys[0] > ys[4] {
+            ys.reverse();
+        }
+
+        assert_eq!(ys, [0.5, 1.5, 2.5, 3.5, 4.5]);
+    }
+
+    #[test]
+    fn isolines_stitch_closed_line() {
+        let hill = elevation(7, 7, |x, y| {
+            10.0 - (x as f32 - 3.0).abs().max((y as f32 - 3.0).abs())
+        });
+
+        let lines = hill.isolines(8.5);
+
+        assert_eq!(lines.len(), 1);
+
+        let line = &lines[0];
+
+        assert!(line.is_closed());
+
+        // around the 3×3 samples above the level
+        assert_eq!(line.0.len(), 13);
+
+        let center = Coord { x: 3.5, y: 3.5 };
+
+        for coord in &line.0 {
+            let distance = (coord.x - center.x).abs().max((coord.y - center.y).abs());
+
+            assert!((1.0..=1.5).contains(&distance), "{coord:?}");
+        }
+    }
+
+    #[test]
+    fn isolines_end_at_missing_data() {
+        let ramp = elevation(6, 5, |x, y| if y == 2 { f32::NAN } else { x as f32 });
+
+        let lines = ramp.isolines(2.25);
+
+        assert_eq!(lines.len(), 2);
+
+        for line in &lines {
+            assert_eq!(line.0.len(), 2);
+        }
+    }
+}
@@ -19,6 +19,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(8.0)).as_params())?;
 
+    if ctx.emit_rows("embankments", &rows, &[]) {
+        return Ok(());
+    }
+
     for row in rows {
         let geom = geometry_line_string(&row).project_to_tile(&ctx.tile_projector);
 
@@ -20,6 +20,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(8.0)).as_params())?;
 
+    if ctx.emit_rows("feature_lines", &rows, &["type"]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     for row in rows {
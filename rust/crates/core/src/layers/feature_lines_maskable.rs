@@ -28,6 +28,10 @@ pub fn render(
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(8.0)).as_params())?;
 
+    if ctx.emit_rows("feature_lines", &rows, &["type"]) {
+        return Ok(());
+    }
+
     if rows.is_empty() {
         return Ok(());
     }
@@ -35,12 +39,14 @@ pub fn render(
     let mut mask_surfaces = Vec::new();
 
     if shading && let Some(hillshading_datasets) = hillshading_datasets {
-        for cc in [
-            "pl", "sk", "cz", "at", /*"ch", "it" (CH, IT are not so detailed) */
-        ] {
+        for dataset in hillshading_datasets
+            .covering(ctx.bbox)
+            .into_iter()
+            .filter(|dataset| dataset.detailed)
+        {
             let mask_surface = hillshading::load_surface(
                 ctx,
-                cc,
+                &dataset.name,
                 hillshading_datasets,
                 hillshade_scale,
                 hillshading::Mode::Mask,
@@ -1,354 +1,22 @@
 use crate::SvgRepo;
-use crate::colors::{self, Color};
+use crate::ctx::Ctx;
 use crate::draw::create_pango_layout::FontAndLayoutOptions;
 use crate::draw::text::{TextOptions, draw_text, draw_text_with_attrs};
+use crate::labels::{Labels, priority};
 use crate::layer_render_error::LayerRenderResult;
+use crate::pois::{Poi, Pois};
 use crate::projectable::{TileProjectable, geometry_point};
-use crate::regex_replacer::{Replacement, build_replacements, replace};
-use crate::svg_repo::Options;
-use crate::{collision::Collision, ctx::Ctx};
+use crate::svg_repo::{Options, SvgRepoError};
+use cairo::RecordingSurface;
 use core::f64;
 use geo::{Point, Rect};
-use pangocairo::pango::{AttrList, AttrSize, SCALE, Style, Weight};
+use pangocairo::pango::{AttrList, AttrSize, SCALE, Style};
 use postgres::Client;
 use std::borrow::Cow;
+use std::cell::Cell;
+use std::rc::Rc;
 use std::{collections::HashMap, sync::LazyLock};
 
-struct Extra<'a> {
-    replacements: Vec<Replacement<'a>>,
-    icon: Option<&'a str>,
-    font_size: f64,
-    weight: Weight,
-    text_color: Color,
-    max_zoom: u32,
-    stylesheet: Option<&'a str>,
-    halo: bool,
-}
-
-impl Default for Extra<'_> {
-    fn default() -> Self {
-        Self {
-            replacements: vec![],
-            icon: None,
-            font_size: 12.0,
-            weight: Weight::Normal,
-            text_color: colors::BLACK,
-            max_zoom: u32::MAX,
-            stylesheet: None,
-            halo: true,
-        }
-    }
-}
-
-struct Def {
-    min_zoom: u32,
-    min_text_zoom: u32,
-    with_ele: bool,
-    natural: bool,
-    extra: Extra<'static>,
-}
-
-#[rustfmt::skip]
-static POIS: LazyLock<HashMap<&'static str, Vec<Def>>> = LazyLock::new(|| {
-    const Y: bool = true;
-    const N: bool = false;
-    const NN: u32 = u32::MAX;
-
-    let spring_replacements = build_replacements(&[
-        (r"\b[Mm]inerálny\b", "min."),
-        (r"\b[Pp]rameň\b", "prm."),
-        (r"\b[Ss]tud(ničk|ň)a\b", "stud."),
-        (r"\b[Vv]yvieračka\b", "vyv."),
-    ]);
-
-    let church_replacements = build_replacements(&[
-        (r"^[Kk]ostol\b *", ""),
-        (r"\b([Ss]vät\w+|Sv\.)", "sv."),
-    ]);
-
-    let chapel_replacements = build_replacements(&[
-        (r"^[Kk]aplnka\b *", ""),
-        (r"\b([Ss]vät\w+|Sv\.)", "sv."),
-    ]);
-
-    let school_replacements = build_replacements(&[
-        (r"[Zz]ákladná [Šš]kola", "ZŠ"),
-        (r"[Zz]ákladná [Uu]melecká [Šš]kola", "ZUŠ"),
-        (r"[Ss]tredná [Oo]dborná [Šš]kola", "SOŠ"),
-        (r"[Gg]ymnázium ", "gym. "),
-        (r" [Gg]ymnázium", " gym."),
-        (r"[V]ysoká [Šš]kola", "VŠ"),
-    ]);
-
-    let college_replacements = build_replacements(&[
-        (r"[Ss]tredná [Oo]dborná [Šš]kola", "SOŠ"),
-        (r"[Gg]ymnázium ", "gym. "),
-        (r" [Gg]ymnázium", " gym."),
-        (r"[V]ysoká [Šš]kola", "VŠ"),
-    ]);
-
-    let university_replacements = build_replacements(&[(r"[V]ysoká [Šš]kola", "VŠ")]);
-
-    let entries = vec![
-        (12, 12, N, N, "aerodrome", Extra {
-            replacements: build_replacements(&[(r"^[Ll]etisko\b *", "")]),
-            ..Extra::default()
-        }),
-        // (12, 12, Y, N, "guidepost", Extra { icon: Some("guidepost_x"), weight: Weight::Bold, max_zoom: 12, ..Extra::default() }),
-        (13, 13, Y, N, "guidepost", Extra { icon: Some("guidepost_xx"), weight: Weight::Bold, max_zoom: 13, ..Extra::default() }),
-        (14, 14, Y, N, "guidepost", Extra { icon: Some("guidepost_xx"), weight: Weight::Bold, ..Extra::default() }),
-        (10, 10, Y, Y, "peak1", Extra { icon: Some("peak"), font_size: 13.0, halo: false, ..Extra::default() }),
-        (11, 11, Y, Y, "peak2", Extra { icon: Some("peak"), font_size: 13.0, halo: false, ..Extra::default() }),
-        (12, 12, Y, Y, "peak3", Extra { icon: Some("peak"), font_size: 13.0, halo: false, ..Extra::default() }),
-        (13, 13, Y, Y, "peak", Extra { font_size: 13.0, halo: false, ..Extra::default() }),
-        (14, 14, N, N, "castle", Extra {
-            replacements: build_replacements(&[(r"^[Hh]rad\b *", "")]),
-            ..Extra::default()
-        }),
-        (14, 15, Y, Y, "arch", Extra::default()),
-        (14, 15, Y, Y, "cave_entrance", Extra {
-            replacements: build_replacements(&[
-                (r"^[Jj]jaskyňa\b *", ""),
-                (r"\b[Jj]jaskyňa$", "j."),
-                (r"\b[Pp]riepasť\b", "p."),
-            ]),
-            ..Extra::default()
-        }),
-        (14, 15, Y, Y, "spring", Extra { replacements: spring_replacements.clone(), text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (14, 15, Y, Y, "waterfall", Extra {
-            replacements: build_replacements(&[
-                (r"^[Vv]odopád\b *", ""),
-                (r"\b[Vv]odopád$", "vdp."),
-            ]),
-            text_color: colors::WATER_LABEL,
-            ..Extra::default()
-        }),
-        (14, 15, N, N, "drinking_water", Extra { text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (14, 15, N, N, "water_point", Extra { text_color: colors::WATER_LABEL, icon: Some("drinking_water"), ..Extra::default() }),
-        (14, 15, N, N, "water_well", Extra { text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (14, 15, Y, N, "monument", Extra::default()),
-        (14, 15, Y, Y, "viewpoint", Extra {
-            replacements: build_replacements(&[
-                (r"^[Vv]yhliadka\b *", ""),
-                (r"\b[Vv]yhliadka$", "vyhl."),
-            ]),
-            ..Extra::default()
-        }),
-        (14, 15, Y, N, "mine", Extra { icon: Some("mine"), ..Extra::default() }),
-        (14, 15, Y, N, "adit", Extra { icon: Some("mine"), ..Extra::default() }),
-        (14, 15, Y, N, "mineshaft", Extra { icon: Some("mine"), ..Extra::default() }),
-        (14, 15, Y, N, "disused_mine", Extra::default()),
-        (14, 15, Y, N, "hotel", Extra {
-            replacements: build_replacements(&[(r"^[Hh]otel\b *", "")]),
-            ..Extra::default()
-        }),
-        (14, 15, Y, N, "chalet", Extra {
-            replacements: build_replacements(&[
-                (r"^[Cc]hata\b *", ""),
-                (r"\b[Cc]hata$", "ch."),
-            ]),
-            ..Extra::default()
-        }),
-        (14, 15, Y, N, "hostel", Extra::default()),
-        (14, 15, Y, N, "motel", Extra {
-            replacements: build_replacements(&[(r"^[Mm]otel\b *", "")]),
-            ..Extra::default()
-        }),
-        (14, 15, Y, N, "guest_house", Extra::default()),
-        (14, 15, Y, N, "apartment", Extra::default()),
-        (14, 15, Y, N, "wilderness_hut", Extra::default()),
-        (14, 15, Y, N, "alpine_hut", Extra::default()),
-        (14, 15, Y, N, "camp_site", Extra::default()),
-        (14, 15, N, N, "attraction", Extra::default()),
-        (14, 15, N, N, "hospital", Extra {
-            replacements: build_replacements(&[(r"^[Nn]emocnica\b", "Nem.")]),
-            ..Extra::default()
-        }),
-        (14, NN, N, N, "townhall", Extra {
-            replacements: chapel_replacements.clone(),
-            ..Extra::default()
-        }),
-        (14, 15, N, N, "chapel", Extra::default()),
-        (14, 15, N, N, "church", Extra {
-            replacements: church_replacements.clone(),
-            ..Extra::default()
-        }),
-        (14, 15, N, N, "cathedral", Extra {
-            replacements: church_replacements.clone(),
-            icon: Some("church"),
-            ..Extra::default()
-        }),
-        (14, 15, N, N, "synagogue", Extra::default()),
-        (14, 15, N, N, "mosque", Extra::default()),
-        (14, 15, Y, N, "tower_observation", Extra::default()),
-        (14, 15, Y, N, "archaeological_site", Extra::default()),
-        (14, 15, N, N, "station", Extra::default()),
-        (14, 15, N, N, "halt", Extra { icon: Some("station"), ..Extra::default() }),
-        (14, 15, N, N, "bus_station", Extra::default()),
-        (14, 15, N, N, "water_park", Extra::default()),
-        (14, 15, N, N, "museum", Extra::default()),
-        (14, 15, N, N, "manor", Extra::default()),
-        (14, 15, N, N, "free_flying", Extra::default()),
-        (14, 15, N, N, "forester's_lodge", Extra::default()),
-        (14, 15, N, N, "horse_riding", Extra::default()),
-        (14, 15, N, N, "golf_course", Extra::default()),
-        // TODO (14, 14, N, N, "recycling", Extra { text_color: colors::AREA_LABEL, ..Extra::default() }), // { icon: null } // has no icon yet - render as area name
-        (15, NN, Y, N, "guidepost_noname", Extra { icon: Some("guidepost_x"), ..Extra::default() }),
-        (15, 15, Y, Y, "saddle", Extra { font_size: 13.0, halo: false, ..Extra::default() }),
-        (15, 16, N, N, "ruins", Extra::default()),
-        (15, 16, N, N, "generator_wind", Extra::default()),
-        (15, 16, N, N, "chimney", Extra::default()),
-        (15, 16, N, N, "fire_station", Extra {
-            replacements: build_replacements(&[(r"^([Hh]asičská zbrojnica|[Pp]ožiarná stanica)\b *", "")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "community_centre", Extra {
-            replacements: build_replacements(&[(r"\b[Cc]entrum voľného času\b", "CVČ")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "police", Extra {
-            replacements: build_replacements(&[(r"^[Pp]olícia\b *", "")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "office", Extra::default()),           // information=office
-        (15, 16, N, N, "hunting_stand", Extra::default()),
-        (15, 16, Y, N, "shelter", Extra::default()),
-        // (15, 16, Y, N, 'shopping_cart', Extra::default()),
-        (15, 16, Y, N, "lean_to", Extra::default()),
-        (15, 16, Y, N, "public_transport", Extra::default()),
-        (15, 16, Y, N, "picnic_shelter", Extra::default()),
-        (15, 16, Y, N, "basic_hut", Extra::default()),
-        (15, 16, Y, N, "weather_shelter", Extra::default()),
-        (15, 16, N, N, "pharmacy", Extra {
-            replacements: build_replacements(&[(r"^[Ll]ekáreň\b *", "")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "cinema", Extra {
-            replacements: build_replacements(&[(r"^[Kk]ino\b *", "")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "theatre", Extra {
-            replacements: build_replacements(&[(r"^[Dd]ivadlo\b *", "")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "memorial", Extra {
-            replacements: build_replacements(&[(r"^[Pp]amätník\b *", "")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "pub", Extra::default()),
-        (15, 16, N, N, "cafe", Extra {
-            replacements: build_replacements(&[(r"^[Kk]aviareň\b *", "")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "bar", Extra::default()),
-        (15, 16, N, N, "restaurant", Extra {
-            replacements: build_replacements(&[(r"^[Rr]eštaurácia\b *", "")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "convenience", Extra::default()),
-        (15, 16, N, N, "greengrocer", Extra::default()),
-        (15, 16, N, N, "farm", Extra { icon: Some("greengrocer"), ..Extra::default()}),
-        (15, 16, N, N, "supermarket", Extra::default()),
-        (15, 16, N, N, "fast_food", Extra::default()),
-        (15, 16, N, N, "confectionery", Extra::default()),
-        (15, 16, N, N, "pastry", Extra { icon: Some("confectionery"), ..Extra::default() }),
-        (15, 16, N, N, "fuel", Extra::default()),
-        (15, 16, N, N, "post_office", Extra::default()),
-        (15, 16, N, N, "bunker", Extra::default()),
-        (15, NN, N, N, "mast_other", Extra::default()),
-        (15, NN, N, N, "tower_other", Extra::default()),
-        (15, NN, N, N, "tower_communication", Extra::default()),
-        (15, NN, N, N, "mast_communication", Extra { icon: Some("tower_communication"), ..Extra::default() }),
-        (15, 16, N, N, "tower_bell_tower", Extra::default()),
-        (15, 16, N, N, "water_tower", Extra::default()),
-        (15, 16, N, N, "bus_stop", Extra::default()),
-        (15, 16, N, N, "sauna", Extra::default()),
-        (15, 16, N, N, "taxi", Extra::default()),
-        (15, 16, N, N, "bicycle", Extra::default()),
-        (15, 15, N, Y, "tree_protected", Extra { text_color: colors::TREE, ..Extra::default() }),
-        (15, 15, N, Y, "tree", Extra::default()),
-        (15, 16, N, N, "bird_hide", Extra::default()),
-        (15, 16, N, N, "dam", Extra { text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (15, 16, N, N, "school", Extra { replacements: school_replacements.clone(), ..Extra::default() }),
-        (15, 16, N, N, "college", Extra { replacements: college_replacements.clone(), ..Extra::default() }),
-        (15, 16, N, N, "university", Extra { replacements: university_replacements.clone(), ..Extra::default() }),
-        (15, 16, N, N, "kindergarten", Extra {
-            replacements: build_replacements(&[(r"[Mm]atersk(á|ou) [Šš]k[oô]lk?(a|ou)", "MŠ")]),
-            ..Extra::default()
-        }),
-        (15, 16, N, N, "climbing", Extra::default()),
-        (15, 16, N, N, "shooting", Extra::default()),
-        (16, 17, N, Y, "rock", Extra::default()),
-        (16, 17, N, Y, "stone", Extra::default()),
-        (16, 17, N, Y, "sinkhole", Extra::default()),
-        (16, 17, N, N, "building", Extra::default()),
-        (16, 17, N, N, "weir", Extra { text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (16, 17, N, N, "miniature_golf", Extra::default()),
-        (16, 17, N, N, "soccer", Extra::default()),
-        (16, 17, N, N, "tennis", Extra::default()),
-        (16, 17, N, N, "basketball", Extra::default()),
-        (16, NN, Y, N, "guidepost_noname", Extra { icon: Some("guidepost_x"), ..Extra::default() }),
-        (16, NN, Y, N, "route_marker", Extra { icon: Some("guidepost_x"), ..Extra::default() }),
-        (16, NN, N, N, "picnic_table", Extra::default()),
-        (16, NN, N, N, "outdoor_seating", Extra::default()),
-        (16, 17, N, N, "picnic_site", Extra::default()),
-        (16, 16, N, N, "board", Extra::default()),
-        (16, 17, N, N, "map", Extra::default()),
-        (16, 17, N, N, "artwork", Extra::default()),
-        (16, 17, N, N, "fountain", Extra { text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (16, NN, N, N, "watering_place", Extra { text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (16, NN, N, N, "feeding_place", Extra { icon: Some("manger"), ..Extra::default() }),
-        (16, NN, N, N, "game_feeding", Extra { icon: Some("manger"), ..Extra::default() }),
-        (16, 17, N, N, "playground", Extra {
-            replacements: build_replacements(&[(r"^[Dd]etské ihrisko\b", "")]),
-            ..Extra::default()
-        }),
-        (16, 17, N, N, "water_works", Extra { text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (16, 17, N, N, "reservoir_covered", Extra { icon: Some("water_works"), text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (16, 17, N, N, "pumping_station", Extra { icon: Some("water_works"), text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (16, 17, N, N, "wastewater_plant", Extra { icon: Some("water_works"), text_color: colors::WATER_LABEL, ..Extra::default() }),
-        (16, 17, N, N, "cross", Extra::default()),
-        (17, 18, N, N, "boundary_stone", Extra::default()),
-        (17, 18, N, N, "marker", Extra { icon: Some("boundary_stone"), ..Extra::default() }),
-        (17, 18, N, N, "wayside_shrine", Extra::default()),
-        (17, 18, N, N, "cross", Extra::default()), // NOTE cross is also on lower zoom
-        (17, 18, N, N, "wayside_cross", Extra { icon: Some("cross"), ..Extra::default() }), // NOTE cross is also on lower zoom
-        (17, 18, N, N, "tree_shrine", Extra { icon: Some("cross"), ..Extra::default() }), // NOTE cross is also on lower zoom
-        (17, NN, N, N, "firepit", Extra::default()),
-        (17, NN, N, N, "toilets", Extra::default()),
-        (17, NN, N, N, "bench", Extra::default()),
-        (17, 18, N, N, "beehive", Extra::default()),
-        (17, 18, N, N, "apiary", Extra { icon: Some("beehive"), ..Extra::default() }),
-        (17, NN, N, N, "lift_gate", Extra::default()),
-        (17, NN, N, N, "swing_gate", Extra { icon: Some("lift_gate"), ..Extra::default() }),
-        (17, NN, N, N, "ford", Extra::default()),
-        (17, 19, N, N, "parking", Extra { font_size: 10.0, text_color: colors::AREA_LABEL, ..Extra::default() }), // { font: { haloOpacity: 0.5 } },
-        (18, 19, N, N, "building_ruins", Extra { icon: Some("ruins"), ..Extra::default() }),
-        (18, 19, N, N, "post_box", Extra::default()),
-        (18, 19, N, N, "telephone", Extra::default()),
-        (18, NN, N, N, "gate", Extra::default()),
-        (18, NN, N, N, "waste_disposal", Extra::default()),
-        (19, NN, N, N, "waste_basket", Extra::default()),
-        ];
-
-    let mut pois = HashMap::new();
-
-    for (min_zoom, min_text_zoom, with_ele, natural, name, extra) in entries.into_iter() {
-        pois
-            .entry(name)
-            .or_insert_with(Vec::new)
-            .push(Def {
-                min_zoom,
-                min_text_zoom,
-                with_ele,
-                natural,
-                extra,
-            });
-    }
-
-    pois
-});
-
 const RADII: [f64; 4] = [2.0, 4.0, 6.0, 8.0];
 
 const fn offset_at(r: f64, idx: usize) -> (f64, f64) {
@@ -380,11 +48,12 @@ static OFFSETS: LazyLock<[(f64, f64); 33]> = LazyLock::new(|| {
     offsets
 });
 
-pub fn render(
-    ctx: &Ctx,
+pub fn render<'a>(
+    ctx: &'a Ctx,
     client: &mut Client,
-    collision: &mut Collision,
+    labels: &mut Labels<'a>,
     svg_repo: &mut SvgRepo,
+    pois: &'a Pois,
 ) -> LayerRenderResult {
     let _span = tracy_client::span!("features::render");
 
@@ -720,171 +389,107 @@ pub fn render(
 
     let rows = {
         let _span = tracy_client::span!("features::query");
+
         client.query(&sql, &ctx.bbox_query_params(Some(1024.0)).as_params())?
     };
 
-    let mut to_label = Vec::<(Point, f64, String, Option<String>, usize, &Def)>::new();
+    if ctx.emit_rows("features", &rows, &["osm_id", "type", "n"]) {
+        return Ok(());
+    }
 
     let context = ctx.context;
 
-    {
-        let _span = tracy_client::span!("features::paint_svgs");
+    for row in rows {
+        let typ: &str = row.get("type");
 
-        for row in rows {
-            let typ: &str = row.get("type");
+        let h: HashMap<String, Option<String>> = row.get("h");
 
-            let h: HashMap<String, Option<String>> = row.get("h");
+        let Some(def) = pois.get(typ, zoom) else {
+            continue;
+        };
 
-            let Some(def) = POIS.get(typ).and_then(|defs| {
-                defs.iter()
-                    .find(|def| def.min_zoom <= zoom && def.extra.max_zoom >= zoom)
-            }) else {
-                continue;
-            };
+        let position = geometry_point(&row);
 
-            let point = geometry_point(&row).project_to_tile(&ctx.tile_projector);
+        let point = position.project_to_tile(&ctx.tile_projector);
 
-            let key = def.extra.icon.unwrap_or(typ);
+        let surface = icon(svg_repo, def, &h)?.clone();
 
-            let (key, names, stylesheet) = match key {
-                "spring" => {
-                    let mut stylesheet = String::new();
+        let (x, y, w, he) = surface.ink_extents();
 
-                    let is_mineral = h
-                        .get("water_characteristic")
-                        .map_or(false, |v| v.is_some() && v.as_deref() != Some(""));
+        let name: &str = row.get("n");
 
-                    let mut key = (if is_mineral {
-                        "mineral-spring"
-                    } else {
-                        "spring"
-                    })
-                    .to_string();
-
-                    let mut names = vec![key.clone()];
-
-                    if !is_mineral
-                        && h.get("refitted")
-                            .map_or(false, |r| r.as_deref() == Some("true"))
-                    {
-                        key.push_str("|refitted");
-                        names.push("refitted_spring".into());
-                    }
+        let feature = ctx.hit_feature(
+            "features",
+            row.get("osm_id"),
+            &[("type", typ), ("name", name)],
+        );
 
-                    let fill = if h.get("hot").map_or(false, |r| r.as_deref() == Some("true")) {
-                        key.push_str("|hot");
+        let alpha = if typ != "cave_entrance"
+            && h.get("access").map_or(false, |access| {
+                matches!(access.as_deref(), Some("private" | "no"))
+            }) {
+            0.33
+        } else {
+            1.0
+        };
 
-                        "#e11919"
-                    } else {
-                        "#0064ff"
-                    };
-
-                    if h.get("intermittent")
-                        .map_or(false, |r| r.as_deref() == Some("true"))
-                    {
-                        key.push_str("|tmp");
-                        names.push("intermittent".into());
-                    }
+        let bonus = priority_bonus(typ);
 
-                    stylesheet.push_str(&format!("#spring {{ fill: {fill} }}"));
-
-                    match h.get("drinkable").map_or(None, |r| r.as_deref()) {
-                        Some("true") => {
-                            key.push_str("|drinkable");
-                            names.push("drinkable_spring".into());
-                            stylesheet.push_str(r#"#drinkable { fill: #00ff00 } "#);
-                        }
-                        Some("false") => {
-                            key.push_str("|not_drinkable");
-                            names.push("drinkable_spring".into());
-                            stylesheet.push_str(r#"#drinkable { fill: #ff0000 } "#);
-                        }
-                        _ => {}
-                    }
+        // label position and icon box index, set once the icon is placed
+        let placed = Rc::new(Cell::new(None::<(Point, usize)>));
 
-                    (Cow::Owned(key), names, Some(stylesheet))
-                }
-                _ => (
-                    Cow::Borrowed(key),
-                    vec![key.to_string()],
-                    def.extra.stylesheet.map(str::to_string),
-                ),
-            };
+        labels.submit(priority::FEATURE + bonus, feature, {
+            let placed = Rc::clone(&placed);
 
-            let surface = svg_repo.get_extra(
-                &key,
-                Some({
-                    || Options {
-                        names,
-                        stylesheet,
-                        halo: def.extra.halo,
-                        use_extents: false,
-                    }
-                }),
-            )?;
+            move |collision| {
+                let corner_x = point.x() - w / 2.0;
 
-            let (x, y, w, he) = surface.ink_extents();
+                let corner_y = point.y() - he / 2.0;
 
-            let corner_x = point.x() - w / 2.0;
+                for &(dx, dy) in OFFSETS.iter() {
+                    // NOTE 0.5 is for icnos not to be blurred on MDPI
+                    // TODO align by scale
+                    let corner_x = (corner_x + dx - 0.5).round() + 0.5;
+                    let corner_y = (corner_y + dy - 0.5).round() + 0.5;
 
-            let corner_y = point.y() - he / 2.0;
+                    let bbox = Rect::new((corner_x, corner_y), (corner_x + w, corner_y + he));
 
-            'outer: for &(dx, dy) in OFFSETS.iter() {
-                // NOTE 0.5 is for icnos not to be blurred on MDPI
-                // TODO align by scale
-                let corner_x = (corner_x + dx - 0.5).round() + 0.5;
-                let corner_y = (corner_y + dy - 0.5).round() + 0.5;
+                    if collision.collides(&bbox) {
+                        continue;
+                    }
 
-                let bbox = Rect::new((corner_x, corner_y), (corner_x + w, corner_y + he));
+                    let bbox_idx = collision.add(bbox);
 
-                if collision.collides(&bbox) {
-                    continue;
-                }
+                    placed.set(Some((Point::new(point.x() + dx, point.y() + dy), bbox_idx)));
 
-                let bbox_idx = collision.add(bbox);
+                    let _span = tracy_client::span!("features::paint_svg");
 
-                if def.min_text_zoom <= zoom {
-                    let name: &str = row.get("n");
+                    context.set_source_surface(&surface, corner_x - x, corner_y - y)?;
 
-                    if !name.is_empty() {
-                        let name = replace(name, &def.extra.replacements);
+                    context.paint_with_alpha(alpha)?;
 
-                        to_label.push((
-                            Point::new(point.x() + dx, point.y() + dy),
-                            he / 2.0,
-                            name.into_owned(),
-                            h.get("ele").map_or(None, |ele| ele.clone()),
-                            bbox_idx,
-                            def,
-                        ));
-                    }
+                    break;
                 }
 
-                let _span = tracy_client::span!("features::paint_svg");
+                Ok(())
+            }
+        });
 
-                context.set_source_surface(surface, corner_x - x, corner_y - y)?;
+        if def.min_text_zoom > zoom || name.is_empty() {
+            continue;
+        }
 
-                context.paint_with_alpha(
-                    if typ != "cave_entrance"
-                        && h.get("access").map_or(false, |access| {
-                            matches!(access.as_deref(), Some("private" | "no"))
-                        })
-                    {
-                        0.33
-                    } else {
-                        1.0
-                    },
-                )?;
+        let name = ctx.abbreviations.replace(&position, typ, name).into_owned();
 
-                break 'outer;
-            }
-        }
-    }
+        let ele = h.get("ele").map_or(None, |ele| ele.clone());
+
+        let d = he / 2.0;
 
-    {
-        let _span = tracy_client::span!("features::labels");
+        labels.submit(priority::FEATURE_LABEL + bonus, feature, move |collision| {
+            let Some((point, bbox_idx)) = placed.get() else {
+                return Ok(());
+            };
 
-        for (point, d, name, ele, bbox_idx, def) in to_label.into_iter() {
             let text_options = TextOptions {
                 flo: FontAndLayoutOptions {
                     style: if def.natural {
@@ -892,18 +497,18 @@ pub fn render(
                     } else {
                         Style::Normal
                     },
-                    size: def.extra.font_size,
-                    weight: def.extra.weight,
+                    size: def.font_size,
+                    weight: def.weight,
                     ..Default::default()
                 },
-                color: def.extra.text_color,
+                color: def.text_color,
                 valign_by_placement: true,
                 placements: &[-d - 3.0, d - 3.0, -d - 5.0, d - 1.0, -d - 7.0, d + 1.0],
                 omit_bbox: Some(bbox_idx),
                 ..Default::default()
             };
 
-            let drawn = if def.with_ele
+            if def.with_ele
                 && let Some(ele) = ele
             {
                 let attr_list = AttrList::new();
@@ -921,16 +526,110 @@ pub fn render(
                     format!("{}\n{}", name, ele).trim(),
                     Some(attr_list),
                     &text_options,
-                )?
+                )?;
             } else {
-                draw_text(context, Some(collision), &point, &name, &text_options)?
+                draw_text(context, Some(collision), &point, &name, &text_options)?;
+            }
+
+            Ok(())
+        });
+    }
+
+    Ok(())
+}
+
+/// Prominent peaks outrank the other POIs.
+fn priority_bonus(typ: &str) -> u32 {
+    match typ {
+        "peak1" => 15,
+        "peak2" => 10,
+        "peak3" => 5,
+        _ => 0,
+    }
+}
+
+/// Gets the icon of a POI, composing the spring icon from its attributes in `h`.
+pub(crate) fn icon<'a>(
+    svg_repo: &'a mut SvgRepo,
+    def: &Poi,
+    h: &HashMap<String, Option<String>>,
+) -> Result<&'a RecordingSurface, SvgRepoError> {
+    let key = def.icon();
+
+    let (key, names, stylesheet) = match key {
+        "spring" => {
+            let mut stylesheet = String::new();
+
+            let is_mineral = h
+                .get("water_characteristic")
+                .map_or(false, |v| v.is_some() && v.as_deref() != Some(""));
+
+            let mut key = (if is_mineral {
+                "mineral-spring"
+            } else {
+                "spring"
+            })
+            .to_string();
+
+            let mut names = vec![key.clone()];
+
+            if !is_mineral
+                && h.get("refitted")
+                    .map_or(false, |r| r.as_deref() == Some("true"))
+            {
+                key.push_str("|refitted");
+                names.push("refitted_spring".into());
+            }
+
+            let fill = if h.get("hot").map_or(false, |r| r.as_deref() == Some("true")) {
+                key.push_str("|hot");
+
+                "#e11919"
+            } else {
+                "#0064ff"
             };
 
-            if !drawn {
-                continue;
+            if h.get("intermittent")
+                .map_or(false, |r| r.as_deref() == Some("true"))
+            {
+                key.push_str("|tmp");
+                names.push("intermittent".into());
             }
+
+            stylesheet.push_str(&format!("#spring {{ fill: {fill} }}"));
+
+            match h.get("drinkable").map_or(None, |r| r.as_deref()) {
+                Some("true") => {
+                    key.push_str("|drinkable");
+                    names.push("drinkable_spring".into());
+                    stylesheet.push_str(r#"#drinkable { fill: #00ff00 } "#);
+                }
+                Some("false") => {
+                    key.push_str("|not_drinkable");
+                    names.push("drinkable_spring".into());
+                    stylesheet.push_str(r#"#drinkable { fill: #ff0000 } "#);
+                }
+                _ => {}
+            }
+
+            (Cow::Owned(key), names, Some(stylesheet))
         }
-    }
+        _ => (
+            Cow::Borrowed(key),
+            vec![key.to_string()],
+            def.stylesheet.clone(),
+        ),
+    };
 
-    Ok(())
+    svg_repo.get_extra(
+        &key,
+        Some({
+            || Options {
+                names,
+                stylesheet,
+                halo: def.halo,
+                use_extents: false,
+            }
+        }),
+    )
 }
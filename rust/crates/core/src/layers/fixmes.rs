@@ -18,6 +18,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(8.0)).as_params())?;
 
+    if ctx.emit_rows("fixmes", &rows, &[]) {
+        return Ok(());
+    }
+
     let surface = svg_repo.get("fixme")?;
 
     let rect = surface.extents().expect("surface extents");
@@ -52,6 +56,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(8.0)).as_params())?;
 
+    if ctx.emit_rows("fixmes", &rows, &["fixme"]) {
+        return Ok(());
+    }
+
     for row in rows.into_iter() {
         let line_string = geometry_line_string(&row).project_to_tile(&ctx.tile_projector);
 
@@ -21,6 +21,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(20.0)).as_params())?;
 
+    if ctx.emit_rows("geonames", &rows, &["name"]) {
+        return Ok(());
+    }
+
     let options = TextOnLineOptions {
         flo: FontAndLayoutOptions {
             size: 8.0 + 1.9f64.powf(ctx.zoom as f64 - 6.0),
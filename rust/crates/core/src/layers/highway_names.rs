@@ -1,18 +1,18 @@
 use crate::{
-    collision::Collision,
     colors::{self},
     ctx::Ctx,
     draw::{
         path_geom::walk_geometry_line_strings,
         text_on_line::{Align, Distribution, Repeat, TextOnLineOptions, draw_text_on_line},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_geometry},
 };
 
 use postgres::Client;
 
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("highway_names::render");
 
     let sql = "
@@ -25,12 +25,16 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
                 NOT (class = 'railway' AND type = 'abandoned')
                 GROUP BY z_order, name, type
         )
-        SELECT name, geometry, type
+        SELECT name, geometry, type, osm_id
         FROM merged
         ORDER BY z_order DESC, osm_id";
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(1024.0)).as_params())?;
 
+    if ctx.emit_rows("highway_names", &rows, &["osm_id", "name", "type"]) {
+        return Ok(());
+    }
+
     let options = TextOnLineOptions {
         distribution: Distribution::Align {
             align: Align::Center,
@@ -47,13 +51,24 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
         let geom = geom.project_to_tile(&ctx.tile_projector);
 
-        let name: &str = row.get("name");
+        let name: String = row.get("name");
+
+        let typ: &str = row.get("type");
+
+        let feature = ctx.hit_feature(
+            "roads",
+            row.get("osm_id"),
+            &[("type", typ), ("name", name.as_str())],
+        );
 
-        walk_geometry_line_strings(&geom, &mut |geom| {
-            let _drawn = draw_text_on_line(ctx.context, geom, name, Some(collision), &options)?;
+        labels.submit(priority::HIGHWAY, feature, move |collision| {
+            walk_geometry_line_strings(&geom, &mut |geom| {
+                let _drawn =
+                    draw_text_on_line(ctx.context, geom, &name, Some(collision), &options)?;
 
-            cairo::Result::Ok(())
-        })?;
+                cairo::Result::Ok(())
+            })
+        });
     }
 
     Ok(())
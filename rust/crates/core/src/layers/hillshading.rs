@@ -1,14 +1,356 @@
 use crate::{
+    colors::{self, Color},
     ctx::Ctx,
     layer_render_error::{LayerRenderError, LayerRenderResult},
-    layers::hillshading_datasets::HillshadingDatasets,
+    layers::{
+        dem::{Elevation, read_elevation},
+        hillshading_datasets::{DatasetKind, HillshadingDatasets},
+        raster_level::RasterLevel,
+    },
+    xyz::ground_scale,
 };
 use cairo::{Format, ImageSurface};
-use gdal::Dataset;
+use gdal::{
+    Dataset,
+    raster::{GdalDataType, RasterBand, ResampleAlg},
+};
+
+/// Lighting of hillshading computed from elevation datasets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HillshadingOptions {
+    /// Sun azimuth in degrees clockwise from north.
+    pub azimuth: f64,
+    /// Sun altitude in degrees above the horizon.
+    pub altitude: f64,
+    /// Blends light from around the azimuth, weighted by the aspect, so that
+    /// slopes parallel to the light are modelled too.
+    pub multidirectional: bool,
+    /// Vertical exaggeration on top of the zoom dependent one.
+    pub exaggeration: f64,
+    /// Lowers the contrast in valleys and raises it at summits, like the haze
+    /// of Swiss relief shading.
+    pub aerial_perspective: bool,
+    /// Shadows cast by the terrain from the sun at the azimuth and altitude.
+    pub shadows: bool,
+    /// Darkens valleys and gullies by the share of the sky they see.
+    pub sky_view: bool,
+}
+
+impl Default for HillshadingOptions {
+    fn default() -> Self {
+        Self {
+            azimuth: 315.0,
+            altitude: 45.0,
+            multidirectional: true,
+            exaggeration: 1.0,
+            aerial_perspective: false,
+            shadows: false,
+            sky_view: false,
+        }
+    }
+}
 
+#[derive(Clone, Copy)]
 pub enum Mode {
     Mask,
-    Shading,
+    Shading(HillshadingOptions),
+    /// Shading with cast shadows or the sky-view factor, computed from
+    /// elevation with a halo around the tile so that they continue over its
+    /// edges. Pre-rendered shading is painted as is.
+    Relief(HillshadingOptions),
+}
+
+impl Mode {
+    /// Shading by `options`, as relief if they need the terrain around.
+    pub const fn shading(options: HillshadingOptions) -> Self {
+        if options.shadows || options.sky_view {
+            Self::Relief(options)
+        } else {
+            Self::Shading(options)
+        }
+    }
+}
+
+const SHADOW_COLOR: Color = (0, 16, 32);
+const SHADOW_ALPHA: f64 = 0.8;
+const HIGHLIGHT_ALPHA: f64 = 0.4;
+
+/// Elevations in metres between which the contrast of aerial perspective
+/// shading rises from the minimum to full.
+const AERIAL_PERSPECTIVE_RANGE: (f64, f64) = (0.0, 3000.0);
+const AERIAL_PERSPECTIVE_MIN_CONTRAST: f64 = 0.4;
+
+/// Share of the light blocked in cast shadows, the rest is ambient.
+const CAST_SHADOW_DEPTH: f64 = 0.6;
+
+/// Weight of the sky-view factor in the illumination.
+const SKY_VIEW_WEIGHT: f64 = 0.5;
+
+/// Ground distances in metres searched for terrain casting shadows and
+/// hiding the sky.
+const SHADOW_DISTANCE: f64 = 3000.0;
+const SKY_VIEW_DISTANCE: f64 = 500.0;
+
+/// Most samples of the halo read around the tile for relief.
+const MAX_HALO: usize = 128;
+
+/// Light directions of the multidirectional shading relative to the azimuth.
+const MULTIDIRECTIONAL_OFFSETS: [f64; 4] = [-90.0, -45.0, 0.0, 45.0];
+
+/// Vertical exaggeration at `zoom`, so that the relief stays readable at low
+/// zooms where the slopes of the downsampled terrain flatten.
+pub fn z_factor(zoom: u32) -> f64 {
+    1.4f64.powi(14i32.saturating_sub(zoom as i32).max(0))
+}
+
+/// Illumination (0 to 1) of a surface with the unit `normal` (east, north,
+/// up) by light from `azimuth` and `altitude` in degrees.
+fn illumination(normal: [f64; 3], azimuth: f64, altitude: f64) -> f64 {
+    let (sin_az, cos_az) = azimuth.to_radians().sin_cos();
+    let (sin_alt, cos_alt) = altitude.to_radians().sin_cos();
+
+    let [nx, ny, nz] = normal;
+
+    (nx * sin_az * cos_alt + ny * cos_az * cos_alt + nz * sin_alt).max(0.0)
+}
+
+fn shade(
+    elevation: &Elevation,
+    options: HillshadingOptions,
+    zoom: u32,
+    relief: bool,
+) -> Result<ImageSurface, LayerRenderError> {
+    let _span = tracy_client::span!("hillshading::shade");
+
+    let z = options.exaggeration * z_factor(zoom);
+
+    let flat = options.altitude.to_radians().sin();
+
+    let max_height = elevation.range().map_or(0.0, |(_, max)| max);
+
+    let sky_view_radius = ((SKY_VIEW_DISTANCE / elevation.cell_size).ceil() as usize)
+        .clamp(1, elevation.border.max(1));
+
+    elevation.to_surface(|x, y| {
+        let (dz_dx, dz_dy) = elevation.gradient(x, y)?;
+
+        let (nx, ny) = (-z * dz_dx, -z * dz_dy);
+
+        let length = nx.hypot(ny).hypot(1.0);
+
+        let normal = [nx / length, ny / length, 1.0 / length];
+
+        let mut value = if options.multidirectional {
+            // the horizontal part of the normal points downslope
+            let aspect = nx.atan2(ny);
+
+            let (sum, weights) =
+                MULTIDIRECTIONAL_OFFSETS
+                    .iter()
+                    .fold((0.0, 0.0), |(sum, weights), offset| {
+                        let azimuth = options.azimuth + offset;
+
+                        // light across the slope models it best
+                        let weight = (aspect - azimuth.to_radians()).sin().powi(2) + 0.01;
+
+                        (
+                            weight.mul_add(illumination(normal, azimuth, options.altitude), sum),
+                            weights + weight,
+                        )
+                    });
+
+            sum / weights
+        } else {
+            illumination(normal, options.azimuth, options.altitude)
+        };
+
+        if relief
+            && options.shadows
+            && elevation.in_shadow(x, y, options.azimuth, options.altitude, z, max_height)
+        {
+            value *= 1.0 - CAST_SHADOW_DEPTH;
+        }
+
+        if relief && options.sky_view {
+            value *=
+                SKY_VIEW_WEIGHT.mul_add(elevation.sky_view(x, y, sky_view_radius, z) - 1.0, 1.0);
+        }
+
+        let (color, alpha) = shading_color(value, flat);
+
+        Some(if options.aerial_perspective {
+            (
+                color,
+                alpha * aerial_perspective(elevation.get(x, y) as f64),
+            )
+        } else {
+            (color, alpha)
+        })
+    })
+}
+
+/// Contrast of shading at `height` in metres.
+fn aerial_perspective(height: f64) -> f64 {
+    let (low, high) = AERIAL_PERSPECTIVE_RANGE;
+
+    let t = ((height - low) / (high - low)).clamp(0.0, 1.0);
+
+    (1.0 - AERIAL_PERSPECTIVE_MIN_CONTRAST).mul_add(t, AERIAL_PERSPECTIVE_MIN_CONTRAST)
+}
+
+/// Colour and opacity of illumination `value` (0 to 1): shadows below `flat`,
+/// the illumination of flat terrain, and highlights above it.
+fn shading_color(value: f64, flat: f64) -> (Color, f64) {
+    if value < flat {
+        (SHADOW_COLOR, (flat - value) / flat * SHADOW_ALPHA)
+    } else {
+        (
+            colors::WHITE,
+            (value - flat) / (1.0 - flat).max(1e-6) * HIGHLIGHT_ALPHA,
+        )
+    }
+}
+
+fn elevation_surface(
+    dataset: &Dataset,
+    ctx: &Ctx,
+    raster_scale: f64,
+    mode: Mode,
+) -> Result<Option<ImageSurface>, LayerRenderError> {
+    let border = match mode {
+        Mode::Relief(options) => relief_halo(ctx, raster_scale, options),
+        Mode::Mask | Mode::Shading(_) => 1,
+    };
+
+    let Some(elevation) = read_elevation(dataset, ctx, raster_scale, border)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(match mode {
+        Mode::Mask => elevation.data_mask()?,
+        Mode::Shading(options) => shade(&elevation, options, ctx.zoom, false)?,
+        Mode::Relief(options) => shade(&elevation, options, ctx.zoom, true)?,
+    }))
+}
+
+/// Samples around the tile covering the distances searched for relief.
+fn relief_halo(ctx: &Ctx, raster_scale: f64, options: HillshadingOptions) -> usize {
+    let cell_size = ctx.meters_per_pixel() / raster_scale * ground_scale(ctx.bbox.center().y);
+
+    let distance = match (options.shadows, options.sky_view) {
+        (true, _) => SHADOW_DISTANCE,
+        (false, true) => SKY_VIEW_DISTANCE,
+        (false, false) => 0.0,
+    };
+
+    ((distance / cell_size).ceil() as usize).clamp(1, MAX_HALO)
+}
+
+/// Value of flat terrain in greyscale shading rasters, as by
+/// `gdaldem hillshade` with the default altitude of 45°.
+const GREY_FLAT: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+/// Bands of shading rasters.
+#[derive(Clone, Copy)]
+enum Layout {
+    /// Illumination, integer or float from 0 to 1.
+    Grey,
+    /// Illumination and alpha.
+    GreyAlpha,
+    Rgb,
+    Rgba,
+}
+
+/// Band resampled and scaled to 0–255.
+struct BandData {
+    values: Vec<u8>,
+    no_data: Vec<bool>,
+}
+
+/// Reads a band resampled to `size`. Integer bands are scaled from their full
+/// range, float ones from 0–1.
+fn read_band(
+    band: &RasterBand,
+    window: (isize, isize),
+    window_size: (usize, usize),
+    size: (usize, usize),
+) -> Result<BandData, LayerRenderError> {
+    let no_data = band.no_data_value();
+
+    let band_type = band.band_type();
+
+    if band_type == GdalDataType::UInt8 {
+        let mut values = vec![0u8; size.0 * size.1];
+
+        band.read_into_slice::<u8>(
+            window,
+            window_size,
+            size,
+            &mut values,
+            Some(ResampleAlg::Lanczos),
+        )?;
+
+        let no_data = values
+            .iter()
+            .map(|&value| no_data.is_some_and(|nd| (nd as u8) == value))
+            .collect();
+
+        return Ok(BandData { values, no_data });
+    }
+
+    let mut raw = vec![0f32; size.0 * size.1];
+
+    band.read_into_slice::<f32>(
+        window,
+        window_size,
+        size,
+        &mut raw,
+        Some(ResampleAlg::Lanczos),
+    )?;
+
+    let scale = if band_type.is_integer() {
+        255.0 / (2f64.powi(band_type.bits() as i32) - 1.0)
+    } else {
+        255.0
+    };
+
+    Ok(BandData {
+        values: raw
+            .iter()
+            .map(|&value| (value as f64 * scale).round().clamp(0.0, 255.0) as u8)
+            .collect(),
+        no_data: raw
+            .iter()
+            .map(|&value| value.is_nan() || no_data.is_some_and(|nd| value as f64 == nd))
+            .collect(),
+    })
+}
+
+/// Reads the mask band of the first band, which GDAL derives from nodata
+/// values if there is no explicit mask.
+fn read_mask(
+    level: &RasterLevel,
+    window: (isize, isize),
+    window_size: (usize, usize),
+    size: (usize, usize),
+) -> Result<BandData, LayerRenderError> {
+    let band = level.band(1)?;
+
+    let mask = band.open_mask_band()?;
+
+    let mut values = vec![0u8; size.0 * size.1];
+
+    mask.read_into_slice::<u8>(
+        window,
+        window_size,
+        size,
+        &mut values,
+        Some(ResampleAlg::NearestNeighbour),
+    )?;
+
+    let no_data = values.iter().map(|&value| value == 0).collect();
+
+    Ok(BandData { values, no_data })
 }
 
 fn read_rgba_from_gdal(
@@ -23,7 +365,12 @@ fn read_rgba_from_gdal(
     let min = bbox.min();
     let max = bbox.max();
 
-    let [gt_x_off, gt_x_width, _, gt_y_off, _, gt_y_width] = dataset.geo_transform()?;
+    let scaled_width_px = (size.width as f64 * raster_scale) as usize;
+    let scaled_height_px = (size.height as f64 * raster_scale) as usize;
+
+    let level = RasterLevel::for_pixel_size(dataset, bbox.width() / scaled_width_px as f64)?;
+
+    let [gt_x_off, gt_x_width, _, gt_y_off, _, gt_y_width] = level.geo_transform;
 
     // Convert geographic coordinates (min_x, min_y, max_x, max_y) to pixel coordinates
     let pixel_min_x_f = (min.x - gt_x_off) / gt_x_width;
@@ -45,9 +392,6 @@ fn read_rgba_from_gdal(
     let window_width_px = (pixel_max_x - pixel_min_x) as usize;
     let window_height_px = (pixel_max_y - pixel_min_y) as usize;
 
-    let scaled_width_px = (size.width as f64 * raster_scale) as usize;
-    let scaled_height_px = (size.height as f64 * raster_scale) as usize;
-
     let scale_x = scaled_width_px as f64 / (pixel_max_x_f - pixel_min_x_f).abs().max(1e-6);
     let scale_y = scaled_height_px as f64 / (pixel_max_y_f - pixel_min_y_f).abs().max(1e-6);
 
@@ -56,7 +400,7 @@ fn read_rgba_from_gdal(
 
     let mut rgba_data = vec![0u8; buffered_w * buffered_h * 4];
 
-    let (raster_width, raster_height) = dataset.raster_size();
+    let (raster_width, raster_height) = level.size;
 
     // Adjust the window to fit within the raster bounds
     let clamped_window_x = pixel_min_x.max(0).min(raster_width as isize);
@@ -95,66 +439,94 @@ fn read_rgba_from_gdal(
     let copy_width = resampled_width.min(buffered_w.saturating_sub(offset_x));
     let copy_height = resampled_height.min(buffered_h.saturating_sub(offset_y));
 
-    let mut band_buffer = vec![0u8; resampled_height * resampled_width];
-
-    let mut has_data = false;
-
-    let band_count = dataset.raster_count();
-
-    if band_count != 4 {
-        panic!("unsupported band count");
-    }
+    let layout = match dataset.raster_count() {
+        1 => Layout::Grey,
+        2 => Layout::GreyAlpha,
+        3 => Layout::Rgb,
+        4 => Layout::Rgba,
+        count => {
+            return Err(LayerRenderError::UnsupportedRaster(format!(
+                "{count} bands"
+            )));
+        }
+    };
 
-    let band_indices: &[usize] = match mode {
-        Mode::Shading => &[0, 1, 2, 3],
-        Mode::Mask => &[3],
+    let window = (clamped_window_x, clamped_window_y);
+    let window_size = (clamped_source_width, clamped_source_height);
+    let resampled_size = (resampled_width, resampled_height);
+
+    let band = |band_index: usize| -> Result<BandData, LayerRenderError> {
+        read_band(
+            &level.band(band_index + 1)?,
+            window,
+            window_size,
+            resampled_size,
+        )
     };
 
-    for &band_index in band_indices {
-        let band = dataset.rasterband(band_index + 1)?;
+    let mask = || read_mask(&level, window, window_size, resampled_size);
+
+    // bands by RGBA channel; alpha of layouts without an alpha band comes
+    // from the mask band (nodata or an explicit mask); pre-rendered shading
+    // has no relief of its own
+    let channels = match (layout, mode) {
+        (Layout::Grey | Layout::Rgb, Mode::Mask) => vec![mask()?],
+        (Layout::GreyAlpha, Mode::Mask) => vec![band(1)?],
+        (Layout::Rgba, Mode::Mask) => vec![band(3)?],
+        (Layout::Grey, Mode::Shading(_) | Mode::Relief(_)) => vec![band(0)?, mask()?],
+        (Layout::GreyAlpha, Mode::Shading(_) | Mode::Relief(_)) => vec![band(0)?, band(1)?],
+        (Layout::Rgb, Mode::Shading(_) | Mode::Relief(_)) => {
+            vec![band(0)?, band(1)?, band(2)?, mask()?]
+        }
+        (Layout::Rgba, Mode::Shading(_) | Mode::Relief(_)) => {
+            vec![band(0)?, band(1)?, band(2)?, band(3)?]
+        }
+    };
 
-        let no_data = band.no_data_value();
+    let mut has_data = false;
 
-        if clamped_source_width > 0
-            && clamped_source_height > 0
-            && resampled_width > 0
-            && resampled_height > 0
-        {
-            band.read_into_slice::<u8>(
-                (clamped_window_x, clamped_window_y),
-                (clamped_source_width, clamped_source_height),
-                (resampled_width, resampled_height), // Resampled size
-                &mut band_buffer,
-                Some(gdal::raster::ResampleAlg::Lanczos),
-            )?;
-        }
+    for y in 0..copy_height {
+        for x in 0..copy_width {
+            let data_index = y * resampled_width + x;
 
-        for y in 0..copy_height {
-            for x in 0..copy_width {
-                let data_index = y * resampled_width + x;
+            let rgba_index = ((y + offset_y) * buffered_w + (x + offset_x)) * 4;
 
-                let rgba_index = ((y + offset_y) * buffered_w + (x + offset_x)) * 4;
+            let mut values = [0u8; 4];
 
-                let value = band_buffer[data_index];
-                let is_no_data = no_data.is_some_and(|nd| (nd as u8) == value);
+            for (value, channel) in values.iter_mut().zip(&channels) {
+                *value = channel.values[data_index];
+            }
 
-                if !is_no_data {
-                    has_data = true;
+            // the single channel of a mask is the alpha or mask band, zero
+            // where there is no data
+            let is_no_data = match mode {
+                Mode::Mask => values[0] == 0,
+                Mode::Shading(_) | Mode::Relief(_) => {
+                    channels.iter().all(|channel| channel.no_data[data_index])
                 }
+            };
 
-                match mode {
-                    Mode::Shading => {
-                        rgba_data[rgba_index + band_index] = value;
-                    }
-                    Mode::Mask => {
-                        let alpha = if is_no_data { 0u8 } else { 255u8 };
-                        rgba_data[rgba_index] = 255;
-                        rgba_data[rgba_index + 1] = 255;
-                        rgba_data[rgba_index + 2] = 255;
-                        rgba_data[rgba_index + 3] = alpha;
-                    }
-                }
+            if !is_no_data {
+                has_data = true;
             }
+
+            let rgba = match (mode, &values[..channels.len()]) {
+                (Mode::Mask, _) => [255, 255, 255, if is_no_data { 0 } else { 255 }],
+                (Mode::Shading(_) | Mode::Relief(_), &[grey, alpha]) => {
+                    let ((r, g, b), opacity) = shading_color(grey as f64 / 255.0, GREY_FLAT);
+
+                    [
+                        r as u8,
+                        g as u8,
+                        b as u8,
+                        (opacity * alpha as f64).round() as u8,
+                    ]
+                }
+                (Mode::Shading(_) | Mode::Relief(_), &[r, g, b, a]) => [r, g, b, a],
+                _ => unreachable!("channels by layout"),
+            };
+
+            rgba_data[rgba_index..rgba_index + 4].copy_from_slice(&rgba);
         }
     }
 
@@ -224,19 +596,71 @@ pub fn load_surface(
     raster_scale: f64,
     mode: Mode,
 ) -> Result<Option<ImageSurface>, LayerRenderError> {
-    let hillshading_dataset = shading_data
-        .get(country)
-        .unwrap_or_else(|| panic!("no such dataset {country}"));
+    let kind = shading_data.kind(country);
 
-    let surface = read_rgba_from_gdal(hillshading_dataset, ctx, raster_scale, mode)?;
+    let Some(hillshading_dataset) = shading_data.get(country) else {
+        return Err(LayerRenderError::UnavailableDataset(country.to_string()));
+    };
 
-    if surface.is_some() {
-        shading_data.record_use(country);
-    }
+    let surface = match kind {
+        DatasetKind::Shading => read_rgba_from_gdal(hillshading_dataset, ctx, raster_scale, mode)?,
+        DatasetKind::Elevation => elevation_surface(hillshading_dataset, ctx, raster_scale, mode)?,
+    };
 
     Ok(surface)
 }
 
+/// Reads the elevation of an elevation dataset with `border` extra samples
+/// around the tile. Returns `None` for shading datasets and where there is no
+/// data.
+pub fn load_elevation(
+    ctx: &Ctx,
+    name: &str,
+    shading_data: &mut HillshadingDatasets,
+    raster_scale: f64,
+    border: usize,
+) -> Result<Option<Elevation>, LayerRenderError> {
+    if shading_data.kind(name) != DatasetKind::Elevation {
+        return Ok(None);
+    }
+
+    let Some(dataset) = shading_data.get(name) else {
+        return Ok(None);
+    };
+
+    read_elevation(dataset, ctx, raster_scale, border)
+}
+
+/// Paints the hypsometric tint of the manifest over an elevation dataset.
+/// Returns whether there was anything to paint.
+pub fn load_and_paint_tint(
+    ctx: &Ctx,
+    name: &str,
+    alpha: f64,
+    shading_data: &mut HillshadingDatasets,
+    raster_scale: f64,
+) -> Result<bool, LayerRenderError> {
+    if shading_data.tint().is_empty() {
+        return Ok(false);
+    }
+
+    let Some(elevation) = load_elevation(ctx, name, shading_data, raster_scale, 0)? else {
+        return Ok(false);
+    };
+
+    let tint = shading_data.tint();
+
+    let surface = elevation.to_surface(|x, y| {
+        let height = elevation.get(x, y);
+
+        (!height.is_nan()).then(|| (tint.color_at(height as f64), 1.0))
+    })?;
+
+    paint_surface(ctx, &surface, raster_scale, alpha)?;
+
+    Ok(true)
+}
+
 pub fn paint_surface(
     ctx: &Ctx,
     surface: &ImageSurface,
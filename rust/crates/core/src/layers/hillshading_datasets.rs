@@ -1,107 +1,590 @@
+use crate::{
+    colors::{self, Color, parse_hex_rgb},
+    xyz::lon_lat_to_epsg3857,
+};
 use gdal::Dataset;
+use geo::{BooleanOps, Intersects, MultiPolygon, Rect};
+use serde::{Deserialize, Deserializer, de};
 use std::{
-    collections::{HashMap, hash_map::Entry},
+    collections::{HashMap, HashSet, hash_map::Entry},
+    fs::read_to_string,
     path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
     time::{Duration, Instant},
 };
 
-const DATASET_PATHS: [(&str, &str); 10] = [
-    ("sk", "sk/final.tif"),
-    ("cz", "cz/final.tif"),
-    ("at", "at/final.tif"),
-    ("pl", "pl/final.tif"),
-    ("it", "it/final.tif"),
-    ("ch", "ch/final.tif"),
-    ("si", "si/final.tif"),
-    ("fr", "fr/final.tif"),
-    ("no", "no/final.tif"),
-    ("_", "final.tif"),
-];
-
-const MAX_UNUSED_USES: u64 = 100;
+const BUILTIN: &str = include_str!("../../styles/hillshading.yaml");
+
+/// Manifest in the base directory replacing the built-in one.
+const MANIFEST_FILE: &str = "datasets.yaml";
+
+const VERSION: u32 = 1;
+
+/// Idle time after which pooled dataset handles are closed.
 const EVICT_AFTER: Duration = Duration::from_secs(10);
 
-struct CachedDataset {
-    dataset: Dataset,
-    last_use: u64,
-    last_used_at: Instant,
+#[derive(Debug, thiserror::Error)]
+pub enum HillshadingManifestError {
+    #[error("Error reading {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Error parsing hillshading manifest: {0}")]
+    Parse(#[from] serde_yaml::Error),
+
+    #[error("Unsupported hillshading manifest version {0} (expected {VERSION})")]
+    UnsupportedVersion(u32),
+
+    #[error("Duplicate hillshading dataset \"{0}\"")]
+    Duplicate(String),
+
+    #[error("Hillshading dataset \"{name}\": unknown dataset \"{reference}\" in better_than")]
+    UnknownReference { name: String, reference: String },
+
+    #[error("More than one fallback hillshading dataset (\"{0}\" and \"{1}\")")]
+    MultipleFallbacks(String, String),
+
+    #[error("Hypsometric tint elevations must ascend")]
+    UnorderedTint,
 }
 
-pub struct HillshadingDatasets {
+#[derive(Deserialize)]
+struct ManifestDef {
+    version: u32,
+    #[serde(default)]
+    tint: Tint,
+    datasets: Vec<HillshadingDataset>,
+}
+
+/// Hypsometric tint: colours at ascending elevations in metres, interpolated
+/// between them.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Tint(#[serde(deserialize_with = "deserialize_tint")] Vec<(f64, Color)>);
+
+fn deserialize_tint<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(f64, Color)>, D::Error> {
+    Vec::<(f64, String)>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(elevation, color)| {
+            let (r, g, b) = parse_hex_rgb(&color).ok_or_else(|| {
+                de::Error::invalid_value(de::Unexpected::Str(&color), &"a #rrggbb colour")
+            })?;
+
+            Ok((
+                elevation,
+                (
+                    (r * 255.0).round() as i64,
+                    (g * 255.0).round() as i64,
+                    (b * 255.0).round() as i64,
+                ),
+            ))
+        })
+        .collect()
+}
+
+impl Tint {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn color_at(&self, elevation: f64) -> Color {
+        let index = self.0.partition_point(|(stop, _)| *stop <= elevation);
+
+        match (
+            index.checked_sub(1).map(|index| self.0[index]),
+            self.0.get(index),
+        ) {
+            (Some((low, (r1, g1, b1))), Some(&(high, (r2, g2, b2)))) => {
+                let t = (elevation - low) / (high - low);
+
+                let mix = |a: i64, b: i64| ((b - a) as f64).mul_add(t, a as f64).round() as i64;
+
+                (mix(r1, r2), mix(g1, g2), mix(b1, b2))
+            }
+            (Some((_, color)), None) | (None, Some(&(_, color))) => color,
+            (None, None) => colors::WHITE,
+        }
+    }
+}
+
+/// Content of a dataset raster.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatasetKind {
+    /// Pre-rendered shading.
+    #[default]
+    Shading,
+    /// Single band elevation in metres, shaded per tile.
+    Elevation,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HillshadingDataset {
+    pub name: String,
+    path: PathBuf,
+    #[serde(default)]
+    pub kind: DatasetKind,
+    /// Area with data in EPSG:3857, declared in degrees.
+    #[serde(default, deserialize_with = "deserialize_coverage")]
+    coverage: Option<Rect>,
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    better_than: Vec<String>,
+    /// Contour table drawn in the area of the dataset.
+    #[serde(default)]
+    pub contours: Option<String>,
+    /// Detailed enough for the terrain to replace cliffs, gullies, … drawn
+    /// by `feature_lines_maskable`.
+    #[serde(default)]
+    pub detailed: bool,
+    #[serde(default)]
+    fallback: bool,
+    /// Datasets replacing this one where both have data, the inverse of
+    /// `better_than`.
+    #[serde(skip)]
+    pub replaced_by: Vec<String>,
+}
+
+fn deserialize_coverage<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Rect>, D::Error> {
+    let Some([min_lon, min_lat, max_lon, max_lat]) = Option::<[f64; 4]>::deserialize(deserializer)?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(Rect::new(
+        lon_lat_to_epsg3857(min_lon, min_lat),
+        lon_lat_to_epsg3857(max_lon, max_lat),
+    )))
+}
+
+impl HillshadingDataset {
+    /// Read through a GDAL virtual file system, e.g. a COG over HTTP.
+    fn is_remote(&self) -> bool {
+        self.path
+            .to_str()
+            .is_some_and(|path| path.starts_with("/vsi") || path.contains("://"))
+    }
+
+    /// Path to open, HTTP URLs through `/vsicurl/`.
+    fn location(&self, base: &Path) -> PathBuf {
+        match self.path.to_str() {
+            Some(url) if url.starts_with("http://") || url.starts_with("https://") => {
+                PathBuf::from(format!("/vsicurl/{url}"))
+            }
+            _ => base.join(&self.path),
+        }
+    }
+
+    fn covers(&self, bbox: Rect) -> bool {
+        self.coverage
+            .is_none_or(|coverage| coverage.intersects(&bbox))
+    }
+
+    /// Part of `bbox` within the coverage.
+    pub(crate) fn area(&self, bbox: Rect) -> MultiPolygon {
+        match self.coverage {
+            Some(coverage) => coverage.to_polygon().intersection(&bbox.to_polygon()),
+            None => bbox.to_polygon().into(),
+        }
+    }
+}
+
+/// Hillshading datasets available in the base directory, as declared by its
+/// manifest.
+#[derive(Debug, Clone)]
+pub struct HillshadingManifest {
     base: PathBuf,
-    datasets: HashMap<String, CachedDataset>,
-    use_counter: u64,
+    tint: Tint,
+    /// Ordered by priority.
+    datasets: Vec<HillshadingDataset>,
+    fallback: Option<HillshadingDataset>,
 }
 
-impl HillshadingDatasets {
-    pub fn new(base: impl AsRef<Path>) -> Self {
+impl HillshadingManifest {
+    /// Loads `datasets.yaml` of `base` or, if there is none, the built-in
+    /// manifest. Datasets whose file is missing are left out.
+    pub fn load(base: impl AsRef<Path>) -> Result<Self, HillshadingManifestError> {
+        let base = base.as_ref();
+
+        let path = base.join(MANIFEST_FILE);
+
+        let input = if path.exists() {
+            read_to_string(&path).map_err(|source| HillshadingManifestError::Io { path, source })?
+        } else {
+            BUILTIN.to_string()
+        };
+
+        Self::parse(base, &input)
+    }
+
+    fn parse(base: &Path, input: &str) -> Result<Self, HillshadingManifestError> {
+        let def: ManifestDef = serde_yaml::from_str(input)?;
+
+        if def.version != VERSION {
+            return Err(HillshadingManifestError::UnsupportedVersion(def.version));
+        }
+
+        if def.tint.0.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(HillshadingManifestError::UnorderedTint);
+        }
+
+        let mut names = HashSet::new();
+
+        for dataset in &def.datasets {
+            if !names.insert(dataset.name.as_str()) {
+                return Err(HillshadingManifestError::Duplicate(dataset.name.clone()));
+            }
+        }
+
+        for dataset in &def.datasets {
+            if let Some(reference) = dataset
+                .better_than
+                .iter()
+                .find(|reference| !names.contains(reference.as_str()))
+            {
+                return Err(HillshadingManifestError::UnknownReference {
+                    name: dataset.name.clone(),
+                    reference: reference.clone(),
+                });
+            }
+        }
+
+        let mut datasets: Vec<_> = def
+            .datasets
+            .iter()
+            .filter(|dataset| {
+                // remote datasets are checked when opened
+                let exists = dataset.is_remote() || base.join(&dataset.path).exists();
+
+                if !exists {
+                    eprintln!(
+                        "Skipping hillshading dataset {}: {} not found",
+                        dataset.name,
+                        base.join(&dataset.path).display()
+                    );
+                }
+
+                exists
+            })
+            .cloned()
+            .collect();
+
+        let available: HashSet<_> = datasets
+            .iter()
+            .map(|dataset| dataset.name.clone())
+            .collect();
+
+        for dataset in &mut datasets {
+            dataset.replaced_by = def
+                .datasets
+                .iter()
+                .filter(|better| {
+                    available.contains(&better.name) && better.better_than.contains(&dataset.name)
+                })
+                .map(|better| better.name.clone())
+                .collect();
+        }
+
+        let mut fallback = None::<HillshadingDataset>;
+
+        for dataset in datasets.extract_if(.., |dataset| dataset.fallback) {
+            if let Some(fallback) = fallback {
+                return Err(HillshadingManifestError::MultipleFallbacks(
+                    fallback.name,
+                    dataset.name,
+                ));
+            }
+
+            fallback = Some(dataset);
+        }
+
+        datasets.sort_by_key(|dataset| dataset.priority);
+
+        Ok(Self {
+            base: base.to_path_buf(),
+            tint: def.tint,
+            datasets,
+            fallback,
+        })
+    }
+
+    fn dataset(&self, name: &str) -> Option<&HillshadingDataset> {
+        self.datasets
+            .iter()
+            .chain(&self.fallback)
+            .find(|dataset| dataset.name == name)
+    }
+
+    fn path(&self, name: &str) -> Option<PathBuf> {
+        self.dataset(name)
+            .map(|dataset| dataset.location(&self.base))
+    }
+}
+
+/// Reads remote COGs by HTTP range requests without listing their directory
+/// and caches the fetched ranges.
+fn configure_remote_reads() {
+    for (key, value) in [
+        ("GDAL_DISABLE_READDIR_ON_OPEN", "EMPTY_DIR"),
+        ("GDAL_HTTP_MERGE_CONSECUTIVE_RANGES", "YES"),
+        ("GDAL_HTTP_MULTIPLEX", "YES"),
+        ("VSI_CACHE", "TRUE"),
+    ] {
+        if let Err(err) = gdal::config::set_config_option(key, value) {
+            eprintln!("Error setting {key}: {err}");
+        }
+    }
+}
+
+struct IdleDataset {
+    dataset: Dataset,
+    idle_since: Instant,
+}
+
+/// Counters of [`HillshadingPool`] since its creation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HillshadingPoolMetrics {
+    /// Datasets served by an idle handle.
+    pub hits: u64,
+    /// Datasets opened.
+    pub opens: u64,
+    /// Idle handles closed.
+    pub evictions: u64,
+    /// Handles currently idle in the pool.
+    pub idle: u64,
+}
+
+/// Dataset handles shared by the render threads of the process. GDAL handles
+/// must not be used by two threads at once, so a render checks out a handle
+/// of every dataset it reads and returns them when it is done; idle handles
+/// are reused by any thread and closed after a while.
+pub struct HillshadingPool {
+    manifest: HillshadingManifest,
+    idle: Mutex<HashMap<String, Vec<IdleDataset>>>,
+    hits: AtomicU64,
+    opens: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl HillshadingPool {
+    pub fn new(manifest: HillshadingManifest) -> Self {
+        if manifest
+            .datasets
+            .iter()
+            .chain(&manifest.fallback)
+            .any(HillshadingDataset::is_remote)
+        {
+            configure_remote_reads();
+        }
+
         Self {
-            base: base.as_ref().to_path_buf(),
-            datasets: HashMap::new(),
-            use_counter: 0,
+            manifest,
+            idle: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            opens: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
         }
     }
 
-    pub fn evict_unused(&mut self) {
-        let threshold = self.use_counter.saturating_sub(MAX_UNUSED_USES);
+    /// Limits the GDAL block cache, which is shared by all datasets of the
+    /// process, to `megabytes`.
+    pub fn set_cache_budget(megabytes: usize) {
+        if let Err(err) = gdal::config::set_config_option("GDAL_CACHEMAX", &megabytes.to_string()) {
+            eprintln!("Error setting the GDAL cache budget: {err}");
+        }
+    }
 
-        let now = Instant::now();
+    pub fn metrics(&self) -> HillshadingPoolMetrics {
+        let idle = self
+            .idle
+            .lock()
+            .unwrap()
+            .values()
+            .map(|handles| handles.len() as u64)
+            .sum();
 
-        self.datasets.retain(|_, cached| {
-            cached.last_use >= threshold && now.duration_since(cached.last_used_at) <= EVICT_AFTER
-        });
+        HillshadingPoolMetrics {
+            hits: self.hits.load(Ordering::Relaxed),
+            opens: self.opens.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+            idle,
+        }
     }
 
-    pub fn get(&mut self, name: &str) -> Option<&Dataset> {
-        match self.datasets.entry(name.to_string()) {
-            Entry::Occupied(occ) => Some(&occ.into_mut().dataset),
-            Entry::Vacant(vac) => {
-                let Some(path) = dataset_path(name) else {
-                    eprintln!("Unknown hillshading dataset key: {name}");
-                    return None;
-                };
-
-                let full_path = self.base.join(path);
-
-                match Dataset::open(&full_path) {
-                    Ok(dataset) => {
-                        let entry = vac.insert(CachedDataset {
-                            dataset,
-                            last_use: self.use_counter,
-                            last_used_at: Instant::now(),
-                        });
-                        Some(&entry.dataset)
-                    }
-                    Err(err) => {
-                        eprintln!(
-                            "Error opening hillshading geotiff {}: {}",
-                            full_path.display(),
-                            err
-                        );
-                        None
-                    }
-                }
+    /// Takes an idle handle of `name` or opens one, closing handles idle for
+    /// too long, so that they are closed also while nothing is returned.
+    fn checkout(&self, name: &str) -> Option<Dataset> {
+        let idle = {
+            let mut idle = self.idle.lock().unwrap();
+
+            self.evict(&mut idle, Instant::now());
+
+            idle.get_mut(name).and_then(Vec::pop)
+        };
+
+        if let Some(idle) = idle {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+
+            return Some(idle.dataset);
+        }
+
+        let Some(full_path) = self.manifest.path(name) else {
+            eprintln!("Unknown hillshading dataset key: {name}");
+            return None;
+        };
+
+        match Dataset::open(&full_path) {
+            Ok(dataset) => {
+                self.opens.fetch_add(1, Ordering::Relaxed);
+
+                Some(dataset)
+            }
+            Err(err) => {
+                eprintln!(
+                    "Error opening hillshading geotiff {}: {}",
+                    full_path.display(),
+                    err
+                );
+                None
             }
         }
     }
 
-    pub fn record_use(&mut self, name: &str) {
-        self.use_counter = self.use_counter.saturating_add(1);
+    /// Returns handles to the pool and closes those idle for too long.
+    fn checkin(&self, datasets: impl IntoIterator<Item = (String, Dataset)>) {
+        let now = Instant::now();
+
+        let mut idle = self.idle.lock().unwrap();
 
-        if let Some(entry) = self.datasets.get_mut(name) {
-            entry.last_use = self.use_counter;
-            entry.last_used_at = Instant::now();
+        for (name, dataset) in datasets {
+            idle.entry(name).or_default().push(IdleDataset {
+                dataset,
+                idle_since: now,
+            });
         }
+
+        self.evict(&mut idle, now);
+    }
+
+    fn evict(&self, idle: &mut HashMap<String, Vec<IdleDataset>>, now: Instant) {
+        let mut evictions = 0;
+
+        idle.retain(|_, handles| {
+            let count = handles.len();
+
+            handles.retain(|handle| now.duration_since(handle.idle_since) <= EVICT_AFTER);
+
+            evictions += count - handles.len();
+
+            !handles.is_empty()
+        });
+
+        self.evictions
+            .fetch_add(evictions as u64, Ordering::Relaxed);
     }
 }
 
-fn dataset_path(name: &str) -> Option<&'static str> {
-    DATASET_PATHS
-        .iter()
-        .find(|(dataset_name, _)| dataset_name == &name)
-        .map(|(_, path)| *path)
+/// Datasets of a render thread, checked out of the shared pool while
+/// rendering a tile.
+pub struct HillshadingDatasets {
+    pool: Arc<HillshadingPool>,
+    datasets: HashMap<String, Dataset>,
+}
+
+impl HillshadingDatasets {
+    pub fn new(pool: Arc<HillshadingPool>) -> Self {
+        Self {
+            pool,
+            datasets: HashMap::new(),
+        }
+    }
+
+    fn manifest(&self) -> &HillshadingManifest {
+        &self.pool.manifest
+    }
+
+    /// Datasets (except the fallback) which may have data in `bbox`, in the
+    /// order of compositing.
+    pub(crate) fn covering(&self, bbox: Rect) -> Vec<HillshadingDataset> {
+        self.manifest()
+            .datasets
+            .iter()
+            .filter(|dataset| dataset.covers(bbox))
+            .cloned()
+            .collect()
+    }
+
+    /// Covering datasets with the part of `bbox` where each is on top: over
+    /// the datasets of lower priority, unless they are better than it, and
+    /// under the better ones.
+    pub(crate) fn top_areas(&self, bbox: Rect) -> Vec<(HillshadingDataset, MultiPolygon)> {
+        let datasets = self.covering(bbox);
+
+        datasets
+            .iter()
+            .enumerate()
+            .map(|(i, dataset)| {
+                let mut area = dataset.area(bbox);
+
+                for (j, other) in datasets.iter().enumerate() {
+                    let above = dataset.replaced_by.contains(&other.name)
+                        || (j > i && !other.replaced_by.contains(&dataset.name));
+
+                    if j != i && above {
+                        area = area.difference(&other.area(bbox));
+                    }
+                }
+
+                (dataset.clone(), area)
+            })
+            .collect()
+    }
+
+    /// Dataset shading the area outside of all other datasets.
+    pub(crate) fn fallback(&self, bbox: Rect) -> Option<HillshadingDataset> {
+        self.manifest()
+            .fallback
+            .as_ref()
+            .filter(|dataset| dataset.covers(bbox))
+            .cloned()
+    }
+
+    pub(crate) fn tint(&self) -> &Tint {
+        &self.manifest().tint
+    }
+
+    pub(crate) fn kind(&self, name: &str) -> DatasetKind {
+        self.manifest()
+            .dataset(name)
+            .map(|dataset| dataset.kind)
+            .unwrap_or_default()
+    }
+
+    /// Returns the datasets used by the render to the pool.
+    pub fn release(&mut self) {
+        self.pool.checkin(self.datasets.drain());
+    }
+
+    pub fn get(&mut self, name: &str) -> Option<&Dataset> {
+        match self.datasets.entry(name.to_string()) {
+            Entry::Occupied(occ) => Some(occ.into_mut()),
+            Entry::Vacant(vac) => Some(vac.insert(self.pool.checkout(name)?)),
+        }
+    }
 }
 
-pub fn load_hillshading_datasets(base: impl AsRef<Path>) -> HillshadingDatasets {
-    HillshadingDatasets::new(base)
+impl Drop for HillshadingDatasets {
+    fn drop(&mut self) {
+        self.release();
+    }
 }
@@ -1,17 +1,17 @@
 use crate::{
-    collision::Collision,
     colors,
     ctx::Ctx,
     draw::{
         create_pango_layout::FontAndLayoutOptions,
         text::{TextOptions, draw_text},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_point},
 };
 use postgres::Client;
 
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("housenumbers::render");
 
     let sql = r#"
@@ -38,14 +38,26 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(128.0)).as_params())?;
 
+    if ctx.emit_rows("housenumbers", &rows, &["housenumber"]) {
+        return Ok(());
+    }
+
     for row in rows {
-        draw_text(
-            ctx.context,
-            Some(collision),
-            &geometry_point(&row).project_to_tile(&ctx.tile_projector),
-            row.get("housenumber"),
-            &text_options,
-        )?;
+        let point = geometry_point(&row).project_to_tile(&ctx.tile_projector);
+
+        let housenumber: String = row.get("housenumber");
+
+        labels.submit(priority::HOUSENUMBER, None, move |collision| {
+            draw_text(
+                ctx.context,
+                Some(collision),
+                &point,
+                &housenumber,
+                &text_options,
+            )?;
+
+            Ok(())
+        });
     }
 
     Ok(())
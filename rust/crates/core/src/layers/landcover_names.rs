@@ -1,36 +1,18 @@
-use std::sync::LazyLock;
-
 use crate::{
-    collision::Collision,
     colors,
     ctx::Ctx,
     draw::{
         create_pango_layout::FontAndLayoutOptions,
         text::{TextOptions, draw_text},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_point},
-    regex_replacer::{Replacement, replace},
 };
 use pangocairo::pango::Style;
 use postgres::Client;
-use regex::Regex;
-
-static REPLACEMENTS: LazyLock<Vec<Replacement>> = LazyLock::new(|| {
-    vec![
-        (
-            Regex::new(r"[Čč]istička odpadových vôd").expect("regex"),
-            "ČOV",
-        ),
-        (
-            Regex::new(r"[Pp]oľnohospodárske družstvo").expect("regex"),
-            "PD",
-        ),
-        (Regex::new(r"[Nn]ámestie").expect("regex"), "nám. "),
-    ]
-});
-
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("landcover_names::render");
 
     // nested sql is to remove duplicate entries imported by imposm because we use `mappings` in yaml
@@ -57,19 +39,18 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
                 osm_landusages.geometry && ST_Expand(ST_MakeEnvelope($1, $2, $3, $4, 3857), $5)
             ORDER BY
                 osm_landusages.osm_id, osm_landusages.type IN ('forest', 'wood', 'scrub', 'heath', 'grassland', 'scree', 'blockfield', 'meadow', 'fell', 'wetland') DESC
-        ) SELECT name, area, \"natural\", ST_PointOnSurface(geometry) AS geometry FROM lcn ORDER BY z_order, osm_id";
-
-    let mut text_options = TextOptions {
-        flo: FontAndLayoutOptions {
-            style: Style::Italic,
-            ..FontAndLayoutOptions::default()
-        },
-        color: colors::PROTECTED,
-        ..TextOptions::default()
-    };
+        ) SELECT osm_id, name, area, \"natural\", ST_PointOnSurface(geometry) AS geometry FROM lcn ORDER BY z_order, osm_id";
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(512.0)).as_params())?;
 
+    if ctx.emit_rows(
+        "landcover_names",
+        &rows,
+        &["osm_id", "name", "area", "natural"],
+    ) {
+        return Ok(());
+    }
+
     for row in rows {
         let area: f32 = row.get("area");
 
@@ -79,25 +60,41 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
             continue;
         }
 
-        text_options.flo.style = if natural {
-            Style::Italic
-        } else {
-            Style::Normal
+        let text_options = TextOptions {
+            flo: FontAndLayoutOptions {
+                style: if natural {
+                    Style::Italic
+                } else {
+                    Style::Normal
+                },
+                ..FontAndLayoutOptions::default()
+            },
+            color: if natural {
+                colors::PROTECTED
+            } else {
+                colors::AREA_LABEL
+            },
+            ..TextOptions::default()
         };
 
-        text_options.color = if natural {
-            colors::PROTECTED
-        } else {
-            colors::AREA_LABEL
-        };
+        let position = geometry_point(&row);
+
+        let name: &str = row.get("name");
+
+        let feature = ctx.hit_feature("landuse", row.get("osm_id"), &[("name", name)]);
+
+        let point = position.project_to_tile(&ctx.tile_projector);
+
+        let name = ctx
+            .abbreviations
+            .replace(&position, "landcover", name)
+            .into_owned();
+
+        labels.submit(priority::LANDCOVER, feature, move |collision| {
+            draw_text(ctx.context, Some(collision), &point, &name, &text_options)?;
 
-        draw_text(
-            ctx.context,
-            Some(collision),
-            &geometry_point(&row).project_to_tile(&ctx.tile_projector),
-            &replace(row.get("name"), &REPLACEMENTS),
-            &text_options,
-        )?;
+            Ok(())
+        });
     }
 
     Ok(())
@@ -8,13 +8,13 @@ use crate::{
     xyz::to_absolute_pixel_coords,
 };
 use cairo::{Extend, Matrix, SurfacePattern};
+use geo::Geometry;
 use postgres::Client;
 
 pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRenderResult {
     let _span = tracy_client::span!("landuse::render");
 
     let context = ctx.context;
-    let min = ctx.bbox.min();
 
     let a = "'pitch', 'playground', 'golf_course', 'track'";
 
@@ -34,6 +34,7 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let query = &format!(
         "SELECT
+            osm_id,
             CASE
                 WHEN type = 'wetland' AND tags->'wetland' IN ('bog', 'reedbed', 'marsh', 'swamp', 'wet_meadow', 'mangrove', 'fen')
                 THEN tags->'wetland'
@@ -60,6 +61,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = client.query(query, &ctx.bbox_query_params(None).as_params())?;
 
+    if ctx.emit_rows("landuse", &rows, &["osm_id", "type"]) {
+        return Ok(());
+    }
+
     context.save()?;
 
     for row in rows {
@@ -69,244 +74,254 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
             continue;
         };
 
-        let colour_area = |color: Color| -> cairo::Result<()> {
-            context.set_source_color(color);
-            path_geometry(context, &geom);
-            context.fill()?;
+        let typ = row.get("type");
 
-            Ok(())
-        };
+        draw_area(ctx, svg_repo, typ, &geom)?;
 
-        let mut pattern_area = |path: &str| -> LayerRenderResult {
-            let tile = svg_repo.get(path)?;
+        ctx.hit_area("landuse", row.get("osm_id"), &[("type", typ)], &geom)?;
+    }
 
-            let pattern = SurfacePattern::create(tile);
+    context.restore()?;
 
-            let (x, y) = to_absolute_pixel_coords(min.x, min.y, ctx.zoom as u8);
+    Ok(())
+}
+
+/// Look of a landuse type: a fill colour, patterns painted over it and an
+/// outline of a colour and opacity.
+pub(crate) struct Landuse {
+    pub typ: &'static str,
+    fill: Option<Color>,
+    patterns: &'static [&'static str],
+    outline: Option<(Color, f64)>,
+    /// Shown in the legend; types looking like a listed one or not drawn
+    /// visibly are left out.
+    pub listed: bool,
+}
+
+impl Landuse {
+    const fn new(typ: &'static str) -> Self {
+        Self {
+            typ,
+            fill: None,
+            patterns: &[],
+            outline: None,
+            listed: true,
+        }
+    }
 
-            let rect = tile.extents().expect("tile extents");
+    const fn fill(mut self, color: Color) -> Self {
+        self.fill = Some(color);
 
-            let mut matrix = Matrix::identity();
-            matrix.translate((x % rect.width()).round(), (y % rect.height()).round());
-            pattern.set_matrix(matrix);
+        self
+    }
 
-            pattern.set_extend(Extend::Repeat);
+    const fn patterns(mut self, patterns: &'static [&'static str]) -> Self {
+        self.patterns = patterns;
 
-            context.set_source(&pattern)?;
+        self
+    }
 
-            path_geometry(context, &geom);
+    const fn outline(mut self, color: Color, alpha: f64) -> Self {
+        self.outline = Some((color, alpha));
 
-            context.fill()?;
+        self
+    }
 
-            Ok(())
-        };
+    const fn unlisted(mut self) -> Self {
+        self.listed = false;
 
-        let typ: &str = row.get("type");
-
-        match typ {
-            "allotments" => {
-                colour_area(colors::ALLOTMENTS)?;
-            }
-            "cemetery" | "grave_yard" => {
-                colour_area(colors::GRASSY)?;
-                pattern_area("grave")?;
-            }
-            "clearcut" => {
-                pattern_area("clearcut2")?;
-            }
-            "bare_rock" => {
-                pattern_area("bare_rock")?;
-            }
-            "beach" => {
-                colour_area(colors::BEACH)?;
-                pattern_area("sand")?;
-            }
-            "brownfield" => {
-                colour_area(colors::BROWNFIELD)?;
-            }
-            "bog" => {
-                colour_area(colors::GRASSY)?;
-                pattern_area("wetland")?;
-                pattern_area("bog")?;
-            }
-            "college" => {
-                colour_area(colors::COLLEGE)?;
-            }
-            "commercial" => {
-                colour_area(colors::COMMERCIAL)?;
-            }
-            "dam" => {
-                colour_area(colors::DAM)?;
-            }
-            "farmland" => {
-                colour_area(colors::FARMLAND)?;
-            }
-            "farmyard" => {
-                colour_area(colors::FARMYARD)?;
-            }
-            "fell" => {
-                colour_area(colors::GRASSY)?;
-            }
-            "marsh" | "wet_meadow" | "fen" => {
-                colour_area(colors::GRASSY)?;
-                pattern_area("wetland")?;
-                pattern_area("marsh")?;
-            }
-            "footway" => {
-                colour_area(colors::NONE)?;
-            }
-            "forest" => {
-                colour_area(colors::FOREST)?;
-
-                context.set_source_rgb(0.0, 0.0, 0.0);
-                context.set_line_width(1.0);
-            }
-            "garages" => {
-                colour_area(colors::NONE)?;
-            }
-            "grass" => {
-                colour_area(colors::GRASSY)?;
-            }
-            "garden" => {
-                colour_area(colors::ORCHARD)?;
-
-                context.set_source_rgba(0.0, 0.0, 0.0, 0.2);
-                context.set_line_width(1.0);
-                path_geometry(context, &geom);
-                context.stroke()?;
-            }
-            "grassland" => {
-                colour_area(colors::GRASSY)?;
-            }
-            "heath" => {
-                colour_area(colors::HEATH)?;
-            }
-            "hospital" => {
-                colour_area(colors::HOSPITAL)?;
-            }
-            "industrial" => {
-                colour_area(colors::INDUSTRIAL)?;
-            }
-            "landfill" => {
-                colour_area(colors::LANDFILL)?;
-            }
-            "living_street" => {
-                colour_area(colors::RESIDENTIAL)?;
-            }
-            "mangrove" => {
-                colour_area(colors::GRASSY)?;
-                pattern_area("wetland")?;
-                pattern_area("mangrove")?;
-            }
-            "meadow" => {
-                colour_area(colors::GRASSY)?;
-            }
-            "orchard" => {
-                colour_area(colors::ORCHARD)?;
-                pattern_area("orchard")?;
-            }
-            "park" => {
-                colour_area(colors::GRASSY)?;
-            }
-            "parking" => {
-                colour_area(colors::PARKING)?;
-
-                context.set_source_color(colors::PARKING_STROKE);
-                context.set_line_width(1.0);
-                path_geometry(context, &geom);
-                context.stroke()?;
-            }
-            "pedestrian" => {
-                colour_area(colors::NONE)?;
-            }
-            "pitch" | "playground" | "golf_course" | "track" => {
-                colour_area(colors::PITCH)?;
-
-                context.set_source_color(colors::PITCH_STROKE);
-                context.set_line_width(1.0);
-                path_geometry(context, &geom);
-                context.stroke()?;
-            }
-            "plant_nursery" => {
-                colour_area(colors::SCRUB)?;
-                pattern_area("plant_nursery")?;
-            }
-            "quarry" => {
-                colour_area(colors::QUARRY)?;
-                pattern_area("quarry")?;
-            }
-            "glacier" => {
-                colour_area(colors::GLACIER)?;
-                pattern_area("glacier")?;
-            }
-            "railway" => {
-                colour_area(colors::NONE)?;
-            }
-            "reedbed" => {
-                colour_area(colors::GRASSY)?;
-                pattern_area("wetland")?;
-                pattern_area("reedbed")?;
-            }
-            "recreation_ground" => {
-                colour_area(colors::RECREATION_GROUND)?;
-            }
-            "residential" => {
-                colour_area(colors::RESIDENTIAL)?;
-            }
-            "retail" => {
-                colour_area(colors::COMMERCIAL)?;
-            }
-            "silo" => {
-                colour_area(colors::SILO)?;
-
-                context.set_source_color(colors::SILO_STROKE);
-                context.set_line_width(1.0);
-                path_geometry(context, &geom);
-                context.stroke()?;
-            }
-            "school" => {
-                colour_area(colors::COLLEGE)?;
-            }
-            "scree" | "blockfield" => {
-                colour_area(colors::SCREE)?;
-                pattern_area("scree")?;
-            }
-            "scrub" => {
-                colour_area(colors::SCRUB)?;
-                pattern_area("scrub")?;
-            }
-            "swamp" => {
-                colour_area(colors::GRASSY)?;
-                pattern_area("wetland")?;
-                pattern_area("swamp")?;
-            }
-            "university" => {
-                colour_area(colors::COLLEGE)?;
-            }
-            "village_green" => {
-                colour_area(colors::GRASSY)?;
-            }
-            "vineyard" => {
-                colour_area(colors::ORCHARD)?;
-                pattern_area("grapes")?;
-            }
-            "wastewater_plant" => {
-                colour_area(colors::INDUSTRIAL)?;
-            }
-            "weir" => {
-                colour_area(colors::DAM)?;
-            }
-            "wetland" => {
-                pattern_area("wetland")?;
-            }
-            "wood" => {
-                colour_area(colors::FOREST)?;
-            }
-            _ => (),
-        }
+        self
     }
+}
 
-    context.restore()?;
+/// Landuse types drawn by [`draw_area`], in legend order.
+pub(crate) const LANDUSES: &[Landuse] = &[
+    Landuse::new("forest").fill(colors::FOREST),
+    Landuse::new("wood").fill(colors::FOREST),
+    Landuse::new("scrub")
+        .fill(colors::SCRUB)
+        .patterns(&["scrub"]),
+    Landuse::new("meadow").fill(colors::GRASSY),
+    Landuse::new("grassland").fill(colors::GRASSY),
+    Landuse::new("grass").fill(colors::GRASSY).unlisted(),
+    Landuse::new("heath").fill(colors::HEATH),
+    Landuse::new("fell").fill(colors::GRASSY),
+    Landuse::new("bare_rock").patterns(&["bare_rock"]),
+    Landuse::new("scree")
+        .fill(colors::SCREE)
+        .patterns(&["scree"]),
+    Landuse::new("blockfield")
+        .fill(colors::SCREE)
+        .patterns(&["scree"]),
+    Landuse::new("glacier")
+        .fill(colors::GLACIER)
+        .patterns(&["glacier"]),
+    Landuse::new("clearcut").patterns(&["clearcut2"]),
+    Landuse::new("wetland").patterns(&["wetland"]),
+    Landuse::new("bog")
+        .fill(colors::GRASSY)
+        .patterns(&["wetland", "bog"]),
+    Landuse::new("marsh")
+        .fill(colors::GRASSY)
+        .patterns(&["wetland", "marsh"]),
+    Landuse::new("wet_meadow")
+        .fill(colors::GRASSY)
+        .patterns(&["wetland", "marsh"])
+        .unlisted(),
+    Landuse::new("fen")
+        .fill(colors::GRASSY)
+        .patterns(&["wetland", "marsh"])
+        .unlisted(),
+    Landuse::new("reedbed")
+        .fill(colors::GRASSY)
+        .patterns(&["wetland", "reedbed"]),
+    Landuse::new("swamp")
+        .fill(colors::GRASSY)
+        .patterns(&["wetland", "swamp"]),
+    Landuse::new("mangrove")
+        .fill(colors::GRASSY)
+        .patterns(&["wetland", "mangrove"]),
+    Landuse::new("beach")
+        .fill(colors::BEACH)
+        .patterns(&["sand"]),
+    Landuse::new("farmland").fill(colors::FARMLAND),
+    Landuse::new("farmyard").fill(colors::FARMYARD),
+    Landuse::new("orchard")
+        .fill(colors::ORCHARD)
+        .patterns(&["orchard"]),
+    Landuse::new("vineyard")
+        .fill(colors::ORCHARD)
+        .patterns(&["grapes"]),
+    Landuse::new("plant_nursery")
+        .fill(colors::SCRUB)
+        .patterns(&["plant_nursery"]),
+    Landuse::new("allotments").fill(colors::ALLOTMENTS),
+    Landuse::new("garden")
+        .fill(colors::ORCHARD)
+        .outline(colors::GARDEN_STROKE, 0.2),
+    Landuse::new("park").fill(colors::GRASSY),
+    Landuse::new("village_green").fill(colors::GRASSY),
+    Landuse::new("recreation_ground").fill(colors::RECREATION_GROUND),
+    Landuse::new("pitch")
+        .fill(colors::PITCH)
+        .outline(colors::PITCH_STROKE, 1.0),
+    Landuse::new("playground")
+        .fill(colors::PITCH)
+        .outline(colors::PITCH_STROKE, 1.0)
+        .unlisted(),
+    Landuse::new("golf_course")
+        .fill(colors::PITCH)
+        .outline(colors::PITCH_STROKE, 1.0)
+        .unlisted(),
+    Landuse::new("track")
+        .fill(colors::PITCH)
+        .outline(colors::PITCH_STROKE, 1.0)
+        .unlisted(),
+    Landuse::new("cemetery")
+        .fill(colors::GRASSY)
+        .patterns(&["grave"]),
+    Landuse::new("grave_yard")
+        .fill(colors::GRASSY)
+        .patterns(&["grave"])
+        .unlisted(),
+    Landuse::new("residential").fill(colors::RESIDENTIAL),
+    Landuse::new("living_street")
+        .fill(colors::RESIDENTIAL)
+        .unlisted(),
+    Landuse::new("commercial").fill(colors::COMMERCIAL),
+    Landuse::new("retail").fill(colors::COMMERCIAL).unlisted(),
+    Landuse::new("industrial").fill(colors::INDUSTRIAL),
+    Landuse::new("wastewater_plant")
+        .fill(colors::INDUSTRIAL)
+        .unlisted(),
+    Landuse::new("brownfield").fill(colors::BROWNFIELD),
+    Landuse::new("quarry")
+        .fill(colors::QUARRY)
+        .patterns(&["quarry"]),
+    Landuse::new("landfill").fill(colors::LANDFILL),
+    Landuse::new("dam").fill(colors::DAM),
+    Landuse::new("weir").fill(colors::DAM).unlisted(),
+    Landuse::new("parking")
+        .fill(colors::PARKING)
+        .outline(colors::PARKING_STROKE, 1.0),
+    Landuse::new("silo")
+        .fill(colors::SILO)
+        .outline(colors::SILO_STROKE, 1.0),
+    Landuse::new("hospital").fill(colors::HOSPITAL),
+    Landuse::new("college").fill(colors::COLLEGE),
+    Landuse::new("school").fill(colors::COLLEGE).unlisted(),
+    Landuse::new("university").fill(colors::COLLEGE).unlisted(),
+    Landuse::new("footway").fill(colors::NONE).unlisted(),
+    Landuse::new("pedestrian").fill(colors::NONE).unlisted(),
+    Landuse::new("garages").fill(colors::NONE).unlisted(),
+    Landuse::new("railway").fill(colors::NONE).unlisted(),
+];
+
+/// Draws a landuse area of type `typ` already projected to the tile, as
+/// defined by [`LANDUSES`]; other types are not drawn.
+pub(crate) fn draw_area(
+    ctx: &Ctx,
+    svg_repo: &mut SvgRepo,
+    typ: &str,
+    geom: &Geometry,
+) -> LayerRenderResult {
+    let Some(landuse) = LANDUSES.iter().find(|landuse| landuse.typ == typ) else {
+        return Ok(());
+    };
+
+    let context = ctx.context;
+
+    if let Some(color) = landuse.fill {
+        context.set_source_color(color);
+        path_geometry(context, geom);
+        context.fill()?;
+    }
+
+    for path in landuse.patterns {
+        let tile = svg_repo.get(path)?;
+
+        let pattern = SurfacePattern::create(tile);
+
+        let min = ctx.bbox.min();
+
+        let (x, y) = to_absolute_pixel_coords(min.x, min.y, ctx.zoom as u8);
+
+        let rect = tile.extents().expect("tile extents");
+
+        let mut matrix = Matrix::identity();
+        matrix.translate((x % rect.width()).round(), (y % rect.height()).round());
+        pattern.set_matrix(matrix);
+
+        pattern.set_extend(Extend::Repeat);
+
+        context.set_source(&pattern)?;
+
+        path_geometry(context, geom);
+
+        context.fill()?;
+    }
+
+    if let Some((color, alpha)) = landuse.outline {
+        context.set_source_color_a(color, alpha);
+        context.set_line_width(1.0);
+        path_geometry(context, geom);
+        context.stroke()?;
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::LANDUSES;
+    use std::collections::HashSet;
+
+    #[test]
+    fn types_are_unique() {
+        let mut types = HashSet::new();
+
+        for landuse in LANDUSES {
+            assert!(types.insert(landuse.typ), "{} listed twice", landuse.typ);
+        }
+    }
+}
@@ -23,6 +23,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
 
     let rows = &client.query(sql, &params.as_params())?;
 
+    if ctx.emit_rows("military_areas", rows, &[]) {
+        return Ok(());
+    }
+
     ctx.context.push_group();
 
     ctx.context.push_group();
@@ -55,6 +59,8 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
         context.paint()?;
     }
 
+    ctx.knock_out_labels(1.0)?;
+
     context.pop_group_to_source()?;
     context.paint_with_alpha(if ctx.zoom < 14 { 0.5 / 0.8 } else { 0.2 / 0.8 })?;
 
@@ -1,16 +1,23 @@
 use crate::ImageFormat;
 use crate::SvgRepo;
+use crate::abbreviations::Abbreviations;
 use crate::collision::Collision;
 use crate::ctx::Ctx;
-use crate::layer_render_error::LayerRenderError;
+use crate::debug_overlay::DebugOverlay;
+use crate::labels::{LabelMask, Labels};
+use crate::layer_render_error::{LayerRenderError, LayerRenderResult};
 pub use crate::layers::hillshading_datasets::HillshadingDatasets;
+use crate::mvt::MvtTile;
+use crate::pois::Pois;
 use crate::projectable::TileProjector;
 use crate::render_request::RenderRequest;
 use crate::size::Size;
+use crate::utfgrid::UtfGrid;
 use cairo::{Context, Surface};
 use geo::Geometry;
 use geo::Rect;
 use postgres::Client;
+use std::cell::{OnceCell, RefCell};
 use thiserror::Error;
 
 mod aerialway_names;
@@ -22,22 +29,23 @@ mod borders;
 mod bridge_areas;
 mod building_names;
 mod buildings;
-mod contours;
+pub(crate) mod contours;
 mod country_names;
 mod custom;
 mod cutlines;
+pub(crate) mod dem;
 mod embankments;
 mod feature_lines;
 mod feature_lines_maskable;
-mod features;
+pub(crate) mod features;
 mod fixmes;
 mod geonames;
 mod highway_names;
-mod hillshading;
+pub(crate) mod hillshading;
 pub(crate) mod hillshading_datasets;
 mod housenumbers;
 mod landcover_names;
-mod landuse;
+pub(crate) mod landuse;
 mod locality_names;
 mod military_areas;
 mod national_park_names;
@@ -46,11 +54,13 @@ mod place_names;
 mod power_lines;
 mod protected_area_names;
 mod protected_areas;
+mod raster_level;
 mod road_access_restrictions;
-mod roads;
+pub(crate) mod roads;
 pub(crate) mod routes;
 mod sea;
 mod shading_and_contours;
+mod slope;
 mod solar_power_plants;
 mod special_park_names;
 mod special_parks;
@@ -93,10 +103,14 @@ pub fn render(
     bbox: Rect<f64>,
     size: Size<u32>,
     svg_repo: &mut SvgRepo,
+    abbreviations: &Abbreviations,
+    pois: &Pois,
     hillshading_datasets: &mut Option<HillshadingDatasets>,
     hillshade_scale: f64,
     mask_geometry: Option<&Geometry>,
     render_scale: f64,
+    hit_areas: Option<&RefCell<UtfGrid>>,
+    mvt: Option<&RefCell<MvtTile>>,
 ) -> Result<(), RenderError> {
     let _span = tracy_client::span!("render_tile::draw");
 
@@ -108,207 +122,349 @@ pub fn render(
         context.scale(render_scale, render_scale);
     }
 
-    let collision = &mut Collision::new(Some(context));
+    let debug = (!request.debug.is_empty()).then(|| RefCell::new(DebugOverlay::new(request.debug)));
+
+    let debug = debug.as_ref();
+
+    // attributes the time since the previous layer to `name`
+    let layer = |name: &'static str, result: LayerRenderResult| {
+        if let Some(debug) = debug {
+            debug.borrow_mut().lap(name);
+        }
+
+        result.with_layer(name)
+    };
+
+    let collision = &mut Collision::new(debug, hit_areas);
 
     let zoom = request.zoom;
 
+    // vector tiles carry no raster data
+    let shading = request.shading && mvt.is_none();
+    let tint = request.hypsometric_tint && mvt.is_none();
+
     let ctx = &Ctx {
         context,
         bbox,
         size,
         zoom,
         tile_projector: TileProjector::new(bbox, size),
+        abbreviations: abbreviations.for_tile(bbox),
+        hit_areas,
+        mvt,
+        debug,
+        label_mask: OnceCell::new(),
     };
 
-    sea::render(ctx, client).with_layer("sea")?;
+    let slope = request.slope && mvt.is_none() && zoom >= 10;
 
-    ctx.context.push_group();
+    if request.overlay_only {
+        if slope && let Some(hillshading_datasets) = hillshading_datasets {
+            layer(
+                "slope",
+                slope::render(ctx, hillshading_datasets, hillshade_scale),
+            )?;
 
-    landuse::render(ctx, client, svg_repo).with_layer("landuse")?;
+            hillshading_datasets.release();
+        }
 
-    if zoom >= 13 {
-        cutlines::render(ctx, client).with_layer("cutlines")?;
-    }
+        if let Some(debug) = debug {
+            debug
+                .borrow()
+                .draw(context, size)
+                .map_err(|err| LayerRenderError::from(err))
+                .with_layer("debug")?;
+        }
 
-    water_lines::render(ctx, client, svg_repo).with_layer("water_lines")?;
+        return Ok(());
+    }
 
-    water_areas::render(ctx, client).with_layer("water_areas")?;
+    // Label layers submit candidates placed together by priority. They are
+    // placed first so that contours, hatching and routes can be knocked out
+    // under them; the labels are painted, and their hit areas filled, in
+    // their place in the layer order.
+    let mut labels = Labels::default();
 
-    if zoom >= 15 {
-        bridge_areas::render(ctx, client, false).with_layer("bridge_areas")?;
+    if (8..=14).contains(&zoom) {
+        layer(
+            "place_names",
+            place_names::render(ctx, client, Some(&mut labels)),
+        )?;
     }
 
-    if zoom >= 16 {
-        trees::render(ctx, client, svg_repo).with_layer("trees")?;
+    if (8..=10).contains(&zoom) {
+        layer(
+            "national_park_names",
+            national_park_names::render(ctx, client, &mut labels),
+        )?;
     }
 
-    if zoom >= 12 {
-        pipelines::render(ctx, client).with_layer("pipelines")?;
+    if (13..=16).contains(&zoom) {
+        layer(
+            "special_park_names",
+            special_park_names::render(ctx, client, &mut labels),
+        )?;
     }
 
-    if zoom >= 13 {
-        feature_lines::render(ctx, client, svg_repo).with_layer("feature_lines")?;
+    if zoom >= 10 {
+        layer(
+            "features",
+            features::render(ctx, client, &mut labels, svg_repo, pois),
+        )?;
     }
 
-    if zoom >= 15 {
-        feature_lines_maskable::render(
-            ctx,
-            client,
-            svg_repo,
-            hillshading_datasets,
-            request.shading,
-            hillshade_scale,
-        )
-        .with_layer("feature_lines_maskable")?;
+    if zoom >= 10 {
+        layer(
+            "water_area_names",
+            water_area_names::render(ctx, client, &mut labels),
+        )?;
     }
 
-    if zoom >= 16 {
-        embankments::render(ctx, client, svg_repo).with_layer("embankments")?;
+    if zoom >= 17 {
+        layer(
+            "building_names",
+            building_names::render(ctx, client, &mut labels),
+        )?;
     }
 
-    if zoom >= 8 {
-        roads::render(ctx, client, svg_repo).with_layer("roads")?;
+    if zoom >= 12 {
+        layer(
+            "protected_area_names",
+            protected_area_names::render(ctx, client, &mut labels),
+        )?;
     }
 
-    if zoom >= 14 {
-        road_access_restrictions::render(ctx, client, svg_repo)
-            .with_layer("road_access_restrictions")?;
+    if zoom >= 12 {
+        layer(
+            "landcover_names",
+            landcover_names::render(ctx, client, &mut labels),
+        )?;
     }
 
-    if (request.shading || request.contours)
-        && let Some(hillshading_datasets) = hillshading_datasets
-    {
-        shading_and_contours::render(
-            ctx,
-            client,
-            hillshading_datasets,
-            request.shading,
-            request.contours,
-            hillshade_scale,
-        )
-        .with_layer("shading_and_contours")?;
+    if zoom >= 15 {
+        layer(
+            "locality_names",
+            locality_names::render(ctx, client, &mut labels),
+        )?;
     }
 
-    if zoom >= 11 {
-        aeroways::render(ctx, client).with_layer("aeroways")?;
+    if zoom >= 18 {
+        layer(
+            "housenumbers",
+            housenumbers::render(ctx, client, &mut labels),
+        )?;
     }
 
-    if zoom >= 12 {
-        solar_power_plants::render(ctx, client).with_layer("solar_power_plants")?;
+    if zoom >= 15 {
+        layer(
+            "highway_names",
+            highway_names::render(ctx, client, &mut labels),
+        )?;
     }
 
-    if zoom >= 13 {
-        buildings::render(ctx, client).with_layer("buildings")?;
+    if zoom >= 14 {
+        layer(
+            "routes",
+            routes::render_labels(ctx, client, &request.route_types, &mut labels),
+        )?;
     }
 
     if zoom >= 16 {
-        barrierways::render(ctx, client).with_layer("barrierways")?;
+        layer(
+            "aerialway_names",
+            aerialway_names::render(ctx, client, &mut labels),
+        )?;
     }
 
     if zoom >= 12 {
-        aerialways::render(ctx, client).with_layer("aerialways")?;
+        layer(
+            "water_line_names",
+            water_line_names::render(ctx, client, &mut labels),
+        )?;
     }
 
+    ctx.context.push_group();
+
+    let placed = labels.place(collision).map_err(LayerRenderError::from);
+
+    let label_pattern = ctx
+        .context
+        .pop_group()
+        .map_err(|err| LayerRenderError::from(err))
+        .with_layer("labels")?;
+
+    let _ = ctx.label_mask.set(LabelMask::new(collision));
+
+    layer("labels", placed)?;
+
+    layer("sea", sea::render(ctx, client))?;
+
+    ctx.context.push_group();
+
+    layer("landuse", landuse::render(ctx, client, svg_repo))?;
+
     if zoom >= 13 {
-        power_lines::render_lines(ctx, client).with_layer("power_lines")?;
+        layer("cutlines", cutlines::render(ctx, client))?;
     }
 
-    if zoom >= 14 {
-        power_lines::render_towers_poles(ctx, client).with_layer("power_lines")?;
+    layer("water_lines", water_lines::render(ctx, client, svg_repo))?;
+
+    layer("water_areas", water_areas::render(ctx, client))?;
+
+    if zoom >= 15 {
+        layer("bridge_areas", bridge_areas::render(ctx, client, false))?;
     }
 
-    if zoom >= 8 {
-        protected_areas::render(ctx, client, svg_repo).with_layer("protected_areas")?;
+    if zoom >= 16 {
+        layer("trees", trees::render(ctx, client, svg_repo))?;
     }
 
-    if zoom >= 13 {
-        special_parks::render(ctx, client).with_layer("special_parks")?;
+    if zoom >= 12 {
+        layer("pipelines", pipelines::render(ctx, client))?;
     }
 
-    if zoom >= 10 {
-        military_areas::render(ctx, client).with_layer("military_areas")?;
+    if zoom >= 13 {
+        layer(
+            "feature_lines",
+            feature_lines::render(ctx, client, svg_repo),
+        )?;
     }
 
-    if zoom >= 8 {
-        borders::render(ctx, client).with_layer("borders")?;
+    if zoom >= 15 {
+        layer(
+            "feature_lines_maskable",
+            feature_lines_maskable::render(
+                ctx,
+                client,
+                svg_repo,
+                hillshading_datasets,
+                shading,
+                hillshade_scale,
+            ),
+        )?;
     }
 
-    routes::render_marking(ctx, client, &request.route_types, svg_repo).with_layer("routes")?;
+    if zoom >= 16 {
+        layer("embankments", embankments::render(ctx, client, svg_repo))?;
+    }
 
-    if (9..=11).contains(&zoom) {
-        geonames::render(ctx, client).with_layer("geonames")?;
+    if zoom >= 8 {
+        layer("roads", roads::render(ctx, client, svg_repo))?;
     }
 
-    if (8..=14).contains(&zoom) {
-        place_names::render(ctx, client, &mut Some(collision)).with_layer("place_names")?;
+    if zoom >= 14 {
+        layer(
+            "road_access_restrictions",
+            road_access_restrictions::render(ctx, client, svg_repo),
+        )?;
     }
 
-    if (8..=10).contains(&zoom) {
-        national_park_names::render(ctx, client, collision).with_layer("national_park_names")?;
+    if (shading || request.contours || tint)
+        && let Some(hillshading_datasets) = hillshading_datasets
+    {
+        layer(
+            "shading_and_contours",
+            shading_and_contours::render(
+                ctx,
+                client,
+                hillshading_datasets,
+                shading,
+                request.contours,
+                tint,
+                request.hillshading,
+                request.contour_interval,
+                hillshade_scale,
+            ),
+        )?;
+    }
+
+    if slope && let Some(hillshading_datasets) = hillshading_datasets {
+        layer(
+            "slope",
+            slope::render(ctx, hillshading_datasets, hillshade_scale),
+        )?;
     }
 
-    if (13..=16).contains(&zoom) {
-        special_park_names::render(ctx, client, collision).with_layer("special_park_names")?;
+    if zoom >= 11 {
+        layer("aeroways", aeroways::render(ctx, client))?;
     }
 
-    if zoom >= 10 {
-        features::render(ctx, client, collision, svg_repo).with_layer("features")?;
+    if zoom >= 12 {
+        layer(
+            "solar_power_plants",
+            solar_power_plants::render(ctx, client),
+        )?;
     }
 
-    if zoom >= 10 {
-        water_area_names::render(ctx, client, collision).with_layer("water_area_names")?;
+    if zoom >= 13 {
+        layer("buildings", buildings::render(ctx, client))?;
     }
 
-    if zoom >= 17 {
-        building_names::render(ctx, client, collision).with_layer("building_names")?;
+    if zoom >= 16 {
+        layer("barrierways", barrierways::render(ctx, client))?;
     }
 
     if zoom >= 12 {
-        protected_area_names::render(ctx, client, collision).with_layer("protected_area_names")?;
+        layer("aerialways", aerialways::render(ctx, client))?;
     }
 
-    if zoom >= 12 {
-        landcover_names::render(ctx, client, collision).with_layer("landcover_names")?;
+    if zoom >= 13 {
+        layer("power_lines", power_lines::render_lines(ctx, client))?;
     }
 
-    if zoom >= 15 {
-        locality_names::render(ctx, client, collision).with_layer("locality_names")?;
+    if zoom >= 14 {
+        layer("power_lines", power_lines::render_towers_poles(ctx, client))?;
     }
 
-    if zoom >= 18 {
-        housenumbers::render(ctx, client, collision).with_layer("housenumbers")?;
+    if zoom >= 8 {
+        layer(
+            "protected_areas",
+            protected_areas::render(ctx, client, svg_repo),
+        )?;
     }
 
-    if zoom >= 15 {
-        highway_names::render(ctx, client, collision).with_layer("highway_names")?;
+    if zoom >= 13 {
+        layer("special_parks", special_parks::render(ctx, client))?;
     }
 
-    if zoom >= 14 {
-        routes::render_labels(ctx, client, &request.route_types, collision).with_layer("routes")?;
+    if zoom >= 10 {
+        layer("military_areas", military_areas::render(ctx, client))?;
     }
 
-    if zoom >= 16 {
-        aerialway_names::render(ctx, client, collision).with_layer("aerialway_names")?;
+    if zoom >= 8 {
+        layer("borders", borders::render(ctx, client))?;
     }
 
-    if zoom >= 12 {
-        water_line_names::render(ctx, client, collision).with_layer("water_line_names")?;
+    layer(
+        "routes",
+        routes::render_marking(ctx, client, &request.route_types, svg_repo),
+    )?;
+
+    if (9..=11).contains(&zoom) {
+        layer("geonames", geonames::render(ctx, client))?;
     }
 
+    ctx.context
+        .set_source(&label_pattern)
+        .and_then(|_| ctx.context.paint())
+        .and_then(|_| collision.fill_hit_areas())
+        .map_err(|err| LayerRenderError::from(err))
+        .with_layer("labels")?;
+
     if zoom >= 14 {
-        fixmes::render(ctx, client, svg_repo).with_layer("fixmes")?;
+        layer("fixmes", fixmes::render(ctx, client, svg_repo))?;
     }
 
     if zoom >= 13 {
-        valleys_ridges::render(ctx, client).with_layer("valleys_ridges")?;
+        layer("valleys_ridges", valleys_ridges::render(ctx, client))?;
     }
 
     if zoom >= 15 {
-        place_names::render(ctx, client, &mut None).with_layer("place_names")?;
+        layer("place_names", place_names::render(ctx, client, None))?;
     }
 
     if matches!(request.format, ImageFormat::Jpeg | ImageFormat::Png) {
-        blur_edges::render(ctx, mask_geometry).with_layer("blur_edges")?;
+        layer("blur_edges", blur_edges::render(ctx, mask_geometry))?;
     }
 
     ctx.context
@@ -318,15 +474,23 @@ pub fn render(
         .with_layer("top")?;
 
     if zoom < 8 {
-        country_names::render(ctx, client).with_layer("country_names")?;
+        layer("country_names", country_names::render(ctx, client))?;
     }
 
     if let Some(ref features) = request.featues {
-        custom::render(ctx, features).with_layer("custom")?;
+        layer("custom", custom::render(ctx, features))?;
     }
 
     if let Some(hillshading_datasets) = hillshading_datasets {
-        hillshading_datasets.evict_unused();
+        hillshading_datasets.release();
+    }
+
+    if let Some(debug) = debug {
+        debug
+            .borrow()
+            .draw(context, size)
+            .map_err(|err| LayerRenderError::from(err))
+            .with_layer("debug")?;
     }
 
     Ok(())
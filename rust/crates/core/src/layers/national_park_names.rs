@@ -1,35 +1,18 @@
-use std::sync::LazyLock;
-
 use crate::{
-    collision::Collision,
     colors,
     ctx::Ctx,
     draw::{
         create_pango_layout::FontAndLayoutOptions,
         text::{TextOptions, draw_text},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_point},
-    regex_replacer::{Replacement, replace},
 };
 use pangocairo::pango::Style;
 use postgres::Client;
-use regex::Regex;
-
-pub static REPLACEMENTS: LazyLock<Vec<Replacement>> = LazyLock::new(|| {
-    vec![
-        (
-            Regex::new(r"\b[Oo]chranné [Pp]ásmo\b").expect("regex"),
-            "OP",
-        ),
-        (
-            Regex::new(r"\b[Nn]árodn(ého|ý) [Pp]arku?\b").expect("regex"),
-            "NP",
-        ),
-    ]
-});
 
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("national_park_names::render");
 
     let sql = "
@@ -53,14 +36,29 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(512.0)).as_params())?;
 
+    if ctx.emit_rows(
+        "national_park_names",
+        &rows,
+        &["type", "name", "protect_class"],
+    ) {
+        return Ok(());
+    }
+
     for row in rows {
-        draw_text(
-            ctx.context,
-            Some(collision),
-            &geometry_point(&row).project_to_tile(&ctx.tile_projector),
-            &replace(row.get("name"), &REPLACEMENTS),
-            &text_options,
-        )?;
+        let position = geometry_point(&row);
+
+        let point = position.project_to_tile(&ctx.tile_projector);
+
+        let name = ctx
+            .abbreviations
+            .replace(&position, "protected_area", row.get("name"))
+            .into_owned();
+
+        labels.submit(priority::NATIONAL_PARK, None, move |collision| {
+            draw_text(ctx.context, Some(collision), &point, &name, &text_options)?;
+
+            Ok(())
+        });
     }
 
     Ok(())
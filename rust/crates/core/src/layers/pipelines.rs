@@ -25,6 +25,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
         .query(&sql, &ctx.bbox_query_params(Some(8.0)).as_params())
         ?;
 
+    if ctx.emit_rows("pipelines", &rows, &["below"]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     for row in rows {
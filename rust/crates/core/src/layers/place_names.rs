@@ -5,16 +5,19 @@ use crate::{
         create_pango_layout::FontAndLayoutOptions,
         text::{TextOptions, draw_text},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_point},
 };
 use pangocairo::pango::Weight;
 use postgres::Client;
 
-pub fn render(
-    ctx: &Ctx,
+/// Submits the names to `labels` or, if `None`, draws them without collision
+/// detection.
+pub fn render<'a>(
+    ctx: &'a Ctx,
     client: &mut Client,
-    collision: &mut Option<&mut Collision>,
+    mut labels: Option<&mut Labels<'a>>,
 ) -> LayerRenderResult {
     let _span = tracy_client::span!("place_names::render");
 
@@ -38,38 +41,54 @@ pub fn render(
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(1024.0)).as_params())?;
 
+    if ctx.emit_rows("place_names", &rows, &["name", "type"]) {
+        return Ok(());
+    }
+
     for row in rows {
-        let (size, uppercase, halo_width) = match (zoom, row.get("type")) {
-            (6.., "city") => (1.2, true, 2.0),
-            (9.., "town") => (0.8, true, 2.0),
-            (11.., "village") => (0.55, true, 1.5),
-            (12.., "hamlet" | "allotments" | "suburb") => (0.50, false, 1.5),
-            (14.., "isolated_dwelling" | "quarter") => (0.45, false, 1.5),
-            (15.., "neighbourhood") => (0.40, false, 1.5),
-            (16.., "farm" | "borough" | "square") => (0.35, false, 1.5),
+        let (size, uppercase, halo_width, priority) = match (zoom, row.get("type")) {
+            (6.., "city") => (1.2, true, 2.0, priority::CITY),
+            (9.., "town") => (0.8, true, 2.0, priority::TOWN),
+            (11.., "village") => (0.55, true, 1.5, priority::VILLAGE),
+            (12.., "hamlet" | "allotments" | "suburb") => (0.50, false, 1.5, priority::PLACE),
+            (14.., "isolated_dwelling" | "quarter") => (0.45, false, 1.5, priority::PLACE),
+            (15.., "neighbourhood") => (0.40, false, 1.5, priority::PLACE),
+            (16.., "farm" | "borough" | "square") => (0.35, false, 1.5, priority::PLACE),
             _ => continue,
         };
 
-        draw_text(
-            ctx.context,
-            collision.as_deref_mut(),
-            &geometry_point(&row).project_to_tile(&ctx.tile_projector),
-            row.get("name"),
-            &TextOptions {
-                flo: FontAndLayoutOptions {
-                    size: size * scale,
-                    uppercase,
-                    narrow: true,
-                    weight: Weight::Bold,
-                    letter_spacing: 1.0,
-                    ..FontAndLayoutOptions::default()
+        let point = geometry_point(&row).project_to_tile(&ctx.tile_projector);
+
+        let name: String = row.get("name");
+
+        let draw = move |collision: Option<&mut Collision>| {
+            draw_text(
+                ctx.context,
+                collision,
+                &point,
+                &name,
+                &TextOptions {
+                    flo: FontAndLayoutOptions {
+                        size: size * scale,
+                        uppercase,
+                        narrow: true,
+                        weight: Weight::Bold,
+                        letter_spacing: 1.0,
+                        ..FontAndLayoutOptions::default()
+                    },
+                    halo_width,
+                    halo_opacity: 0.9,
+                    alpha: if zoom <= 14 { 1.0 } else { 0.5 },
+                    ..TextOptions::default()
                 },
-                halo_width,
-                halo_opacity: 0.9,
-                alpha: if zoom <= 14 { 1.0 } else { 0.5 },
-                ..TextOptions::default()
-            },
-        )?;
+            )
+            .map(|_| ())
+        };
+
+        match labels.as_deref_mut() {
+            Some(labels) => labels.submit(priority, None, move |collision| draw(Some(collision))),
+            None => draw(None)?,
+        }
     }
 
     Ok(())
@@ -23,6 +23,10 @@ pub fn render_lines(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
         .query(sql, &ctx.bbox_query_params(None).as_params())
         ?;
 
+    if ctx.emit_rows("power_lines", &rows, &["type"]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     context.save()?;
@@ -67,6 +71,10 @@ pub fn render_towers_poles(ctx: &Ctx, client: &mut Client) -> LayerRenderResult
         .query(&sql, &ctx.bbox_query_params(Some(1024.0)).as_params())
         ?;
 
+    if ctx.emit_rows("power_lines", &rows, &["type"]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     context.save()?;
@@ -1,5 +1,4 @@
 use crate::{
-    collision::Collision,
     colors,
     ctx::Ctx,
     draw::{
@@ -8,18 +7,18 @@ use crate::{
         text::{TextOptions, draw_text},
         text_on_line::{Align, Distribution, Repeat, TextOnLineOptions, draw_text_on_line},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
-    layers::national_park_names::REPLACEMENTS,
     projectable::{TileProjectable, geometry_geometry, geometry_point},
-    regex_replacer::replace,
 };
+use geo::InteriorPoint;
 use pangocairo::pango::Style;
 use postgres::Client;
 
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("protected_area_names::render");
 
-    let sql = "SELECT name, ST_Centroid(geometry) AS geometry
+    let sql = "SELECT osm_id, name, ST_Centroid(geometry) AS geometry
         FROM osm_protected_areas
         WHERE
             geometry && ST_Expand(ST_MakeEnvelope($1, $2, $3, $4, 3857), $5)
@@ -38,17 +37,29 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(1024.0)).as_params())?;
 
+    if ctx.emit_rows("protected_area_names", &rows, &["osm_id", "name"]) {
+        return Ok(());
+    }
+
     for row in rows {
-        draw_text(
-            ctx.context,
-            Some(collision),
-            &geometry_point(&row).project_to_tile(&ctx.tile_projector),
-            row.get("name"),
-            &text_options,
-        )?;
+        let feature = ctx.hit_feature(
+            "protected_areas",
+            row.get("osm_id"),
+            &[("name", row.get("name"))],
+        );
+
+        let point = geometry_point(&row).project_to_tile(&ctx.tile_projector);
+
+        let name: String = row.get("name");
+
+        labels.submit(priority::PROTECTED_AREA, feature, move |collision| {
+            draw_text(ctx.context, Some(collision), &point, &name, &text_options)?;
+
+            Ok(())
+        });
     }
 
-    let sql = "SELECT type, name, protect_class, ST_Boundary(geometry) AS geometry
+    let sql = "SELECT osm_id, type, name, protect_class, ST_Boundary(geometry) AS geometry
         FROM osm_protected_areas
         WHERE
             name <> '' AND
@@ -75,24 +86,44 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(1024.0)).as_params())?;
 
+    if ctx.emit_rows(
+        "protected_area_names",
+        &rows,
+        &["osm_id", "type", "name", "protect_class"],
+    ) {
+        return Ok(());
+    }
+
     for row in rows {
         let Some(geom) = geometry_geometry(&row) else {
             continue;
         };
 
+        let name = match geom.interior_point() {
+            Some(position) => {
+                ctx.abbreviations
+                    .replace(&position, "protected_area", row.get("name"))
+            }
+            None => row.get::<_, &str>("name").into(),
+        }
+        .into_owned();
+
+        let feature = ctx.hit_feature(
+            "protected_areas",
+            row.get("osm_id"),
+            &[("type", row.get("type")), ("name", row.get("name"))],
+        );
+
         let geom = geom.project_to_tile(&ctx.tile_projector);
 
-        walk_geometry_line_strings(&geom, &mut |geom| {
-            let _drawn = draw_text_on_line(
-                ctx.context,
-                geom,
-                &replace(row.get("name"), &REPLACEMENTS),
-                Some(collision),
-                &text_options,
-            )?;
-
-            cairo::Result::Ok(())
-        })?;
+        labels.submit(priority::PROTECTED_AREA, feature, move |collision| {
+            walk_geometry_line_strings(&geom, &mut |geom| {
+                let _drawn =
+                    draw_text_on_line(ctx.context, geom, &name, Some(collision), &text_options)?;
+
+                cairo::Result::Ok(())
+            })
+        });
     }
 
     Ok(())
@@ -28,6 +28,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = &client.query(sql, &ctx.bbox_query_params(Some(10.0)).as_params())?;
 
+    if ctx.emit_rows("protected_areas", rows, &["type", "protect_class"]) {
+        return Ok(());
+    }
+
     let geometries: Vec<_> = rows
         .iter()
         .filter_map(|row| {
@@ -61,6 +65,8 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
                 context.stroke()?;
 
+                ctx.knock_out_labels(1.0)?;
+
                 context.pop_group_to_source()?;
                 context.paint()?;
             }
@@ -94,6 +100,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = &client.query(sql, &params.as_params())?;
 
+    if ctx.emit_rows("protected_areas", rows, &["type", "protect_class"]) {
+        return Ok(());
+    }
+
     let geometries: Vec<_> = rows
         .iter()
         .filter_map(|row| {
@@ -0,0 +1,77 @@
+use gdal::{Dataset, errors::GdalError, raster::RasterBand};
+
+/// Resolution level of a dataset to read from: the full resolution or one of
+/// its overviews (internal, `.ovr` or those of a VRT's sources).
+pub struct RasterLevel<'a> {
+    dataset: &'a Dataset,
+    /// Overview index, `None` for the full resolution.
+    overview: Option<usize>,
+    pub geo_transform: [f64; 6],
+    pub size: (usize, usize),
+}
+
+impl<'a> RasterLevel<'a> {
+    /// Picks the coarsest level still as detailed as `pixel_size` (dataset
+    /// units per pixel read), so that low zooms read and resample a small
+    /// window of an overview instead of the full resolution.
+    pub fn for_pixel_size(dataset: &'a Dataset, pixel_size: f64) -> Result<Self, GdalError> {
+        let geo_transform = dataset.geo_transform()?;
+
+        let size = dataset.raster_size();
+
+        let mut level = Self {
+            dataset,
+            overview: None,
+            geo_transform,
+            size,
+        };
+
+        let band = dataset.rasterband(1)?;
+
+        for index in 0..band.overview_count()?.max(0) as usize {
+            let overview_size = band.overview(index)?.size();
+
+            if overview_size.0 == 0 || overview_size.1 == 0 {
+                continue;
+            }
+
+            let factor_x = size.0 as f64 / overview_size.0 as f64;
+            let factor_y = size.1 as f64 / overview_size.1 as f64;
+
+            // a little coarser than the pixels read is still sharp enough
+            if geo_transform[1].abs() * factor_x > pixel_size * 1.05
+                || overview_size.0 >= level.size.0
+            {
+                continue;
+            }
+
+            let [x_off, x_width, x_rot, y_off, y_rot, y_height] = geo_transform;
+
+            level = Self {
+                dataset,
+                overview: Some(index),
+                geo_transform: [
+                    x_off,
+                    x_width * factor_x,
+                    x_rot * factor_y,
+                    y_off,
+                    y_rot * factor_x,
+                    y_height * factor_y,
+                ],
+                size: overview_size,
+            };
+        }
+
+        Ok(level)
+    }
+
+    /// Band `index` (1-based) at this level.
+    pub fn band(&self, index: usize) -> Result<RasterBand<'a>, GdalError> {
+        let band = self.dataset.rasterband(index)?;
+
+        match self.overview {
+            Some(overview) => band.overview(overview),
+            None => Ok(band),
+        }
+    }
+}
@@ -40,6 +40,14 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(32.0)).as_params())?;
 
+    if ctx.emit_rows(
+        "road_access_restrictions",
+        &rows,
+        &["no_bicycle", "no_foot"],
+    ) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     for row in rows {
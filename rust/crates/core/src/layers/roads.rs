@@ -4,13 +4,272 @@ use crate::draw::markers_on_path::draw_markers_on_path;
 use crate::layer_render_error::LayerRenderResult;
 use crate::projectable::{TileProjectable, geometry_line_string};
 use crate::{colors, ctx::Ctx, draw::path_geom::path_line_string};
-use postgres::Client;
+use geo::{Geometry, LineString};
+use postgres::{Client, Row};
+
+/// Road (or railway, pier, …) segment already projected to the tile.
+pub(crate) struct Road<'a> {
+    pub osm_id: i64,
+    pub geometry: LineString,
+    pub kind: RoadKind,
+    pub typ: &'a str,
+    pub class: &'a str,
+    pub service: &'a str,
+    pub tracktype: &'a str,
+    pub bicycle: &'a str,
+    pub foot: &'a str,
+    pub bridge: i16,
+    pub tunnel: i16,
+    pub oneway: i16,
+    pub trail_visibility: f64,
+    pub is_in_route: bool,
+}
+
+impl<'a> Road<'a> {
+    fn from_row(row: &'a Row, ctx: &Ctx) -> Self {
+        let typ = row.get("type");
+        let class = row.get("class");
+
+        Self {
+            osm_id: row.get("osm_id"),
+            geometry: geometry_line_string(row).project_to_tile(&ctx.tile_projector),
+            kind: RoadKind::of(class, typ),
+            typ,
+            class,
+            service: row.get("service"),
+            tracktype: row.get("tracktype"),
+            bicycle: row.get("bicycle"),
+            foot: row.get("foot"),
+            bridge: row.get("bridge"),
+            tunnel: row.get("tunnel"),
+            oneway: row.get("oneway"),
+            trail_visibility: row.get("trail_visibility"),
+            is_in_route: row.get("is_in_route"),
+        }
+    }
+
+    /// Road of `kind` with defaults for the other attributes.
+    pub fn sample(geometry: LineString, kind: RoadKind, tracktype: &'a str) -> Self {
+        let (class, typ) = kind.class_and_type();
+
+        Self {
+            osm_id: 0,
+            geometry,
+            kind,
+            typ,
+            class,
+            service: "",
+            tracktype,
+            bicycle: "",
+            foot: "",
+            bridge: 0,
+            tunnel: 0,
+            oneway: 0,
+            trail_visibility: 1.0,
+            is_in_route: false,
+        }
+    }
+}
+
+/// Kinds of roads (or railways, piers, …) told apart by [`draw`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RoadKind {
+    Motorway,
+    MotorwayLink,
+    Trunk,
+    TrunkLink,
+    Primary,
+    PrimaryLink,
+    Secondary,
+    SecondaryLink,
+    Tertiary,
+    TertiaryLink,
+    Residential,
+    LivingStreet,
+    Unclassified,
+    UnknownRoad,
+    Service,
+    Construction,
+    Track,
+    Path,
+    Cycleway,
+    Bridleway,
+    Footway,
+    Pedestrian,
+    Steps,
+    ViaFerrata,
+    Piste,
+    Escape,
+    Corridor,
+    BusGuideway,
+    Rail,
+    Tram,
+    LightRail,
+    NarrowGauge,
+    Miniature,
+    Monorail,
+    Funicular,
+    Subway,
+    Disused,
+    RailwayConstruction,
+    Preserved,
+    Platform,
+    LeisureTrack,
+    Raceway,
+    WaterSlide,
+    Pier,
+    /// Not drawn.
+    Other,
+}
+
+impl RoadKind {
+    /// Drawn kinds, in legend order.
+    pub(crate) const ALL: &[Self] = &[
+        Self::Motorway,
+        Self::MotorwayLink,
+        Self::Trunk,
+        Self::TrunkLink,
+        Self::Primary,
+        Self::PrimaryLink,
+        Self::Secondary,
+        Self::SecondaryLink,
+        Self::Tertiary,
+        Self::TertiaryLink,
+        Self::Residential,
+        Self::LivingStreet,
+        Self::Unclassified,
+        Self::UnknownRoad,
+        Self::Service,
+        Self::Construction,
+        Self::Track,
+        Self::Path,
+        Self::Cycleway,
+        Self::Bridleway,
+        Self::Footway,
+        Self::Pedestrian,
+        Self::Steps,
+        Self::ViaFerrata,
+        Self::Piste,
+        Self::Escape,
+        Self::Corridor,
+        Self::BusGuideway,
+        Self::Rail,
+        Self::Tram,
+        Self::LightRail,
+        Self::NarrowGauge,
+        Self::Miniature,
+        Self::Monorail,
+        Self::Funicular,
+        Self::Subway,
+        Self::Disused,
+        Self::RailwayConstruction,
+        Self::Preserved,
+        Self::Platform,
+        Self::LeisureTrack,
+        Self::Raceway,
+        Self::WaterSlide,
+        Self::Pier,
+    ];
+
+    /// `(class, type)` of the kind; piers and raceways are of any class.
+    pub(crate) const fn class_and_type(self) -> (&'static str, &'static str) {
+        match self {
+            Self::Motorway => ("highway", "motorway"),
+            Self::MotorwayLink => ("highway", "motorway_link"),
+            Self::Trunk => ("highway", "trunk"),
+            Self::TrunkLink => ("highway", "trunk_link"),
+            Self::Primary => ("highway", "primary"),
+            Self::PrimaryLink => ("highway", "primary_link"),
+            Self::Secondary => ("highway", "secondary"),
+            Self::SecondaryLink => ("highway", "secondary_link"),
+            Self::Tertiary => ("highway", "tertiary"),
+            Self::TertiaryLink => ("highway", "tertiary_link"),
+            Self::Residential => ("highway", "residential"),
+            Self::LivingStreet => ("highway", "living_street"),
+            Self::Unclassified => ("highway", "unclassified"),
+            Self::UnknownRoad => ("highway", "road"),
+            Self::Service => ("highway", "service"),
+            Self::Construction => ("highway", "construction"),
+            Self::Track => ("highway", "track"),
+            Self::Path => ("highway", "path"),
+            Self::Cycleway => ("highway", "cycleway"),
+            Self::Bridleway => ("highway", "bridleway"),
+            Self::Footway => ("highway", "footway"),
+            Self::Pedestrian => ("highway", "pedestrian"),
+            Self::Steps => ("highway", "steps"),
+            Self::ViaFerrata => ("highway", "via_ferrata"),
+            Self::Piste => ("highway", "piste"),
+            Self::Escape => ("highway", "escape"),
+            Self::Corridor => ("highway", "corridor"),
+            Self::BusGuideway => ("highway", "bus_guideway"),
+            Self::Rail => ("railway", "rail"),
+            Self::Tram => ("railway", "tram"),
+            Self::LightRail => ("railway", "light_rail"),
+            Self::NarrowGauge => ("railway", "narrow_gauge"),
+            Self::Miniature => ("railway", "miniature"),
+            Self::Monorail => ("railway", "monorail"),
+            Self::Funicular => ("railway", "funicular"),
+            Self::Subway => ("railway", "subway"),
+            Self::Disused => ("railway", "disused"),
+            Self::RailwayConstruction => ("railway", "construction"),
+            Self::Preserved => ("railway", "preserved"),
+            Self::Platform => ("railway", "platform"),
+            Self::LeisureTrack => ("leisure", "track"),
+            Self::Raceway => ("highway", "raceway"),
+            Self::WaterSlide => ("attraction", "water_slide"),
+            Self::Pier => ("man_made", "pier"),
+            Self::Other => ("", ""),
+        }
+    }
+
+    fn of(class: &str, typ: &str) -> Self {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| {
+                let (kind_class, kind_typ) = kind.class_and_type();
+
+                kind_typ == typ
+                    && (kind_class == class || matches!(kind, Self::Pier | Self::Raceway))
+            })
+            .unwrap_or(Self::Other)
+    }
+
+    /// Track grades of the samples of the kind in the legend, `""` for none;
+    /// no samples for kinds looking like a listed one.
+    pub(crate) const fn legend_tracktypes(self) -> &'static [&'static str] {
+        match self {
+            Self::Track => &["grade1", "grade2", "grade3", "grade4", "grade5", ""],
+            Self::MotorwayLink
+            | Self::TrunkLink
+            | Self::PrimaryLink
+            | Self::SecondaryLink
+            | Self::TertiaryLink
+            | Self::LivingStreet
+            | Self::Unclassified
+            | Self::UnknownRoad
+            | Self::Pedestrian
+            | Self::Escape
+            | Self::Corridor
+            | Self::BusGuideway
+            | Self::LightRail
+            | Self::Miniature
+            | Self::Monorail
+            | Self::Funicular
+            | Self::Subway
+            | Self::RailwayConstruction
+            | Self::Preserved
+            | Self::Platform
+            | Self::Raceway
+            | Self::Other => &[],
+            _ => &[""],
+        }
+    }
+}
 
 pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRenderResult {
     let _span = tracy_client::span!("roads::render");
 
-    let context = ctx.context;
-
     let zoom = ctx.zoom;
 
     // TODO no roads on zoom 7 and lower
@@ -22,13 +281,62 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
     };
 
     let query = format!("
-        SELECT {table}.geometry, {table}.type, tracktype, class, service, bridge, tunnel, oneway, bicycle, foot,
+        SELECT {table}.osm_id, {table}.geometry, {table}.type, tracktype, class, service, bridge, tunnel, oneway, bicycle, foot,
             power(0.666, greatest(0, trail_visibility - 1))::DOUBLE PRECISION AS trail_visibility,
             osm_route_members.member IS NOT NULL AS is_in_route
         FROM {table} LEFT JOIN osm_route_members ON osm_route_members.type = 1 AND osm_route_members.member = {table}.osm_id
         WHERE {table}.geometry && ST_Expand(ST_MakeEnvelope($1, $2, $3, $4, 3857), $5)
         ORDER BY z_order, CASE WHEN {table}.type = 'rail' AND service IN ('', 'main') THEN 2 ELSE 1 END, {table}.osm_id");
 
+    let rows = client.query(&query, &ctx.bbox_query_params(Some(128.0)).as_params())?;
+
+    if ctx.emit_rows(
+        "roads",
+        &rows,
+        &[
+            "osm_id",
+            "type",
+            "tracktype",
+            "class",
+            "service",
+            "bridge",
+            "tunnel",
+            "oneway",
+            "bicycle",
+            "foot",
+            "trail_visibility",
+            "is_in_route",
+        ],
+    ) {
+        return Ok(());
+    }
+
+    let roads: Vec<_> = rows.iter().map(|row| Road::from_row(row, ctx)).collect();
+
+    draw(ctx, svg_repo, &roads)?;
+
+    if ctx.hit_areas.is_some() {
+        for road in roads {
+            ctx.hit_area(
+                "roads",
+                road.osm_id,
+                &[("class", road.class), ("type", road.typ)],
+                &Geometry::LineString(road.geometry),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Draws roads in the given order.
+pub(crate) fn draw(ctx: &Ctx, svg_repo: &mut SvgRepo, roads: &[Road]) -> LayerRenderResult {
+    use RoadKind::*;
+
+    let context = ctx.context;
+
+    let zoom = ctx.zoom;
+
     let apply_highway_defaults = |width: f64| {
         context.set_dash(&[], 0.0);
         context.set_source_color(colors::TRACK);
@@ -49,8 +357,6 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let highway_width_coef = || 1.5f64.powf(8.6f64.max(zoom as f64) - 8.0);
 
-    let rows = client.query(&query, &ctx.bbox_query_params(Some(128.0)).as_params())?;
-
     let ke = || match zoom {
         12 => 0.66,
         13 => 0.75,
@@ -65,20 +371,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     context.save()?;
 
-    let rows: Vec<_> = rows
-        .iter()
-        .map(|row| {
-            (
-                row,
-                geometry_line_string(row).project_to_tile(&ctx.tile_projector),
-            )
-        })
-        .collect();
-
-    for (row, geom) in &rows {
-        let typ: &str = row.get("type");
+    for road in roads {
+        let kind = road.kind;
 
-        let class: &str = row.get("class");
+        let geom = &road.geometry;
 
         let draw = || -> cairo::Result<()> {
             path_line_string(context, geom);
@@ -88,14 +384,13 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
             Ok(())
         };
 
-        match (zoom, class, typ) {
-            (..=11, _, _) => (),
-            (14.., "highway", "footway" | "pedestrian" | "steps")
-            | (14.., "railway", "platform") => {
+        match (zoom, kind) {
+            (..=11, _) => (),
+            (14.., Footway | Pedestrian | Steps | Platform) => {
                 apply_glow_defaults(1.0);
                 draw()?;
             }
-            (14.., "highway", "via_ferrata") => {
+            (14.., ViaFerrata) => {
                 apply_glow_defaults(3.0);
                 context.set_source_rgb(0.0, 0.0, 0.0);
                 context.set_dash(&[0.0, 4.0, 4.0, 0.0], 0.0);
@@ -104,54 +399,49 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
                 apply_glow_defaults(1.0);
                 draw()?;
             }
-            (12.., "highway", "path")
-                if row.get::<_, &str>("bicycle") != "designated"
-                    && (zoom > 12 || row.get("is_in_route")) =>
-            {
-                apply_glow_defaults_a(1.0, row.get("trail_visibility"));
+            (12.., Path) if road.bicycle != "designated" && (zoom > 12 || road.is_in_route) => {
+                apply_glow_defaults_a(1.0, road.trail_visibility);
                 draw()?;
             }
-            (12.., "highway", _)
-                if typ == "track"
-                    && (zoom > 12
-                        || row.get("is_in_route")
-                        || row.get::<_, &str>("tracktype") == "grade1")
-                    || typ == "service" && row.get::<_, &str>("service") != "parking_aisle"
-                    || ["escape", "corridor", "bus_guideway"].contains(&typ) =>
+            (12.., Track | Service | Escape | Corridor | BusGuideway)
+                if kind == Track
+                    && (zoom > 12 || road.is_in_route || road.tracktype == "grade1")
+                    || kind == Service && road.service != "parking_aisle"
+                    || matches!(kind, Escape | Corridor | BusGuideway) =>
             {
-                apply_glow_defaults_a(ke() * 1.2, row.get("trail_visibility"));
+                apply_glow_defaults_a(ke() * 1.2, road.trail_visibility);
                 draw()?;
             }
-            (14.., _, "raceway") | (14.., "leisure", "track") => {
+            (14.., Raceway | LeisureTrack) => {
                 apply_glow_defaults(1.2);
                 draw()?;
             }
-            (13.., "highway", "bridleway") => {
+            (13.., Bridleway) => {
                 apply_glow_defaults(1.2);
-                context.set_source_color_a(colors::BRIDLEWAY2, row.get("trail_visibility"));
+                context.set_source_color_a(colors::BRIDLEWAY2, road.trail_visibility);
                 draw()?;
             }
-            (_, "highway", "motorway" | "trunk") => {
+            (_, Motorway | Trunk) => {
                 apply_highway_defaults(4.0);
                 draw()?;
             }
-            (_, "highway", "primary" | "motorway_link" | "trunk_link") => {
+            (_, Primary | MotorwayLink | TrunkLink) => {
                 apply_highway_defaults(3.666);
                 draw()?;
             }
-            (_, "highway", "primary_link" | "secondary" | "construction") => {
+            (_, PrimaryLink | Secondary | Construction) => {
                 apply_highway_defaults(3.333);
                 draw()?;
             }
-            (_, "highway", "secondary_link" | "tertiary" | "tertiary_link") => {
+            (_, SecondaryLink | Tertiary | TertiaryLink) => {
                 apply_highway_defaults(3.0);
                 draw()?;
             }
-            (14.., "highway", "living_street" | "residential" | "unclassified" | "road") => {
+            (14.., LivingStreet | Residential | Unclassified | UnknownRoad) => {
                 apply_highway_defaults(2.5);
                 draw()?;
             }
-            (14.., "highway", "piste") => {
+            (14.., Piste) => {
                 apply_highway_defaults(2.2);
                 context.set_dash(&[6.0, 2.0], 0.0);
                 context.set_source_color(colors::PISTE2);
@@ -161,12 +451,12 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
         }
     }
 
-    for (row, geom) in &rows {
-        let typ: &str = row.get("type");
+    for road in roads {
+        let kind = road.kind;
 
-        let class: &str = row.get("class");
+        let service = road.service;
 
-        let service: &str = row.get("service");
+        let geom = &road.geometry;
 
         let draw = || -> cairo::Result<()> {
             path_line_string(context, geom);
@@ -177,7 +467,7 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
         };
 
         let draw_bridges_tunnels = |width: f64| -> cairo::Result<()> {
-            if row.get::<_, i16>("bridge") > 0 {
+            if road.bridge > 0 {
                 context.save()?;
                 context.set_dash(&[], 0.0);
                 context.set_source_rgb(0.0, 0.0, 0.0);
@@ -201,7 +491,7 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
                 context.restore()?;
             }
 
-            if row.get::<_, i16>("tunnel") > 0 {
+            if road.tunnel > 0 {
                 context.set_dash(&[], 0.0);
                 context.set_line_width(width + 1.0);
 
@@ -282,32 +572,27 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
             Ok(())
         };
 
-        match (zoom, class, typ) {
-            (14.., _, "pier") => {
+        match (zoom, kind) {
+            (14.., Pier) => {
                 apply_highway_defaults(2.0);
                 context.set_source_color(colors::PIER);
                 draw()?;
             }
-            (12.., "railway", "rail") if ["main", ""].contains(&service) => {
+            (12.., Rail) if ["main", ""].contains(&service) => {
                 draw_rail(colors::RAIL, 1.5, 5.0, 9.5, 1.0)?;
             }
-            (13.., "railway", _)
-                if ["light_rail", "tram"].contains(&typ)
-                    || typ == "rail" && service != "main" && !service.is_empty() =>
+            (13.., LightRail | Tram | Rail)
+                if kind != Rail || service != "main" && !service.is_empty() =>
             {
                 draw_rail(colors::TRAM, 1.0, 4.5, 9.5, 1.0)?;
             }
-            (
-                13..,
-                "railway",
-                "miniature" | "monorail" | "funicular" | "narrow_gauge" | "subway",
-            ) => {
+            (13.., Miniature | Monorail | Funicular | NarrowGauge | Subway) => {
                 draw_rail(colors::TRAM, 1.0, 4.5, 7.5, 1.0)?;
             }
-            (14.., "railway", "construction" | "disused" | "preserved") => {
+            (14.., RailwayConstruction | Disused | Preserved) => {
                 draw_rail(colors::RAILWAY_DISUSED, 1.0, 4.5, 7.5, 1.0)?;
             }
-            (8..=11, "railway", "rail") if ["main", ""].contains(&service) => {
+            (8..=11, Rail) if ["main", ""].contains(&service) => {
                 let koef = 0.8 * 1.15f64.powf((zoom - 8) as f64);
 
                 draw_rail(
@@ -318,51 +603,51 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
                     0.5 * koef,
                 )?;
             }
-            (8..=11, "highway", "motorway" | "trunk" | "motorway_link" | "trunk_link") => {
+            (8..=11, Motorway | Trunk | MotorwayLink | TrunkLink) => {
                 apply_highway_defaults(0.8 * highway_width_coef());
                 draw()?;
             }
-            (8..=11, "highway", "primary" | "primary_link") => {
+            (8..=11, Primary | PrimaryLink) => {
                 apply_highway_defaults(0.7 * highway_width_coef());
                 draw()?;
             }
-            (8..=11, "highway", "secondary" | "secondary_link") => {
+            (8..=11, Secondary | SecondaryLink) => {
                 apply_highway_defaults(0.6 * highway_width_coef());
                 draw()?;
             }
-            (8..=11, "highway", "tertiary" | "tertiary_link") => {
+            (8..=11, Tertiary | TertiaryLink) => {
                 apply_highway_defaults(0.5 * highway_width_coef());
                 draw()?;
             }
-            (12.., "highway", "motorway" | "trunk") => {
+            (12.., Motorway | Trunk) => {
                 apply_highway_defaults(2.5);
                 context.set_source_color(colors::SUPERROAD);
                 draw()?;
 
                 draw_bridges_tunnels(2.5 + 1.0)?;
             }
-            (12.., "highway", "motorway_link" | "trunk_link") => {
+            (12.., MotorwayLink | TrunkLink) => {
                 apply_highway_defaults(1.5 + 2.0 / 3.0);
                 context.set_source_color(colors::SUPERROAD);
                 draw()?;
 
                 draw_bridges_tunnels(1.5 + 2.0 / 3.0 + 1.0)?;
             }
-            (12.., "highway", "primary") => {
+            (12.., Primary) => {
                 apply_highway_defaults(1.5 + 2.0 / 3.0);
                 context.set_source_color(colors::ROAD);
                 draw()?;
 
                 draw_bridges_tunnels(1.5 + 2.0 / 3.0 + 1.0)?;
             }
-            (12.., "highway", "primary_link" | "secondary") => {
+            (12.., PrimaryLink | Secondary) => {
                 apply_highway_defaults(1.5 + 1.0 / 3.0);
                 context.set_source_color(colors::ROAD);
                 draw()?;
 
                 draw_bridges_tunnels(1.5 + 1.0 / 3.0 + 1.0)?;
             }
-            (12.., "highway", "construction") => {
+            (12.., Construction) => {
                 apply_highway_defaults(1.5 + 1.0 / 3.0);
                 context.set_source_color(colors::CONSTRUCTION_ROAD_1);
                 context.set_dash(&[5.0, 5.0], 0.0);
@@ -372,47 +657,47 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
                 context.set_dash(&[5.0, 5.0], 5.0);
                 draw()?;
             }
-            (12.., "highway", "secondary_link" | "tertiary" | "tertiary_link") => {
+            (12.., SecondaryLink | Tertiary | TertiaryLink) => {
                 apply_highway_defaults(1.5);
                 context.set_source_color(colors::ROAD);
                 draw()?;
 
                 draw_bridges_tunnels(1.5 + 1.0 / 3.0 + 1.0)?;
             }
-            (12..=13, "highway", "living_street" | "residential" | "unclassified" | "road") => {
+            (12..=13, LivingStreet | Residential | Unclassified | UnknownRoad) => {
                 apply_highway_defaults(1.0);
                 draw()?;
 
                 draw_bridges_tunnels(1.0 + 1.0)?;
             }
-            (14.., "highway", "living_street" | "residential" | "unclassified" | "road") => {
+            (14.., LivingStreet | Residential | Unclassified | UnknownRoad) => {
                 apply_highway_defaults(1.0);
                 context.set_source_color(colors::ROAD);
                 draw()?;
 
                 draw_bridges_tunnels(1.0 + 1.0)?;
             }
-            (14.., "attraction", "water_slide") => {
+            (14.., WaterSlide) => {
                 apply_highway_defaults(1.5);
                 context.set_source_color(colors::WATER_SLIDE);
                 draw()?;
 
                 draw_bridges_tunnels(1.5 + 1.0)?;
             }
-            (14.., "highway", "service") if service == "parking_aisle" => {
+            (14.., Service) if service == "parking_aisle" => {
                 apply_highway_defaults(1.0);
                 draw()?;
 
                 draw_bridges_tunnels(1.0 + 1.0)?;
             }
-            (14.., _, "raceway") | (14.., "leisure", "track") => {
+            (14.., Raceway | LeisureTrack) => {
                 apply_highway_defaults(1.2);
                 context.set_dash(&[9.5, 1.5], 0.0);
                 draw()?;
 
                 draw_bridges_tunnels(1.2 + 1.0)?;
             }
-            (14.., "highway", "piste") => {
+            (14.., Piste) => {
                 apply_highway_defaults(1.2);
                 context.set_source_color(colors::PISTE);
                 context.set_dash(&[9.5, 1.5], 0.0);
@@ -420,21 +705,20 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
                 draw_bridges_tunnels(1.2 + 1.0)?;
             }
-            (14.., "highway", "footway" | "pedestrian") | (14.., "railway", "platform") => {
+            (14.., Footway | Pedestrian | Platform) => {
                 apply_highway_defaults(1.0);
                 context.set_dash(&[4.0, 2.0], 0.0);
                 draw()?;
 
                 draw_bridges_tunnels(1.0 + 1.0)?;
             }
-            (14.., "highway", "steps") => {
+            (14.., Steps) => {
                 apply_highway_defaults(2.5);
                 context.set_dash(&[1.0, 2.0], 2.0);
                 draw()?;
             }
-            (12.., "highway", _)
-                if typ == "service" && service != "parking_aisle"
-                    || ["escape", "corridor", "bus_guideway"].contains(&typ) =>
+            (12.., Service | Escape | Corridor | BusGuideway)
+                if kind != Service || service != "parking_aisle" =>
             {
                 let width = ke() * 1.2;
 
@@ -443,61 +727,60 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
                 draw_bridges_tunnels(width + 1.0)?;
             }
-            (12.., "highway", "path")
-                if row.get::<_, &str>("bicycle") == "designated"
-                    && row.get::<_, &str>("foot") == "designated"
-                    && (zoom > 12 || row.get("is_in_route")) =>
+            (12.., Path)
+                if road.bicycle == "designated"
+                    && road.foot == "designated"
+                    && (zoom > 12 || road.is_in_route) =>
             {
                 let width = ke();
 
                 apply_highway_defaults(width);
                 context.set_dash(&[4.0, 2.0], 0.0);
-                context.set_source_color_a(colors::CYCLEWAY, row.get("trail_visibility"));
+                context.set_source_color_a(colors::CYCLEWAY, road.trail_visibility);
                 draw()?;
 
                 draw_bridges_tunnels(width + 1.0)?;
             }
-            (12.., "highway", _)
-                if (typ == "cycleway"
-                    || typ == "path"
-                        && row.get::<_, &str>("bicycle") == "designated"
-                        && row.get::<_, &str>("foot") != "designated")
-                    && (zoom > 12 || row.get("is_in_route")) =>
+            (12.., Cycleway | Path)
+                if (kind == Cycleway
+                    || kind == Path
+                        && road.bicycle == "designated"
+                        && road.foot != "designated")
+                    && (zoom > 12 || road.is_in_route) =>
             {
                 let width = ke();
 
                 apply_highway_defaults(width);
                 context.set_dash(&[6.0, 3.0], 0.0);
-                context.set_source_color_a(colors::CYCLEWAY, row.get("trail_visibility"));
+                context.set_source_color_a(colors::CYCLEWAY, road.trail_visibility);
                 draw()?;
 
                 draw_bridges_tunnels(width + 1.0)?;
             }
-            (12.., "highway", "path")
-                if (row.get::<_, &str>("bicycle") != "designated"
-                    || row.get::<_, &str>("foot") == "designated")
-                    && (zoom > 12 || row.get("is_in_route")) =>
+            (12.., Path)
+                if (road.bicycle != "designated" || road.foot == "designated")
+                    && (zoom > 12 || road.is_in_route) =>
             {
                 let width = ke();
 
                 apply_highway_defaults(width);
                 context.set_dash(&[3.0, 3.0], 0.0);
-                context.set_source_color_a(colors::TRACK, row.get("trail_visibility"));
+                context.set_source_color_a(colors::TRACK, road.trail_visibility);
                 draw()?;
 
                 draw_bridges_tunnels(width + 1.0)?;
             }
-            (12.., "highway", "bridleway") if zoom > 12 || row.get("is_in_route") => {
+            (12.., Bridleway) if zoom > 12 || road.is_in_route => {
                 let width = ke();
 
                 apply_highway_defaults(width);
                 context.set_dash(&[6.0, 3.0], 0.0);
-                context.set_source_color_a(colors::BRIDLEWAY, row.get("trail_visibility"));
+                context.set_source_color_a(colors::BRIDLEWAY, road.trail_visibility);
                 draw()?;
 
                 draw_bridges_tunnels(width + 1.0)?;
             }
-            (12.., "highway", "via_ferrata") if zoom > 12 || row.get("is_in_route") => {
+            (12.., ViaFerrata) if zoom > 12 || road.is_in_route => {
                 let width = ke();
 
                 apply_highway_defaults(width);
@@ -506,17 +789,13 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
                 draw_bridges_tunnels(width + 1.0)?;
             }
-            (12.., "highway", "track")
-                if (zoom > 12
-                    || row.get("is_in_route")
-                    || row.get::<_, &str>("tracktype") == "grade1") =>
-            {
+            (12.., Track) if (zoom > 12 || road.is_in_route || road.tracktype == "grade1") => {
                 let width = ke() * 1.2;
 
                 apply_highway_defaults(width);
 
                 context.set_dash(
-                    match row.get::<_, &str>("tracktype") {
+                    match road.tracktype {
                         "grade1" => &[],
                         "grade2" => &[8.0, 2.0],
                         "grade3" => &[6.0, 4.0],
@@ -527,7 +806,7 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
                     0.0,
                 );
 
-                context.set_source_color_a(colors::TRACK, row.get("trail_visibility"));
+                context.set_source_color_a(colors::TRACK, road.trail_visibility);
 
                 draw()?;
 
@@ -537,7 +816,7 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
             _ => (),
         };
 
-        let oneway = row.get::<_, i16>("oneway");
+        let oneway = road.oneway;
 
         if zoom >= 14 && oneway != 0 {
             path_line_string(context, geom);
@@ -563,3 +842,21 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::RoadKind;
+
+    #[test]
+    fn kinds_are_found_by_class_and_type() {
+        for &kind in RoadKind::ALL {
+            let (class, typ) = kind.class_and_type();
+
+            assert_eq!(RoadKind::of(class, typ), kind);
+        }
+
+        assert_eq!(RoadKind::of("leisure", "raceway"), RoadKind::Raceway);
+        assert_eq!(RoadKind::of("highway", "pier"), RoadKind::Pier);
+        assert_eq!(RoadKind::of("railway", "abandoned"), RoadKind::Other);
+    }
+}
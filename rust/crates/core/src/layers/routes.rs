@@ -1,6 +1,5 @@
 use crate::{
     SvgRepo,
-    collision::Collision,
     ctx::Ctx,
     draw::{
         create_pango_layout::FontAndLayoutOptions,
@@ -9,12 +8,14 @@ use crate::{
         path_geom::{path_line_string_with_offset, walk_geometry_line_strings},
         text_on_line::{Align, Distribution, Repeat, TextOnLineOptions, draw_text_on_line},
     },
+    labels::{Labels, priority},
     layer_render_error::{LayerRenderError, LayerRenderResult},
     projectable::{TileProjectable, geometry_geometry},
     svg_repo::Options,
 };
 use bitflags::bitflags;
 use colorsys::{Rgb, RgbRatio};
+use geo::Geometry;
 use postgres::Client;
 
 const COLOR_SQL: &str = r#"
@@ -41,7 +42,7 @@ const COLOR_SQL: &str = r#"
   END
 "#;
 
-const COLORS: [(&str, &str); 9] = [
+pub(crate) const COLORS: [(&str, &str); 9] = [
     ("none", "a0a0a0"),
     ("purple", "c000c0"),
     ("orange", "ff8000"),
@@ -63,6 +64,58 @@ bitflags! {
   }
 }
 
+/// Kinds of route marking, each shown by a column per colour of the routes
+/// query.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RouteKind {
+    Hiking,
+    HikingLocal,
+    Bicycle,
+    Ski,
+    Horse,
+}
+
+impl RouteKind {
+    pub(crate) const ALL: [Self; 5] = [
+        Self::Hiking,
+        Self::HikingLocal,
+        Self::Bicycle,
+        Self::Ski,
+        Self::Horse,
+    ];
+
+    pub(crate) const fn name(self) -> &'static str {
+        match self {
+            Self::Hiking => "hiking",
+            Self::HikingLocal => "hiking_local",
+            Self::Bicycle => "bicycle",
+            Self::Ski => "ski",
+            Self::Horse => "horse",
+        }
+    }
+
+    pub(crate) const fn route_types(self) -> RouteTypes {
+        match self {
+            Self::Hiking | Self::HikingLocal => RouteTypes::HIKING,
+            Self::Bicycle => RouteTypes::BICYCLE,
+            Self::Ski => RouteTypes::SKI,
+            Self::Horse => RouteTypes::HORSE,
+        }
+    }
+
+    /// Column of the routes query with the offset of a route of the kind and
+    /// `color`, e.g. `h_red`.
+    pub(crate) fn column(self, color: &str) -> String {
+        match self {
+            Self::Hiking => format!("h_{color}"),
+            Self::HikingLocal => format!("h_{color}_loc"),
+            Self::Bicycle => format!("b_{color}"),
+            Self::Ski => format!("s_{color}"),
+            Self::Horse => format!("r_{color}"),
+        }
+    }
+}
+
 fn format_vec(vec: &[&str]) -> String {
     if vec.is_empty() {
         "'_x_'".to_string()
@@ -286,6 +339,23 @@ pub fn render_marking(
 
     let rows = client.query(&query, &ctx.bbox_query_params(Some(512.0)).as_params())?;
 
+    if ctx.mvt.is_some() {
+        let columns: Vec<_> = RouteKind::ALL
+            .iter()
+            .flat_map(|kind| COLORS.iter().map(move |(color, _)| kind.column(color)))
+            .collect();
+
+        let mut attributes = vec!["refs1", "refs2"];
+
+        attributes.extend(columns.iter().map(String::as_str));
+
+        ctx.emit_rows("routes", &rows, &attributes);
+
+        return Ok(());
+    }
+
+    ctx.context.push_group();
+
     for row in rows {
         let Some(geom) = geometry_geometry(&row) else {
             continue;
@@ -293,94 +363,146 @@ pub fn render_marking(
 
         let geom = geom.project_to_tile(&ctx.tile_projector);
 
-        let (zo, wf) = match zoom {
-            ..=11 => (1.0, 1.5),
-            12 => (2.0, 1.5),
-            13.. => (3.0, 2.0),
-        }; // offset from highway
+        draw_marking(ctx, svg_repo, route_types, &geom, |column| row.get(column))?;
+    }
 
-        let df = 1.25;
+    // lightened only, not to break the marking
+    ctx.knock_out_labels(0.6)?;
 
-        for color in COLORS.iter() {
-            if route_types.contains(RouteTypes::HORSE) {
-                let off = row.get::<_, i32>(&format!("r_{}", color.0)[..]);
+    ctx.context.pop_group_to_source()?;
+    ctx.context.paint()?;
 
-                if off > 0 {
-                    let offset = ((off as f64 - 1.0) * wf).mul_add(df, zo) + 0.5;
+    Ok(())
+}
+
+/// Draws marking of routes along `geom` already projected to the tile. `off`
+/// returns the 1-based offset of a route of the colour and kind named by
+/// a column of the routes query (e.g. `h_red`), or zero if there is none.
+pub(crate) fn draw_marking(
+    ctx: &Ctx,
+    svg_repo: &mut SvgRepo,
+    route_types: &RouteTypes,
+    geom: &Geometry,
+    off: impl Fn(&str) -> i32,
+) -> LayerRenderResult {
+    let zoom = ctx.zoom;
 
-                    let sample = svg_repo.get_extra(
-                        &format!("horse-{}", color.1),
-                        Some(|| Options {
-                            names: vec!["horse".into()],
-                            stylesheet: Some(format!("path {{ fill: #{} }}", color.1)),
-                            ..Default::default()
-                        }),
+    let (zo, wf) = match zoom {
+        ..=11 => (1.0, 1.5),
+        12 => (2.0, 1.5),
+        13.. => (3.0, 2.0),
+    }; // offset from highway
+
+    let df = 1.25;
+
+    for color in COLORS.iter() {
+        if route_types.contains(RouteTypes::HORSE) {
+            let off = off(&RouteKind::Horse.column(color.0));
+
+            if off > 0 {
+                let offset = ((off as f64 - 1.0) * wf).mul_add(df, zo) + 0.5;
+
+                let sample = svg_repo.get_extra(
+                    &format!("horse-{}", color.1),
+                    Some(|| Options {
+                        names: vec!["horse".into()],
+                        stylesheet: Some(format!("path {{ fill: #{} }}", color.1)),
+                        ..Default::default()
+                    }),
+                )?;
+
+                walk_geometry_line_strings(geom, &mut |part| {
+                    draw_line_pattern_scaled(
+                        ctx,
+                        &offset_line_string(part, offset),
+                        0.5,
+                        wf / 2.0,
+                        sample,
+                    )
+                })?;
+            }
+        }
+
+        if route_types.contains(RouteTypes::SKI) {
+            let off = off(&RouteKind::Ski.column(color.0));
+
+            if off > 0 {
+                let offset = -((off as f64 - 1.0) * wf).mul_add(2.0, zo) - 1.0;
+
+                let pattern = svg_repo.get_extra(
+                    &format!("ski-{}", color.1),
+                    Some(|| Options {
+                        names: vec!["ski".into()],
+                        stylesheet: Some(format!("path {{ fill: #{} }}", color.1)),
+                        ..Default::default()
+                    }),
+                )?;
+
+                walk_geometry_line_strings::<_, LayerRenderError>(geom, &mut |part| {
+                    draw_line_pattern_scaled(
+                        ctx,
+                        &offset_line_string(part, offset),
+                        0.5,
+                        wf / 2.0,
+                        pattern,
                     )?;
 
-                    walk_geometry_line_strings(&geom, &mut |part| {
-                        draw_line_pattern_scaled(
-                            ctx,
-                            &offset_line_string(part, offset),
-                            0.5,
-                            wf / 2.0,
-                            sample,
-                        )
-                    })?;
-                }
+                    Ok(())
+                })?;
             }
+        }
 
-            if route_types.contains(RouteTypes::SKI) {
-                let off = row.get::<_, i32>(&format!("s_{}", color.0)[..]);
+        let context = ctx.context;
 
-                if off > 0 {
-                    let offset = -((off as f64 - 1.0) * wf).mul_add(2.0, zo) - 1.0;
-
-                    let pattern = svg_repo.get_extra(
-                        &format!("ski-{}", color.1),
-                        Some(|| Options {
-                            names: vec!["ski".into()],
-                            stylesheet: Some(format!("path {{ fill: #{} }}", color.1)),
-                            ..Default::default()
-                        }),
-                    )?;
+        if route_types.contains(RouteTypes::BICYCLE) {
+            let off = off(&RouteKind::Bicycle.column(color.0));
 
-                    walk_geometry_line_strings::<_, LayerRenderError>(&geom, &mut |part| {
-                        draw_line_pattern_scaled(
-                            ctx,
-                            &offset_line_string(part, offset),
-                            0.5,
-                            wf / 2.0,
-                            pattern,
-                        )?;
+            if off > 0 {
+                let offset = -((off as f64 - 1.0) * wf).mul_add(2.0, zo) - 1.0;
 
-                        Ok(())
-                    })?;
-                }
-            }
+                context.save()?;
+
+                walk_geometry_line_strings(geom, &mut |part| {
+                    path_line_string_with_offset(context, part, offset);
+
+                    cairo::Result::Ok(())
+                })?;
+
+                context.set_line_width(wf * 2.0);
+                context.set_line_join(cairo::LineJoin::Round);
+                context.set_line_cap(cairo::LineCap::Round);
 
-            let context = ctx.context;
+                let rgb: RgbRatio = Rgb::from_hex_str(color.1).expect("color").as_ratio();
+                context.set_source_rgb(rgb.r(), rgb.g(), rgb.b());
+                context.set_dash(&[0.001, wf * 3.0], 0.0);
 
-            if route_types.contains(RouteTypes::BICYCLE) {
-                let off = row.get::<_, i32>(&format!("b_{}", color.0)[..]);
+                context.stroke()?;
+
+                context.restore()?;
+            }
+        }
+
+        if route_types.contains(RouteTypes::HIKING) {
+            {
+                let off = off(&RouteKind::Hiking.column(color.0));
 
                 if off > 0 {
-                    let offset = -((off as f64 - 1.0) * wf).mul_add(2.0, zo) - 1.0;
+                    let offset = ((off as f64 - 1.0) * wf).mul_add(df, zo) + 0.5;
 
                     context.save()?;
 
-                    walk_geometry_line_strings(&geom, &mut |part| {
+                    walk_geometry_line_strings(geom, &mut |part| {
                         path_line_string_with_offset(context, part, offset);
 
                         cairo::Result::Ok(())
                     })?;
 
-                    context.set_line_width(wf * 2.0);
+                    context.set_line_width(wf);
                     context.set_line_join(cairo::LineJoin::Round);
-                    context.set_line_cap(cairo::LineCap::Round);
-
+                    context.set_line_cap(cairo::LineCap::Butt);
                     let rgb: RgbRatio = Rgb::from_hex_str(color.1).expect("color").as_ratio();
                     context.set_source_rgb(rgb.r(), rgb.g(), rgb.b());
-                    context.set_dash(&[0.001, wf * 3.0], 0.0);
+                    context.set_dash(&[], 0.0);
 
                     context.stroke()?;
 
@@ -388,59 +510,30 @@ pub fn render_marking(
                 }
             }
 
-            if route_types.contains(RouteTypes::HIKING) {
-                {
-                    let off = row.get::<_, i32>(&format!("h_{}", color.0)[..]);
-
-                    if off > 0 {
-                        let offset = ((off as f64 - 1.0) * wf).mul_add(df, zo) + 0.5;
-
-                        context.save()?;
-
-                        walk_geometry_line_strings(&geom, &mut |part| {
-                            path_line_string_with_offset(context, part, offset);
-
-                            cairo::Result::Ok(())
-                        })?;
-
-                        context.set_line_width(wf);
-                        context.set_line_join(cairo::LineJoin::Round);
-                        context.set_line_cap(cairo::LineCap::Butt);
-                        let rgb: RgbRatio = Rgb::from_hex_str(color.1).expect("color").as_ratio();
-                        context.set_source_rgb(rgb.r(), rgb.g(), rgb.b());
-                        context.set_dash(&[], 0.0);
-
-                        context.stroke()?;
-
-                        context.restore()?;
-                    }
-                }
-
-                {
-                    let off = row.get::<_, i32>(&format!("h_{}_loc", color.0)[..]);
+            {
+                let off = off(&RouteKind::HikingLocal.column(color.0));
 
-                    if off > 0 {
-                        let offset = ((off as f64 - 1.0) * wf).mul_add(df, zo) + 0.5;
+                if off > 0 {
+                    let offset = ((off as f64 - 1.0) * wf).mul_add(df, zo) + 0.5;
 
-                        context.save()?;
+                    context.save()?;
 
-                        walk_geometry_line_strings(&geom, &mut |part| {
-                            path_line_string_with_offset(context, part, offset);
+                    walk_geometry_line_strings(geom, &mut |part| {
+                        path_line_string_with_offset(context, part, offset);
 
-                            cairo::Result::Ok(())
-                        })?;
+                        cairo::Result::Ok(())
+                    })?;
 
-                        context.set_line_width(wf);
-                        context.set_line_join(cairo::LineJoin::Round);
-                        context.set_line_cap(cairo::LineCap::Butt);
-                        let rgb: RgbRatio = Rgb::from_hex_str(color.1).expect("color").as_ratio();
-                        context.set_source_rgb(rgb.r(), rgb.g(), rgb.b());
-                        context.set_dash(&[wf * 3.0, wf], 0.0);
+                    context.set_line_width(wf);
+                    context.set_line_join(cairo::LineJoin::Round);
+                    context.set_line_cap(cairo::LineCap::Butt);
+                    let rgb: RgbRatio = Rgb::from_hex_str(color.1).expect("color").as_ratio();
+                    context.set_source_rgb(rgb.r(), rgb.g(), rgb.b());
+                    context.set_dash(&[wf * 3.0, wf], 0.0);
 
-                        context.stroke()?;
+                    context.stroke()?;
 
-                        context.restore()?;
-                    }
+                    context.restore()?;
                 }
             }
         }
@@ -449,14 +542,19 @@ pub fn render_marking(
     Ok(())
 }
 
-pub fn render_labels(
-    ctx: &Ctx,
+pub fn render_labels<'a>(
+    ctx: &'a Ctx,
     client: &mut Client,
     route_types: &RouteTypes,
-    collision: &mut Collision,
+    labels: &mut Labels<'a>,
 ) -> LayerRenderResult {
     let _span = tracy_client::span!("routes::render_labels");
 
+    // labels are in the route features emitted by `render_marking`
+    if ctx.mvt.is_some() {
+        return Ok(());
+    }
+
     let query = get_routes_query(route_types, None, "");
 
     let rows = client.query(&query, &ctx.bbox_query_params(Some(2048.0)).as_params())?;
@@ -468,38 +566,41 @@ pub fn render_labels(
 
         let geom = geom.project_to_tile(&ctx.tile_projector);
 
-        walk_geometry_line_strings(&geom, &mut |geom| {
-            let refs1: &str = row.get("refs1");
-            let off1: i32 = row.get("off1");
+        let refs1: String = row.get("refs1");
+        let off1: i32 = row.get("off1");
+
+        let refs2: String = row.get("refs2");
+        let off2: i32 = row.get("off2");
+
+        labels.submit(priority::ROUTE, None, move |collision| {
+            walk_geometry_line_strings(&geom, &mut |geom| {
+                let mut options = TextOnLineOptions {
+                    flo: FontAndLayoutOptions {
+                        size: 11.0,
+                        ..Default::default()
+                    },
+                    halo_opacity: 0.2,
+                    distribution: Distribution::Align {
+                        align: Align::Center,
+                        repeat: Repeat::Spaced(500.0),
+                    },
+                    keep_offset_side: true,
+                    ..Default::default()
+                };
 
-            let refs2: &str = row.get("refs2");
-            let off2: i32 = row.get("off2");
+                for (refs, offset) in [
+                    (refs1.as_str(), -f64::from(off1).mul_add(2.5, 9.0)),
+                    (refs2.as_str(), f64::from(off2).mul_add(2.5, 10.0)),
+                ] {
+                    options.offset = offset;
 
-            let mut options = TextOnLineOptions {
-                flo: FontAndLayoutOptions {
-                    size: 11.0,
-                    ..Default::default()
-                },
-                halo_opacity: 0.2,
-                distribution: Distribution::Align {
-                    align: Align::Center,
-                    repeat: Repeat::Spaced(500.0),
-                },
-                keep_offset_side: true,
-                ..Default::default()
-            };
-
-            for (refs, offset) in [
-                (refs1, -f64::from(off1).mul_add(2.5, 9.0)),
-                (refs2, f64::from(off2).mul_add(2.5, 10.0)),
-            ] {
-                options.offset = offset;
-
-                let _drawn = draw_text_on_line(ctx.context, geom, refs, Some(collision), &options)?;
-            }
+                    let _drawn =
+                        draw_text_on_line(ctx.context, geom, refs, Some(collision), &options)?;
+                }
 
-            cairo::Result::Ok(())
-        })?;
+                cairo::Result::Ok(())
+            })
+        });
     }
 
     Ok(())
@@ -36,6 +36,12 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
 
     let rows = client.query(&sql, &params.as_params())?;
 
+    if ctx.emit_rows("land", &rows, &[]) {
+        context.restore()?;
+
+        return Ok(());
+    }
+
     for row in rows {
         let Some(geom) = geometry_geometry(&row) else {
             continue;
@@ -1,11 +1,18 @@
 use crate::{
     ctx::Ctx,
     layer_render_error::LayerRenderResult,
-    layers::{bridge_areas, contours, hillshading, hillshading_datasets::HillshadingDatasets},
+    layers::{
+        bridge_areas,
+        contours::{self, ContourInterval, ContourSource},
+        hillshading::{self, HillshadingOptions},
+        hillshading_datasets::{HillshadingDataset, HillshadingDatasets},
+    },
 };
 use postgres::Client;
 
-const FALLBACK: bool = true;
+/// Opacity of the hypsometric tint, which is painted over the map like the
+/// shading.
+const TINT_ALPHA: f64 = 0.35;
 
 pub fn render(
     ctx: &Ctx,
@@ -13,6 +20,9 @@ pub fn render(
     hillshading_datasets: &mut HillshadingDatasets,
     shading: bool,
     contours: bool,
+    tint: bool,
+    hillshading_options: HillshadingOptions,
+    contour_interval: Option<ContourInterval>,
     hillshade_scale: f64,
 ) -> LayerRenderResult {
     let _span = tracy_client::span!("shading_and_contours::render");
@@ -31,22 +41,12 @@ pub fn render(
 
     // (CC, CC, CC, (mask-$cc, mask-$cc, mask-$cc, (fallback_contours, fallback_final):src-out):src-over)
 
-    let config: Vec<(&str, Vec<&str>)> = vec![
-        ("at", vec!["sk", "si", "cz"]),
-        ("it", vec!["at", "ch", "si", "fr"]),
-        ("ch", vec!["at", "fr"]),
-        ("si", vec![]),
-        ("cz", vec!["sk", "pl"]),
-        ("pl", vec!["sk"]),
-        ("sk", vec![]),
-        ("fr", vec![]),
-        ("no", vec![]),
-    ];
-
-    for (country, better_countries) in config {
+    let datasets = hillshading_datasets.covering(ctx.bbox);
+
+    for dataset in &datasets {
         let mask_surface = hillshading::load_surface(
             ctx,
-            country,
+            &dataset.name,
             hillshading_datasets,
             hillshade_scale,
             hillshading::Mode::Mask,
@@ -62,21 +62,35 @@ pub fn render(
 
         context.push_group(); // contours-and-shading
 
+        if tint {
+            hillshading::load_and_paint_tint(
+                ctx,
+                &dataset.name,
+                TINT_ALPHA,
+                hillshading_datasets,
+                hillshade_scale,
+            )?;
+        }
+
         if contours && ctx.zoom >= 12 {
-            context.push_group(); // contours
-            contours::render(ctx, client, Some(country))?;
-            context.pop_group_to_source()?; // contours
-            context.paint_with_alpha(0.33)?;
+            render_contours(
+                ctx,
+                client,
+                hillshading_datasets,
+                Some(dataset),
+                contour_interval,
+                hillshade_scale,
+            )?;
         }
 
         if shading {
             hillshading::load_and_paint(
                 ctx,
-                country,
+                &dataset.name,
                 fade_alpha,
                 hillshading_datasets,
                 hillshade_scale,
-                hillshading::Mode::Shading,
+                hillshading::Mode::shading(hillshading_options),
             )?;
         }
 
@@ -84,13 +98,16 @@ pub fn render(
         context.set_operator(cairo::Operator::In);
         context.paint()?;
 
-        if shading {
-            for better_country in better_countries {
+        if shading || tint {
+            for better in datasets
+                .iter()
+                .filter(|better| dataset.replaced_by.contains(&better.name))
+            {
                 context.set_operator(cairo::Operator::DestOut);
 
                 hillshading::load_and_paint(
                     ctx,
-                    better_country,
+                    &better.name,
                     1.0,
                     hillshading_datasets,
                     hillshade_scale,
@@ -103,13 +120,14 @@ pub fn render(
         context.paint()?;
     }
 
-    if FALLBACK {
+    // contours and the fallback shading outside of the datasets
+    {
         let mut mask_surfaces = Vec::new();
 
-        for country in ["it", "at", "ch", "si", "pl", "sk", "cz", "fr", "no"] {
+        for dataset in &datasets {
             let mask_surface = hillshading::load_surface(
                 ctx,
-                country,
+                &dataset.name,
                 hillshading_datasets,
                 hillshade_scale,
                 hillshading::Mode::Mask,
@@ -121,6 +139,8 @@ pub fn render(
         }
 
         if !hillshading::mask_covers_tile(&mut mask_surfaces)? {
+            let fallback = hillshading_datasets.fallback(ctx.bbox);
+
             context.push_group(); // mask
 
             for mask_surface in &mask_surfaces {
@@ -129,25 +149,39 @@ pub fn render(
 
             context.push_group(); // fallback
 
+            if tint && let Some(fallback) = &fallback {
+                hillshading::load_and_paint_tint(
+                    ctx,
+                    &fallback.name,
+                    TINT_ALPHA,
+                    hillshading_datasets,
+                    hillshade_scale,
+                )?;
+            }
+
             {
                 let _span = tracy_client::span!("shading_and_contours::contours");
 
                 if contours && ctx.zoom >= 12 {
-                    context.push_group(); // contours
-                    contours::render(ctx, client, None)?;
-                    context.pop_group_to_source()?; // contours
-                    context.paint_with_alpha(0.33)?;
+                    render_contours(
+                        ctx,
+                        client,
+                        hillshading_datasets,
+                        fallback.as_ref(),
+                        contour_interval,
+                        hillshade_scale,
+                    )?;
                 }
             }
 
-            if shading {
+            if shading && let Some(fallback) = &fallback {
                 hillshading::load_and_paint(
                     ctx,
-                    "_",
+                    &fallback.name,
                     fade_alpha,
                     hillshading_datasets,
                     hillshade_scale,
-                    hillshading::Mode::Shading,
+                    hillshading::Mode::shading(hillshading_options),
                 )?;
             }
 
@@ -165,3 +199,47 @@ pub fn render(
 
     Ok(())
 }
+
+/// Extra samples around the tile for smoothing traced contours and continuing
+/// them over the tile edges.
+const CONTOUR_BORDER: usize = 2;
+
+/// Draws contours of `dataset`: traced from its elevation if it has no
+/// contour table or an interval is requested, from its contour table
+/// otherwise.
+fn render_contours(
+    ctx: &Ctx,
+    client: &mut Client,
+    hillshading_datasets: &mut HillshadingDatasets,
+    dataset: Option<&HillshadingDataset>,
+    contour_interval: Option<ContourInterval>,
+    hillshade_scale: f64,
+) -> LayerRenderResult {
+    let elevation = match dataset {
+        Some(dataset) if dataset.contours.is_none() || contour_interval.is_some() => {
+            hillshading::load_elevation(
+                ctx,
+                &dataset.name,
+                hillshading_datasets,
+                hillshade_scale,
+                CONTOUR_BORDER,
+            )?
+        }
+        _ => None,
+    };
+
+    let source = match &elevation {
+        Some(elevation) => ContourSource::Elevation(elevation, contour_interval),
+        None => ContourSource::Table(dataset.and_then(|dataset| dataset.contours.as_deref())),
+    };
+
+    let context = ctx.context;
+
+    context.push_group(); // contours
+    contours::render(ctx, client, source)?;
+    ctx.knock_out_labels(1.0)?;
+    context.pop_group_to_source()?; // contours
+    context.paint_with_alpha(0.33)?;
+
+    Ok(())
+}
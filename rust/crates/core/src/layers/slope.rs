@@ -0,0 +1,68 @@
+use crate::{
+    colors::{self, Color},
+    ctx::Ctx,
+    layer_render_error::LayerRenderResult,
+    layers::{hillshading, hillshading_datasets::HillshadingDatasets},
+};
+
+/// Slope classes of avalanche terrain, by the lower bound in degrees.
+const CLASSES: [(f64, Color); 4] = [
+    (30.0, colors::SLOPE_30),
+    (35.0, colors::SLOPE_35),
+    (40.0, colors::SLOPE_40),
+    (45.0, colors::SLOPE_45),
+];
+
+const ALPHA: f64 = 0.5;
+
+/// Paints terrain steeper than 30° by slope class, computed from the
+/// elevation datasets. Datasets of higher priority replace the lower ones
+/// where both have data.
+pub fn render(
+    ctx: &Ctx,
+    hillshading_datasets: &mut HillshadingDatasets,
+    raster_scale: f64,
+) -> LayerRenderResult {
+    let _span = tracy_client::span!("slope::render");
+
+    let context = ctx.context;
+
+    let names: Vec<_> = hillshading_datasets
+        .fallback(ctx.bbox)
+        .into_iter()
+        .chain(hillshading_datasets.covering(ctx.bbox))
+        .map(|dataset| dataset.name)
+        .collect();
+
+    context.push_group();
+
+    for name in &names {
+        let Some(elevation) =
+            hillshading::load_elevation(ctx, name, hillshading_datasets, raster_scale, 1)?
+        else {
+            continue;
+        };
+
+        context.set_operator(cairo::Operator::DestOut);
+        hillshading::paint_surface(ctx, &elevation.data_mask()?, raster_scale, 1.0)?;
+        context.set_operator(cairo::Operator::Over);
+
+        // true slope, without the exaggeration of the shading
+        let surface = elevation.to_surface(|x, y| {
+            let slope = elevation.slope(x, y, 1.0)?;
+
+            CLASSES
+                .iter()
+                .rev()
+                .find(|(min, _)| slope >= *min)
+                .map(|&(_, color)| (color, 1.0))
+        })?;
+
+        hillshading::paint_surface(ctx, &surface, raster_scale, 1.0)?;
+    }
+
+    context.pop_group_to_source()?;
+    context.paint_with_alpha(ALPHA)?;
+
+    Ok(())
+}
@@ -20,6 +20,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
 
     let rows = client.query(sql, &ctx.bbox_query_params(None).as_params())?;
 
+    if ctx.emit_rows("solar_power_plants", &rows, &[]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     for row in rows {
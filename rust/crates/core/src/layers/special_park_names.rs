@@ -1,18 +1,18 @@
 use crate::{
-    collision::Collision,
     colors,
     ctx::Ctx,
     draw::{
         create_pango_layout::FontAndLayoutOptions,
         text::{TextOptions, draw_text},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_point},
 };
 use pangocairo::pango::Style;
 use postgres::Client;
 
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("national_park_names::render");
 
     let sql = "
@@ -36,14 +36,20 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(512.0)).as_params())?;
 
+    if ctx.emit_rows("special_park_names", &rows, &["name"]) {
+        return Ok(());
+    }
+
     for row in rows {
-        draw_text(
-            ctx.context,
-            Some(collision),
-            &geometry_point(&row).project_to_tile(&ctx.tile_projector),
-            row.get("name"),
-            &text_options,
-        )?;
+        let point = geometry_point(&row).project_to_tile(&ctx.tile_projector);
+
+        let name: String = row.get("name");
+
+        labels.submit(priority::SPECIAL_PARK, None, move |collision| {
+            draw_text(ctx.context, Some(collision), &point, &name, &text_options)?;
+
+            Ok(())
+        });
     }
 
     Ok(())
@@ -22,6 +22,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = client.query(sql, &ctx.bbox_query_params(Some(32.0)).as_params())?;
 
+    if ctx.emit_rows("trees", &rows, &["type"]) {
+        return Ok(());
+    }
+
     for row in rows {
         let typ: &str = row.get("type");
 
@@ -1,5 +1,3 @@
-use std::sync::LazyLock;
-
 use crate::{
     collision::Collision,
     colors,
@@ -9,21 +7,11 @@ use crate::{
         text_on_line::{Align, Distribution, Repeat, TextOnLineOptions, draw_text_on_line},
     },
     projectable::{TileProjectable, geometry_line_string},
-    regex_replacer::{Replacement, replace},
     layer_render_error::LayerRenderResult,
 };
-use geo::ChaikinSmoothing;
+use geo::{ChaikinSmoothing, InteriorPoint};
 use pangocairo::pango::Style;
 use postgres::{Client, Row};
-use regex::Regex;
-
-static REPLACEMENTS: LazyLock<Vec<Replacement>> = LazyLock::new(|| {
-    vec![
-        (Regex::new(r"^Dolink?a\b *").expect("regex"), "Dol. "),
-        (Regex::new(r"^dolink?a\b *").expect("regex"), "dol. "),
-        (Regex::new(r" *\b[Dd]olink?a$").expect("regex"), " dol."),
-    ]
-});
 
 pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
     let _span = tracy_client::span!("valleys_ridges::render");
@@ -37,13 +25,22 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
 
     let context = ctx.context;
 
-    let collision = &mut Collision::new(Some(context));
+    let collision = &mut Collision::new(ctx.debug, ctx.hit_areas);
 
     let mut render_rows = |rows: Vec<Row>| -> cairo::Result<()> {
+        if ctx.emit_rows("valleys_ridges", &rows, &["name"]) {
+            return Ok(());
+        }
+
         for row in rows {
-            let name = replace(row.get("name"), &REPLACEMENTS);
+            let geom = geometry_line_string(&row);
+
+            let name = match geom.interior_point() {
+                Some(position) => ctx.abbreviations.replace(&position, "valley", row.get("name")),
+                None => row.get::<_, &str>("name").into(),
+            };
 
-            let geom = geometry_line_string(&row).project_to_tile(&ctx.tile_projector);
+            let geom = geom.project_to_tile(&ctx.tile_projector);
 
             let offset_factor: f64 = row.get("offset_factor");
 
@@ -1,22 +1,24 @@
 use crate::{
-    collision::Collision,
     colors,
     ctx::Ctx,
     draw::{
         create_pango_layout::FontAndLayoutOptions,
         text::{TextOptions, draw_text},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_point},
 };
 use pangocairo::pango::Style;
 use postgres::Client;
 
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("water_area_names::render");
 
     let sql = "
         SELECT
+            osm_waterareas.osm_id,
+            osm_waterareas.name AS full_name,
             REGEXP_REPLACE(osm_waterareas.name, '[Vv]odná [Nn]ádrž\\M', 'v. n.') AS name,
             ST_PointOnSurface(osm_waterareas.geometry) AS geometry
         FROM
@@ -45,14 +47,26 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
     let rows = client.query(sql, &params.as_params())?;
 
+    if ctx.emit_rows("water_area_names", &rows, &["osm_id", "name", "full_name"]) {
+        return Ok(());
+    }
+
     for row in rows {
-        draw_text(
-            ctx.context,
-            Some(collision),
-            &geometry_point(&row).project_to_tile(&ctx.tile_projector),
-            row.get("name"),
-            &text_options,
-        )?;
+        let feature = ctx.hit_feature(
+            "water_areas",
+            row.get("osm_id"),
+            &[("name", row.get("full_name"))],
+        );
+
+        let point = geometry_point(&row).project_to_tile(&ctx.tile_projector);
+
+        let name: String = row.get("name");
+
+        labels.submit(priority::WATER_AREA, feature, move |collision| {
+            draw_text(ctx.context, Some(collision), &point, &name, &text_options)?;
+
+            Ok(())
+        });
     }
 
     Ok(())
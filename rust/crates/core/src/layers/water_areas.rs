@@ -26,6 +26,10 @@ pub fn render(ctx: &Ctx, client: &mut Client) -> LayerRenderResult {
         &ctx.bbox_query_params(None).as_params(),
     )?;
 
+    if ctx.emit_rows("water_areas", &rows, &["type", "tmp"]) {
+        return Ok(());
+    }
+
     let context = ctx.context;
 
     context.save()?;
@@ -1,5 +1,4 @@
 use crate::{
-    collision::Collision,
     colors::{self},
     ctx::Ctx,
     draw::{
@@ -7,23 +6,15 @@ use crate::{
         path_geom::walk_geometry_line_strings,
         text_on_line::{Align, Distribution, Repeat, TextOnLineOptions, draw_text_on_line},
     },
+    labels::{Labels, priority},
     layer_render_error::LayerRenderResult,
     projectable::{TileProjectable, geometry_geometry},
-    regex_replacer::{Replacement, replace},
 };
+use geo::InteriorPoint;
 use pangocairo::pango::Style;
 use postgres::Client;
-use regex::Regex;
-use std::sync::LazyLock;
 
-static REPLACEMENTS: LazyLock<Vec<Replacement>> = LazyLock::new(|| {
-    vec![
-        (Regex::new(r"\b[Pp]otok$").expect("regex"), "p."),
-        (Regex::new(r"^[Pp]otok\b *").expect("regex"), ""),
-    ]
-});
-
-pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> LayerRenderResult {
+pub fn render<'a>(ctx: &'a Ctx, client: &mut Client, labels: &mut Labels<'a>) -> LayerRenderResult {
     let _span = tracy_client::span!("water_line_names::render");
 
     let sql = format!(
@@ -35,7 +26,7 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
             WHERE name <> '' {}AND geometry && ST_Expand(ST_MakeEnvelope($1, $2, $3, $4, 3857), $5)
             GROUP BY name, type
         )
-        SELECT name, type, geometry
+        SELECT name, type, geometry, osm_id
         FROM merged ORDER BY type <> 'river', osm_id",
         if ctx.zoom < 14 {
             "AND type = 'river' "
@@ -46,7 +37,11 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
 
     let rows = client.query(&sql, &ctx.bbox_query_params(Some(2048.0)).as_params())?;
 
-    let mut options = TextOnLineOptions {
+    if ctx.emit_rows("water_line_names", &rows, &["osm_id", "name", "type"]) {
+        return Ok(());
+    }
+
+    let options = TextOnLineOptions {
         flo: FontAndLayoutOptions {
             style: Style::Italic,
             letter_spacing: 2.0,
@@ -62,26 +57,48 @@ pub fn render(ctx: &Ctx, client: &mut Client, collision: &mut Collision) -> Laye
             continue;
         };
 
+        let name = match geom.interior_point() {
+            Some(position) => ctx
+                .abbreviations
+                .replace(&position, "waterway", row.get("name")),
+            None => row.get::<_, &str>("name").into(),
+        }
+        .into_owned();
+
         let geom = geom.project_to_tile(&ctx.tile_projector);
 
         let typ: &str = row.get("type");
 
-        options.distribution = Distribution::Align {
-            align: Align::Center,
-            repeat: Repeat::Spaced(if typ == "river" { 400.0 } else { 300.0 }),
+        let feature = ctx.hit_feature(
+            "water_lines",
+            row.get("osm_id"),
+            &[("type", typ), ("name", row.get("name"))],
+        );
+
+        let river = typ == "river";
+
+        let options = TextOnLineOptions {
+            distribution: Distribution::Align {
+                align: Align::Center,
+                repeat: Repeat::Spaced(if river { 400.0 } else { 300.0 }),
+            },
+            ..options
         };
 
-        walk_geometry_line_strings(&geom, &mut |geom| {
-            let _drawn = draw_text_on_line(
-                ctx.context,
-                geom,
-                &replace(row.get("name"), &REPLACEMENTS),
-                Some(collision),
-                &options,
-            )?;
-
-            cairo::Result::Ok(())
-        })?;
+        let priority = if river {
+            priority::RIVER
+        } else {
+            priority::STREAM
+        };
+
+        labels.submit(priority, feature, move |collision| {
+            walk_geometry_line_strings(&geom, &mut |geom| {
+                let _drawn =
+                    draw_text_on_line(ctx.context, geom, &name, Some(collision), &options)?;
+
+                cairo::Result::Ok(())
+            })
+        });
     }
 
     Ok(())
@@ -30,6 +30,10 @@ pub fn render(ctx: &Ctx, client: &mut Client, svg_repo: &mut SvgRepo) -> LayerRe
 
     let rows = &client.query(sql, &ctx.bbox_query_params(Some(8.0)).as_params())?;
 
+    if ctx.emit_rows("water_lines", rows, &["type", "tmp", "tunnel"]) {
+        return Ok(());
+    }
+
     // TODO lazy
     let arrow = svg_repo.get("waterway-arrow")?;
 
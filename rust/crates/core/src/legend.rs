@@ -0,0 +1,338 @@
+use crate::abbreviations::Abbreviations;
+use crate::ctx::Ctx;
+use crate::image_format::ImageFormat;
+use crate::layer_render_error::{LayerRenderError, LayerRenderResult};
+use crate::layers::features;
+use crate::layers::landuse;
+use crate::layers::roads::{self, Road, RoadKind};
+use crate::layers::routes::{self, RouteKind, RouteTypes};
+use crate::pois::Pois;
+use crate::projectable::TileProjector;
+use crate::size::Size;
+use crate::svg_repo::SvgRepo;
+use crate::xyz::bbox_around;
+use cairo::{Context, Format, ImageSurface, Surface, SvgSurface};
+use geo::{Coord, Geometry, LineString, Polygon};
+use serde::Serialize;
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+const SIZE: Size<u32> = Size::new(64, 24);
+
+/// Limits of the swatch scale, as of the map export.
+const MIN_SCALE: f64 = 0.1;
+const MAX_SCALE: f64 = 10.0;
+
+static NO_ABBREVIATIONS: LazyLock<Abbreviations> = LazyLock::new(Abbreviations::empty);
+
+#[derive(Debug, thiserror::Error)]
+pub enum LegendError {
+    #[error("Unknown legend item \"{0}\"")]
+    UnknownItem(String),
+
+    #[error("Unsupported legend image format {0:?}")]
+    UnsupportedFormat(ImageFormat),
+
+    #[error("Error rendering legend item: {0}")]
+    Render(#[from] LayerRenderError),
+
+    #[error(transparent)]
+    Cairo(#[from] cairo::Error),
+
+    #[error("Error encoding image: {0}")]
+    Encoding(#[from] cairo::IoError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LegendCategory {
+    Poi,
+    Landuse,
+    Road,
+    Route,
+}
+
+enum Sample {
+    Poi,
+    Landuse,
+    Road {
+        kind: RoadKind,
+        tracktype: &'static str,
+    },
+    Route {
+        route_types: RouteTypes,
+        column: String,
+    },
+}
+
+#[derive(Serialize)]
+pub struct LegendItem {
+    pub id: String,
+    pub category: LegendCategory,
+    #[serde(rename = "type")]
+    pub typ: String,
+    /// Track grade of roads or colour of routes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+    pub zoom: u32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(skip)]
+    sample: Sample,
+}
+
+impl LegendItem {
+    fn new(category: LegendCategory, id: String, typ: &str, zoom: u32, sample: Sample) -> Self {
+        Self {
+            id,
+            category,
+            typ: typ.to_string(),
+            variant: None,
+            zoom,
+            width: SIZE.width,
+            height: SIZE.height,
+            sample,
+        }
+    }
+}
+
+/// Legend enumerating what the renderer draws. Every item is rendered by the
+/// drawing code of its layer.
+pub struct Legend {
+    items: Vec<LegendItem>,
+}
+
+impl Legend {
+    pub fn new(pois: &Pois) -> Self {
+        let mut items = Vec::new();
+
+        let mut seen = HashSet::new();
+
+        for poi in pois.iter() {
+            if seen.insert(poi.typ.as_str()) {
+                items.push(LegendItem::new(
+                    LegendCategory::Poi,
+                    format!("poi/{}", poi.typ),
+                    &poi.typ,
+                    poi.min_zoom,
+                    Sample::Poi,
+                ));
+            }
+        }
+
+        for landuse in landuse::LANDUSES.iter().filter(|landuse| landuse.listed) {
+            items.push(LegendItem::new(
+                LegendCategory::Landuse,
+                format!("landuse/{}", landuse.typ),
+                landuse.typ,
+                15,
+                Sample::Landuse,
+            ));
+        }
+
+        for (kind, &tracktype) in RoadKind::ALL.iter().flat_map(|&kind| {
+            kind.legend_tracktypes()
+                .iter()
+                .map(move |tracktype| (kind, tracktype))
+        }) {
+            let (class, typ) = kind.class_and_type();
+
+            let mut id = format!("road/{class}/{typ}");
+
+            if !tracktype.is_empty() {
+                id.push('/');
+                id.push_str(tracktype);
+            }
+
+            let mut item = LegendItem::new(
+                LegendCategory::Road,
+                id,
+                typ,
+                16,
+                Sample::Road { kind, tracktype },
+            );
+
+            item.variant = (!tracktype.is_empty()).then(|| tracktype.to_string());
+
+            items.push(item);
+        }
+
+        for kind in RouteKind::ALL {
+            for (color, _) in routes::COLORS.iter().rev() {
+                let mut item = LegendItem::new(
+                    LegendCategory::Route,
+                    format!("route/{}/{color}", kind.name()),
+                    kind.name(),
+                    14,
+                    Sample::Route {
+                        route_types: kind.route_types(),
+                        column: kind.column(color),
+                    },
+                );
+
+                item.variant = Some(color.to_string());
+
+                items.push(item);
+            }
+        }
+
+        Self { items }
+    }
+
+    pub fn items(&self) -> &[LegendItem] {
+        &self.items
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(&self.items).expect("serialized legend")
+    }
+
+    /// Renders the swatch of item `id` as PNG or SVG. The scale is clamped to
+    /// 0.1–10.
+    pub fn render_item(
+        &self,
+        id: &str,
+        svg_repo: &mut SvgRepo,
+        pois: &Pois,
+        format: ImageFormat,
+        scale: f64,
+    ) -> Result<Vec<u8>, LegendError> {
+        let _span = tracy_client::span!("legend::render_item");
+
+        let item = self
+            .items
+            .iter()
+            .find(|item| item.id == id)
+            .ok_or_else(|| LegendError::UnknownItem(id.to_string()))?;
+
+        let scale = if scale.is_nan() {
+            1.0
+        } else {
+            scale.clamp(MIN_SCALE, MAX_SCALE)
+        };
+
+        let width = item.width as f64 * scale;
+        let height = item.height as f64 * scale;
+
+        match format {
+            ImageFormat::Svg => {
+                let surface = SvgSurface::for_stream(width, height, Vec::new())?;
+
+                draw_item(&surface, item, svg_repo, pois, scale)?;
+
+                Ok(*surface
+                    .finish_output_stream()
+                    .expect("finished output stream")
+                    .downcast::<Vec<u8>>()
+                    .expect("vector of bytes"))
+            }
+            ImageFormat::Png => {
+                let surface = ImageSurface::create(
+                    Format::ARgb32,
+                    width.round() as i32,
+                    height.round() as i32,
+                )?;
+
+                draw_item(&surface, item, svg_repo, pois, scale)?;
+
+                let mut buffer = Vec::new();
+
+                surface.write_to_png(&mut buffer)?;
+
+                Ok(buffer)
+            }
+            _ => Err(LegendError::UnsupportedFormat(format)),
+        }
+    }
+}
+
+fn draw_item(
+    surface: &Surface,
+    item: &LegendItem,
+    svg_repo: &mut SvgRepo,
+    pois: &Pois,
+    scale: f64,
+) -> LayerRenderResult {
+    let context = &Context::new(surface)?;
+
+    context.scale(scale, scale);
+
+    let size = Size::new(item.width, item.height);
+
+    let bbox = bbox_around(Coord { x: 0.0, y: 0.0 }, item.zoom, size);
+
+    let ctx = &Ctx {
+        context,
+        bbox,
+        size,
+        zoom: item.zoom,
+        tile_projector: TileProjector::new(bbox, size),
+        abbreviations: NO_ABBREVIATIONS.for_tile(bbox),
+        hit_areas: None,
+        mvt: None,
+        debug: None,
+        label_mask: OnceCell::new(),
+    };
+
+    let width = size.width as f64;
+    let height = size.height as f64;
+
+    let line = LineString::from(vec![(4.0, height * 0.7), (width - 4.0, height * 0.3)]);
+
+    match &item.sample {
+        Sample::Poi => {
+            let Some(def) = pois.get(&item.typ, item.zoom) else {
+                return Ok(());
+            };
+
+            let icon = features::icon(svg_repo, def, &HashMap::new())?;
+
+            let (x, y, w, h) = icon.ink_extents();
+
+            context.set_source_surface(
+                icon,
+                ((width - w) / 2.0).round() - x,
+                ((height - h) / 2.0).round() - y,
+            )?;
+
+            context.paint()?;
+        }
+        Sample::Landuse => {
+            let area = Polygon::new(
+                LineString::from(vec![
+                    (4.0, height - 3.0),
+                    (8.0, 3.0),
+                    (width - 4.0, 3.0),
+                    (width - 8.0, height - 3.0),
+                ]),
+                vec![],
+            );
+
+            landuse::draw_area(ctx, svg_repo, &item.typ, &Geometry::Polygon(area))?;
+        }
+        Sample::Road { kind, tracktype } => {
+            roads::draw(ctx, svg_repo, &[Road::sample(line, *kind, tracktype)])?;
+        }
+        Sample::Route {
+            route_types,
+            column,
+        } => {
+            roads::draw(
+                ctx,
+                svg_repo,
+                &[Road::sample(line.clone(), RoadKind::Track, "grade2")],
+            )?;
+
+            routes::draw_marking(
+                ctx,
+                svg_repo,
+                route_types,
+                &Geometry::LineString(line),
+                |name| i32::from(name == column.as_str()),
+            )?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,25 +1,51 @@
+pub use abbreviations::{Abbreviations, AbbreviationsError};
+#[cfg(feature = "bench")]
+pub use collision::Collision;
+pub use contour_export::{ContourExportError, export_contours};
+pub use debug_overlay::DebugFlags;
+pub use elevation::{
+    ElevationError, ElevationProfile, ProfilePoint, elevation_profile, elevations, parse_gpx,
+};
 pub use geojson_utils::load_geometry_from_geojson;
 pub use image_format::ImageFormat;
-pub use layers::hillshading_datasets::{HillshadingDatasets, load_hillshading_datasets};
+pub use layers::contours::ContourInterval;
+pub use layers::hillshading::HillshadingOptions;
+pub use layers::hillshading_datasets::{
+    HillshadingDatasets, HillshadingManifest, HillshadingManifestError, HillshadingPool,
+    HillshadingPoolMetrics,
+};
 pub use layers::routes::RouteTypes;
+pub use legend::{Legend, LegendCategory, LegendError, LegendItem};
+pub use pois::{Pois, PoisError};
 pub use render::RenderError;
 pub use render::render;
 pub use render_request::RenderRequest;
 pub use svg_repo::SvgRepo;
+pub use terrain::{TerrainEncoding, TerrainError, TerrainFormat, render_terrain};
 pub use xyz::tile_bounds_to_epsg3857;
 
+mod abbreviations;
 mod collision;
 mod colors;
+mod contour_export;
 mod ctx;
+mod debug_overlay;
 mod draw;
+mod elevation;
 mod geojson_utils;
 mod image_format;
+mod labels;
 mod layer_render_error;
 mod layers;
+mod legend;
+mod mvt;
+mod pois;
 mod projectable;
 mod regex_replacer;
 mod render;
 mod render_request;
 mod size;
 mod svg_repo;
+mod terrain;
+mod utfgrid;
 mod xyz;
@@ -0,0 +1,700 @@
+use crate::projectable::{TileProjectable, TileProjector, geometry_geometry};
+use crate::size::Size;
+use geo::{
+    BooleanOps, BoundingRect, Coord, Geometry, LineString, MultiLineString, MultiPolygon, Polygon,
+    Rect,
+};
+use postgres::Row;
+use std::collections::HashMap;
+
+/// Size of the tile in its coordinates.
+pub const EXTENT: u32 = 4096;
+
+/// Geometries are clipped to the tile extended by this buffer (in tile coordinates).
+const BUFFER: f64 = 64.0;
+
+const VERSION: u32 = 2;
+
+const POINT: u32 = 1;
+const LINE_STRING: u32 = 2;
+const POLYGON: u32 = 3;
+
+const MOVE_TO: u32 = 1;
+const LINE_TO: u32 = 2;
+const CLOSE_PATH: u32 = 7;
+
+/// Attribute value of a feature.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Double(f64),
+    Int(i64),
+    Bool(bool),
+}
+
+impl Value {
+    /// Reads column `name` of `row`. Returns `None` for NULLs, empty strings
+    /// and columns of unsupported types.
+    pub fn from_row(row: &Row, name: &str) -> Option<Self> {
+        let idx = row
+            .columns()
+            .iter()
+            .position(|column| column.name() == name)?;
+
+        let value = match row.columns()[idx].type_().name() {
+            "text" | "varchar" | "bpchar" | "name" => {
+                row.get::<_, Option<String>>(idx).map(Self::String)
+            }
+            "bool" => row.get::<_, Option<bool>>(idx).map(Self::Bool),
+            "int2" => row.get::<_, Option<i16>>(idx).map(|v| Self::Int(v.into())),
+            "int4" => row.get::<_, Option<i32>>(idx).map(|v| Self::Int(v.into())),
+            "int8" => row.get::<_, Option<i64>>(idx).map(Self::Int),
+            "float4" => row
+                .get::<_, Option<f32>>(idx)
+                .map(|v| Self::Double(v.into())),
+            "float8" => row.get::<_, Option<f64>>(idx).map(Self::Double),
+            _ => None,
+        };
+
+        value.filter(|value| !matches!(value, Self::String(s) if s.is_empty()))
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        match self {
+            Self::String(value) => write_bytes(&mut buf, 1, value.as_bytes()),
+            Self::Double(value) => {
+                write_key(&mut buf, 3, 1);
+                buf.extend_from_slice(&value.to_le_bytes());
+            }
+            Self::Int(value) if *value >= 0 => write_varint_field(&mut buf, 5, *value as u64),
+            Self::Int(value) => write_varint_field(&mut buf, 6, zigzag(*value)),
+            Self::Bool(value) => write_varint_field(&mut buf, 7, u64::from(*value)),
+        }
+
+        buf
+    }
+}
+
+struct Layer {
+    name: String,
+    features: Vec<u8>,
+    keys: Vec<String>,
+    key_index: HashMap<String, u32>,
+    values: Vec<Vec<u8>>,
+    value_index: HashMap<Vec<u8>, u32>,
+}
+
+impl Layer {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            features: Vec::new(),
+            keys: Vec::new(),
+            key_index: HashMap::new(),
+            values: Vec::new(),
+            value_index: HashMap::new(),
+        }
+    }
+
+    fn tags(&mut self, properties: &[(&str, Value)]) -> Vec<u32> {
+        let mut tags = Vec::with_capacity(properties.len() * 2);
+
+        for (key, value) in properties {
+            let key = match self.key_index.get(*key) {
+                Some(&idx) => idx,
+                None => {
+                    let idx = self.keys.len() as u32;
+
+                    self.keys.push(key.to_string());
+                    self.key_index.insert(key.to_string(), idx);
+
+                    idx
+                }
+            };
+
+            let value = value.encode();
+
+            let value = match self.value_index.get(&value) {
+                Some(&idx) => idx,
+                None => {
+                    let idx = self.values.len() as u32;
+
+                    self.values.push(value.clone());
+                    self.value_index.insert(value, idx);
+
+                    idx
+                }
+            };
+
+            tags.push(key);
+            tags.push(value);
+        }
+
+        tags
+    }
+
+    fn add_feature(&mut self, typ: u32, tags: &[u32], geometry: &[u32]) {
+        let mut feature = Vec::new();
+
+        write_packed(&mut feature, 2, tags);
+        write_varint_field(&mut feature, 3, typ.into());
+        write_packed(&mut feature, 4, geometry);
+
+        write_bytes(&mut self.features, 2, &feature);
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+
+        write_varint_field(&mut buf, 15, VERSION.into());
+        write_bytes(&mut buf, 1, self.name.as_bytes());
+        buf.extend_from_slice(&self.features);
+
+        for key in &self.keys {
+            write_bytes(&mut buf, 3, key.as_bytes());
+        }
+
+        for value in &self.values {
+            write_bytes(&mut buf, 4, value);
+        }
+
+        write_varint_field(&mut buf, 5, EXTENT.into());
+
+        buf
+    }
+}
+
+/// [Mapbox Vector Tile](https://github.com/mapbox/vector-tile-spec) built from
+/// layer query rows.
+pub struct MvtTile {
+    tile_projector: TileProjector,
+    clip_rect: Rect,
+    layers: Vec<Layer>,
+}
+
+impl MvtTile {
+    pub fn new(bbox: Rect) -> Self {
+        Self {
+            tile_projector: TileProjector::new(bbox, Size::new(EXTENT, EXTENT)),
+            clip_rect: Rect::new(
+                (-BUFFER, -BUFFER),
+                (EXTENT as f64 + BUFFER, EXTENT as f64 + BUFFER),
+            ),
+            layers: Vec::new(),
+        }
+    }
+
+    /// Adds rows of a layer query as features of `layer`. Geometry is read from
+    /// the `geometry` column and properties from the `attributes` columns.
+    pub fn add_rows(&mut self, layer: &str, rows: &[Row], attributes: &[&str]) {
+        let _span = tracy_client::span!("mvt::add_rows");
+
+        for row in rows {
+            let Some(geom) = geometry_geometry(row) else {
+                continue;
+            };
+
+            let properties: Vec<_> = attributes
+                .iter()
+                .filter_map(|&name| Some((name, Value::from_row(row, name)?)))
+                .collect();
+
+            self.add_feature(layer, &geom, &properties);
+        }
+    }
+
+    /// Adds a feature of `geom` in EPSG:3857. Geometry collections and
+    /// geometries mixing points, lines and polygons produce a feature per
+    /// kind.
+    pub fn add_feature(&mut self, layer: &str, geom: &Geometry, properties: &[(&str, Value)]) {
+        let geom = geom.project_to_tile(&self.tile_projector);
+
+        let mut parts = Parts::default();
+
+        parts.collect(geom);
+
+        let encoded = [
+            (POINT, self.encode_points(&parts.points)),
+            (LINE_STRING, self.encode_lines(parts.lines)),
+            (POLYGON, self.encode_polygons(parts.polygons)),
+        ];
+
+        if encoded.iter().all(|(_, geometry)| geometry.is_empty()) {
+            return;
+        }
+
+        let layer = self.layer(layer);
+
+        let tags = layer.tags(properties);
+
+        for (typ, geometry) in encoded {
+            if !geometry.is_empty() {
+                layer.add_feature(typ, &tags, &geometry);
+            }
+        }
+    }
+
+    /// Encodes the tile as protobuf. Layers are in the order of their first
+    /// feature.
+    pub fn encode(&self) -> Vec<u8> {
+        let _span = tracy_client::span!("mvt::encode");
+
+        let mut buf = Vec::new();
+
+        for layer in &self.layers {
+            write_bytes(&mut buf, 3, &layer.encode());
+        }
+
+        buf
+    }
+
+    fn layer(&mut self, name: &str) -> &mut Layer {
+        let idx = match self.layers.iter().position(|layer| layer.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.layers.push(Layer::new(name));
+
+                self.layers.len() - 1
+            }
+        };
+
+        &mut self.layers[idx]
+    }
+
+    fn contains(&self, rect: Option<Rect>) -> bool {
+        rect.is_some_and(|rect| {
+            rect.min().x >= self.clip_rect.min().x
+                && rect.min().y >= self.clip_rect.min().y
+                && rect.max().x <= self.clip_rect.max().x
+                && rect.max().y <= self.clip_rect.max().y
+        })
+    }
+
+    fn encode_points(&self, points: &[Coord]) -> Vec<u32> {
+        let min = self.clip_rect.min();
+        let max = self.clip_rect.max();
+
+        let points: Vec<_> = points
+            .iter()
+            .filter(|c| c.x >= min.x && c.x <= max.x && c.y >= min.y && c.y <= max.y)
+            .map(quantize)
+            .collect();
+
+        let mut encoder = GeometryEncoder::default();
+
+        if !points.is_empty() {
+            encoder.command(MOVE_TO, &points);
+        }
+
+        encoder.commands
+    }
+
+    fn encode_lines(&self, lines: Vec<LineString>) -> Vec<u32> {
+        let lines = MultiLineString::new(lines);
+
+        let lines = if self.contains(lines.bounding_rect()) {
+            lines
+        } else {
+            self.clip_rect.to_polygon().clip(&lines, false)
+        };
+
+        let mut encoder = GeometryEncoder::default();
+
+        for line in &lines {
+            let points = quantize_line(line);
+
+            if points.len() < 2 {
+                continue;
+            }
+
+            encoder.command(MOVE_TO, &points[..1]);
+            encoder.command(LINE_TO, &points[1..]);
+        }
+
+        encoder.commands
+    }
+
+    fn encode_polygons(&self, polygons: Vec<Polygon>) -> Vec<u32> {
+        let polygons = MultiPolygon::new(polygons);
+
+        let polygons = if self.contains(polygons.bounding_rect()) {
+            polygons
+        } else {
+            polygons.intersection(&self.clip_rect.to_polygon())
+        };
+
+        let mut encoder = GeometryEncoder::default();
+
+        for polygon in &polygons {
+            let Some(exterior) = quantize_ring(polygon.exterior(), true) else {
+                continue;
+            };
+
+            encoder.ring(&exterior);
+
+            for interior in polygon.interiors() {
+                if let Some(interior) = quantize_ring(interior, false) {
+                    encoder.ring(&interior);
+                }
+            }
+        }
+
+        encoder.commands
+    }
+}
+
+#[derive(Default)]
+struct Parts {
+    points: Vec<Coord>,
+    lines: Vec<LineString>,
+    polygons: Vec<Polygon>,
+}
+
+impl Parts {
+    fn collect(&mut self, geom: Geometry) {
+        match geom {
+            Geometry::Point(point) => self.points.push(point.0),
+            Geometry::MultiPoint(mp) => self.points.extend(mp.into_iter().map(|point| point.0)),
+            Geometry::Line(line) => self.lines.push(LineString::new(vec![line.start, line.end])),
+            Geometry::LineString(ls) => self.lines.push(ls),
+            Geometry::MultiLineString(mls) => self.lines.extend(mls),
+            Geometry::Polygon(polygon) => self.polygons.push(polygon),
+            Geometry::MultiPolygon(mp) => self.polygons.extend(mp),
+            Geometry::Rect(rect) => self.polygons.push(rect.to_polygon()),
+            Geometry::Triangle(triangle) => self.polygons.push(triangle.to_polygon()),
+            Geometry::GeometryCollection(gc) => {
+                for geom in gc {
+                    self.collect(geom);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct GeometryEncoder {
+    commands: Vec<u32>,
+    cursor: (i32, i32),
+}
+
+impl GeometryEncoder {
+    fn command(&mut self, id: u32, points: &[(i32, i32)]) {
+        self.commands.push(id | ((points.len() as u32) << 3));
+
+        for &(x, y) in points {
+            self.commands
+                .push(zigzag((x - self.cursor.0).into()) as u32);
+            self.commands
+                .push(zigzag((y - self.cursor.1).into()) as u32);
+
+            self.cursor = (x, y);
+        }
+    }
+
+    fn ring(&mut self, points: &[(i32, i32)]) {
+        self.command(MOVE_TO, &points[..1]);
+        self.command(LINE_TO, &points[1..]);
+        self.commands.push(CLOSE_PATH | (1 << 3));
+    }
+}
+
+fn quantize(coord: &Coord) -> (i32, i32) {
+    (coord.x.round() as i32, coord.y.round() as i32)
+}
+
+fn quantize_line(line: &LineString) -> Vec<(i32, i32)> {
+    let mut points: Vec<_> = line.coords().map(quantize).collect();
+
+    points.dedup();
+
+    points
+}
+
+/// Quantizes a ring, dropping the closing point, and orients it to positive
+/// area (by the surveyor's formula in tile coordinates) for exterior rings or
+/// negative area for interior ones. Returns `None` for degenerate rings.
+fn quantize_ring(ring: &LineString, exterior: bool) -> Option<Vec<(i32, i32)>> {
+    let mut points = quantize_line(ring);
+
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+
+    if points.len() < 3 {
+        return None;
+    }
+
+    let area: i64 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| i64::from(a.0) * i64::from(b.1) - i64::from(b.0) * i64::from(a.1))
+        .sum();
+
+    if area == 0 {
+        return None;
+    }
+
+    if (area > 0) != exterior {
+        points.reverse();
+    }
+
+    Some(points)
+}
+
+const fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+
+    buf.push(value as u8);
+}
+
+fn write_key(buf: &mut Vec<u8>, field: u32, wire_type: u32) {
+    write_varint(buf, ((field << 3) | wire_type).into());
+}
+
+fn write_varint_field(buf: &mut Vec<u8>, field: u32, value: u64) {
+    write_key(buf, field, 0);
+    write_varint(buf, value);
+}
+
+fn write_bytes(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_key(buf, field, 2);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn write_packed(buf: &mut Vec<u8>, field: u32, values: &[u32]) {
+    if values.is_empty() {
+        return;
+    }
+
+    let mut packed = Vec::with_capacity(values.len());
+
+    for &value in values {
+        write_varint(&mut packed, value.into());
+    }
+
+    write_bytes(buf, field, &packed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_varint(buf: &[u8], pos: &mut usize) -> u64 {
+        let mut value = 0;
+
+        for shift in (0..64).step_by(7) {
+            let byte = buf[*pos];
+
+            *pos += 1;
+
+            value |= u64::from(byte & 0x7f) << shift;
+
+            if byte < 0x80 {
+                break;
+            }
+        }
+
+        value
+    }
+
+    const fn unzigzag(value: u64) -> i64 {
+        (value >> 1) as i64 ^ -((value & 1) as i64)
+    }
+
+    /// Absolute points of the `MoveTo` and `LineTo` commands, with the
+    /// `ClosePath` count.
+    fn decode_commands(commands: &[u32]) -> (Vec<(i32, i32)>, usize) {
+        let mut points = Vec::new();
+        let mut closed = 0;
+        let mut cursor = (0, 0);
+        let mut iter = commands.iter();
+
+        while let Some(&command) = iter.next() {
+            let (id, count) = (command & 7, command >> 3);
+
+            if id == CLOSE_PATH {
+                closed += count as usize;
+
+                continue;
+            }
+
+            for _ in 0..count {
+                let dx = unzigzag((*iter.next().unwrap()).into()) as i32;
+                let dy = unzigzag((*iter.next().unwrap()).into()) as i32;
+
+                cursor = (cursor.0 + dx, cursor.1 + dy);
+
+                points.push(cursor);
+            }
+        }
+
+        (points, closed)
+    }
+
+    #[test]
+    fn varint_round_trip() {
+        let mut buf = Vec::new();
+
+        write_varint(&mut buf, 300);
+
+        assert_eq!(buf, [0xac, 0x02]);
+
+        let values = [
+            0,
+            1,
+            127,
+            128,
+            300,
+            16_383,
+            16_384,
+            u32::MAX.into(),
+            u64::MAX,
+        ];
+
+        let mut buf = Vec::new();
+
+        for value in values {
+            write_varint(&mut buf, value);
+        }
+
+        let mut pos = 0;
+
+        for value in values {
+            assert_eq!(read_varint(&buf, &mut pos), value);
+        }
+
+        assert_eq!(pos, buf.len());
+    }
+
+    #[test]
+    fn zigzag_round_trip() {
+        for (value, encoded) in [(0, 0), (-1, 1), (1, 2), (-2, 3), (2, 4)] {
+            assert_eq!(zigzag(value), encoded);
+        }
+
+        for value in [
+            i64::from(i32::MIN),
+            -4096,
+            -1,
+            0,
+            1,
+            4096,
+            i64::from(i32::MAX),
+        ] {
+            assert_eq!(unzigzag(zigzag(value)), value);
+        }
+    }
+
+    #[test]
+    fn line_commands() {
+        // example of the vector tile specification
+        let mut encoder = GeometryEncoder::default();
+
+        encoder.command(MOVE_TO, &[(2, 2)]);
+        encoder.command(LINE_TO, &[(2, 10), (10, 10)]);
+
+        assert_eq!(encoder.commands, [9, 4, 4, 18, 0, 16, 16, 0]);
+    }
+
+    #[test]
+    fn ring_commands() {
+        // example of the vector tile specification
+        let mut encoder = GeometryEncoder::default();
+
+        encoder.ring(&[(3, 6), (8, 12), (20, 34)]);
+
+        assert_eq!(encoder.commands, [9, 6, 12, 18, 10, 12, 24, 44, 15]);
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let lines = [
+            vec![(0, 0), (4096, 4096), (-64, 4160)],
+            vec![(100, -50), (99, -51), (2000, 3000)],
+        ];
+
+        let mut encoder = GeometryEncoder::default();
+
+        for line in &lines {
+            encoder.command(MOVE_TO, &line[..1]);
+            encoder.command(LINE_TO, &line[1..]);
+        }
+
+        encoder.ring(&[(10, 10), (20, 10), (20, 20)]);
+
+        let (points, closed) = decode_commands(&encoder.commands);
+
+        let expected: Vec<_> = lines
+            .concat()
+            .into_iter()
+            .chain([(10, 10), (20, 10), (20, 20)])
+            .collect();
+
+        assert_eq!(points, expected);
+        assert_eq!(closed, 1);
+    }
+
+    #[test]
+    fn packed_round_trip() {
+        let values = [9, 4, 4, 18, 0, 16, 16, 0, 300, u32::MAX];
+
+        let mut buf = Vec::new();
+
+        write_packed(&mut buf, 4, &values);
+
+        let mut pos = 0;
+
+        assert_eq!(read_varint(&buf, &mut pos), (4 << 3) | 2);
+
+        let len = read_varint(&buf, &mut pos) as usize;
+
+        assert_eq!(pos + len, buf.len());
+
+        let decoded: Vec<_> =
+            std::iter::from_fn(|| (pos < buf.len()).then(|| read_varint(&buf, &mut pos) as u32))
+                .collect();
+
+        assert_eq!(decoded, values);
+    }
+
+    #[test]
+    fn ring_orientation() {
+        let square = LineString::from(vec![
+            (0.0, 0.0),
+            (0.0, 10.0),
+            (10.0, 10.0),
+            (10.0, 0.0),
+            (0.0, 0.0),
+        ]);
+
+        let exterior = quantize_ring(&square, true).unwrap();
+        let interior = quantize_ring(&square, false).unwrap();
+
+        assert_eq!(exterior.len(), 4);
+        assert_eq!(
+            interior.iter().rev().collect::<Vec<_>>(),
+            exterior.iter().collect::<Vec<_>>()
+        );
+
+        let area = |points: &[(i32, i32)]| -> i64 {
+            points
+                .iter()
+                .zip(points.iter().cycle().skip(1))
+                .map(|(a, b)| i64::from(a.0) * i64::from(b.1) - i64::from(b.0) * i64::from(a.1))
+                .sum()
+        };
+
+        assert!(area(&exterior) > 0);
+        assert!(area(&interior) < 0);
+    }
+}
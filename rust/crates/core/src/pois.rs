@@ -0,0 +1,235 @@
+use crate::colors::{self, Color, parse_hex_rgb};
+use crate::svg_repo::{SvgRepo, SvgRepoError};
+use pangocairo::pango::Weight;
+use serde::{Deserialize, Deserializer, de};
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+const BUILTIN: &str = include_str!("../styles/pois.yaml");
+
+const VERSION: u32 = 1;
+
+/// SVGs composed into spring icons by `features::render`.
+const SPRING_SVGS: [&str; 5] = [
+    "spring",
+    "mineral-spring",
+    "refitted_spring",
+    "drinkable_spring",
+    "intermittent",
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum PoisError {
+    #[error("Error reading {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[error("Error parsing POI style: {0}")]
+    Parse(#[from] serde_yaml::Error),
+
+    #[error("Unsupported POI style version {0} (expected {VERSION})")]
+    UnsupportedVersion(u32),
+
+    #[error("POI #{index} (\"{typ}\"): max_zoom {max_zoom} is lower than min_zoom {min_zoom}")]
+    ZoomRange {
+        index: usize,
+        typ: String,
+        min_zoom: u32,
+        max_zoom: u32,
+    },
+
+    #[error("POI \"{typ}\": {source}")]
+    Svg { typ: String, source: SvgRepoError },
+}
+
+#[derive(Deserialize)]
+struct PoisDef {
+    version: u32,
+    pois: Vec<Poi>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Poi {
+    #[serde(rename = "type")]
+    pub typ: String,
+    pub min_zoom: u32,
+    #[serde(default = "never")]
+    pub min_text_zoom: u32,
+    #[serde(default = "never")]
+    pub max_zoom: u32,
+    #[serde(default)]
+    pub with_ele: bool,
+    #[serde(default)]
+    pub natural: bool,
+    pub icon: Option<String>,
+    pub stylesheet: Option<String>,
+    #[serde(default = "yes")]
+    pub halo: bool,
+    #[serde(default = "default_font_size")]
+    pub font_size: f64,
+    #[serde(default = "default_weight", deserialize_with = "deserialize_weight")]
+    pub weight: Weight,
+    #[serde(default = "default_text_color", deserialize_with = "deserialize_color")]
+    pub text_color: Color,
+}
+
+const fn never() -> u32 {
+    u32::MAX
+}
+
+const fn yes() -> bool {
+    true
+}
+
+const fn default_font_size() -> f64 {
+    12.0
+}
+
+const fn default_weight() -> Weight {
+    Weight::Normal
+}
+
+const fn default_text_color() -> Color {
+    colors::BLACK
+}
+
+fn deserialize_weight<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Weight, D::Error> {
+    let weight = String::deserialize(deserializer)?;
+
+    Ok(match weight.as_str() {
+        "thin" => Weight::Thin,
+        "ultralight" => Weight::Ultralight,
+        "light" => Weight::Light,
+        "semilight" => Weight::Semilight,
+        "book" => Weight::Book,
+        "normal" => Weight::Normal,
+        "medium" => Weight::Medium,
+        "semibold" => Weight::Semibold,
+        "bold" => Weight::Bold,
+        "ultrabold" => Weight::Ultrabold,
+        "heavy" => Weight::Heavy,
+        "ultraheavy" => Weight::Ultraheavy,
+        _ => {
+            return Err(de::Error::invalid_value(
+                de::Unexpected::Str(&weight),
+                &"a font weight (normal, bold, …)",
+            ));
+        }
+    })
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+    let color = String::deserialize(deserializer)?;
+
+    let (r, g, b) = parse_hex_rgb(&color).ok_or_else(|| {
+        de::Error::invalid_value(de::Unexpected::Str(&color), &"a #rrggbb colour")
+    })?;
+
+    Ok((
+        (r * 255.0).round() as i64,
+        (g * 255.0).round() as i64,
+        (b * 255.0).round() as i64,
+    ))
+}
+
+impl Poi {
+    /// SVG name of the icon.
+    pub fn icon(&self) -> &str {
+        self.icon.as_deref().unwrap_or(&self.typ)
+    }
+}
+
+/// POI style definitions keyed by POI type.
+pub struct Pois {
+    pois: Vec<Poi>,
+    by_type: HashMap<String, Vec<usize>>,
+}
+
+impl Default for Pois {
+    fn default() -> Self {
+        Self::parse(BUILTIN).expect("built-in POI style")
+    }
+}
+
+impl Pois {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, PoisError> {
+        let path = path.as_ref();
+
+        let input = read_to_string(path).map_err(|source| PoisError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        Self::parse(&input)
+    }
+
+    fn parse(input: &str) -> Result<Self, PoisError> {
+        let def: PoisDef = serde_yaml::from_str(input)?;
+
+        if def.version != VERSION {
+            return Err(PoisError::UnsupportedVersion(def.version));
+        }
+
+        let mut by_type = HashMap::<String, Vec<usize>>::new();
+
+        for (index, poi) in def.pois.iter().enumerate() {
+            if poi.max_zoom < poi.min_zoom {
+                return Err(PoisError::ZoomRange {
+                    index,
+                    typ: poi.typ.clone(),
+                    min_zoom: poi.min_zoom,
+                    max_zoom: poi.max_zoom,
+                });
+            }
+
+            by_type.entry(poi.typ.clone()).or_default().push(index);
+        }
+
+        Ok(Self {
+            pois: def.pois,
+            by_type,
+        })
+    }
+
+    /// Checks that every icon referenced by the style is available in `svg_repo`.
+    pub fn validate(&self, svg_repo: &SvgRepo) -> Result<(), PoisError> {
+        for poi in self.iter() {
+            let icon = poi.icon();
+
+            let names = if icon == "spring" {
+                &SPRING_SVGS[..]
+            } else {
+                &[icon][..]
+            };
+
+            for name in names {
+                svg_repo.check(name).map_err(|source| PoisError::Svg {
+                    typ: poi.typ.clone(),
+                    source,
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the POI definition for `typ` at `zoom`.
+    pub fn get(&self, typ: &str, zoom: u32) -> Option<&Poi> {
+        self.by_type
+            .get(typ)?
+            .iter()
+            .map(|&index| &self.pois[index])
+            .find(|poi| poi.min_zoom <= zoom && poi.max_zoom >= zoom)
+    }
+
+    /// Iterates POI definitions in the order of the style file.
+    pub fn iter(&self) -> impl Iterator<Item = &Poi> {
+        self.pois.iter()
+    }
+}
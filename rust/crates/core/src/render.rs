@@ -1,8 +1,12 @@
+use crate::abbreviations::Abbreviations;
 use crate::image_format::ImageFormat;
 use crate::layers;
 use crate::layers::hillshading_datasets::HillshadingDatasets;
+use crate::mvt::MvtTile;
+use crate::pois::Pois;
 use crate::render_request::RenderRequest;
 use crate::svg_repo::SvgRepo;
+use crate::utfgrid::UtfGrid;
 use crate::xyz::bbox_size_in_pixels;
 use cairo::{
     Content, Context, Format, ImageSurface, PdfSurface, RecordingSurface, Rectangle, Surface,
@@ -11,6 +15,7 @@ use cairo::{
 use geo::Geometry;
 use image::codecs::jpeg::JpegEncoder;
 use image::{ExtendedColorType, ImageEncoder};
+use std::cell::RefCell;
 
 #[derive(Debug, thiserror::Error)]
 pub enum RenderError {
@@ -31,6 +36,8 @@ pub fn render(
     request: &RenderRequest,
     client: &mut postgres::Client,
     svg_repo: &mut SvgRepo,
+    abbreviations: &Abbreviations,
+    pois: &Pois,
     hillshading_datasets: &mut Option<HillshadingDatasets>,
     mask_geometry: Option<&Geometry>,
 ) -> Result<Vec<Vec<u8>>, RenderError> {
@@ -44,7 +51,11 @@ pub fn render(
 
     let scales = request.scales.clone();
 
-    let mut render = |surface: &Surface, hillshade_scale: f64, render_scale: f64| {
+    let mut render = |surface: &Surface,
+                      hillshade_scale: f64,
+                      render_scale: f64,
+                      hit_areas: Option<&RefCell<UtfGrid>>,
+                      mvt: Option<&RefCell<MvtTile>>| {
         layers::render(
             surface,
             request,
@@ -52,10 +63,14 @@ pub fn render(
             request.bbox,
             size,
             svg_repo,
+            abbreviations,
+            pois,
             hillshading_datasets,
             hillshade_scale,
             mask_geometry,
             render_scale,
+            hit_areas,
+            mvt,
         )
     };
 
@@ -69,7 +84,7 @@ pub fn render(
                 Vec::new(),
             )?;
 
-            render(&surface, primary_scale.max(1.0), primary_scale)?;
+            render(&surface, primary_scale.max(1.0), primary_scale, None, None)?;
 
             Ok(vec![
                 *surface
@@ -88,7 +103,7 @@ pub fn render(
                 Vec::new(),
             )?;
 
-            render(&surface, primary_scale.max(1.0), primary_scale)?;
+            render(&surface, primary_scale.max(1.0), primary_scale, None, None)?;
 
             Ok(vec![
                 *surface
@@ -114,7 +129,7 @@ pub fn render(
                 )),
             )?;
 
-            render(&recording_surface, max_scale.max(1.0), 1.0)?;
+            render(&recording_surface, max_scale.max(1.0), 1.0, None, None)?;
 
             let mut images = Vec::with_capacity(scales.len());
 
@@ -158,7 +173,7 @@ pub fn render(
                 )),
             )?;
 
-            render(&recording_surface, max_scale.max(1.0), 1.0)?;
+            render(&recording_surface, max_scale.max(1.0), 1.0, None, None)?;
 
             let mut images = Vec::with_capacity(scales.len());
 
@@ -205,6 +220,42 @@ pub fn render(
 
             Ok(images)
         }
+        ImageFormat::UtfGrid => {
+            let hit_areas = RefCell::new(UtfGrid::new(size)?);
+
+            // the tile itself is rendered only to collect hit areas
+            let recording_surface = RecordingSurface::create(
+                Content::ColorAlpha,
+                Some(Rectangle::new(
+                    0.0,
+                    0.0,
+                    size.width as f64,
+                    size.height as f64,
+                )),
+            )?;
+
+            render(&recording_surface, 1.0, 1.0, Some(&hit_areas), None)?;
+
+            Ok(vec![hit_areas.into_inner().into_json().into_bytes()])
+        }
+        ImageFormat::Mvt => {
+            let tile = RefCell::new(MvtTile::new(request.bbox));
+
+            // layers add their rows to the tile instead of drawing them
+            let recording_surface = RecordingSurface::create(
+                Content::ColorAlpha,
+                Some(Rectangle::new(
+                    0.0,
+                    0.0,
+                    size.width as f64,
+                    size.height as f64,
+                )),
+            )?;
+
+            render(&recording_surface, 1.0, 1.0, None, Some(&tile))?;
+
+            Ok(vec![tile.into_inner().encode()])
+        }
     }
 }
 
@@ -1,4 +1,8 @@
-use crate::{image_format::ImageFormat, layers::routes::RouteTypes};
+use crate::{
+    debug_overlay::DebugFlags,
+    image_format::ImageFormat,
+    layers::{contours::ContourInterval, hillshading::HillshadingOptions, routes::RouteTypes},
+};
 use geo::Rect;
 use geojson::Feature;
 
@@ -10,8 +14,18 @@ pub struct RenderRequest {
     pub format: ImageFormat,
     pub shading: bool,
     pub contours: bool,
+    /// Interval of contours traced from elevation datasets, by zoom if `None`.
+    pub contour_interval: Option<ContourInterval>,
+    /// Colours elevation datasets by the hypsometric tint of the manifest.
+    pub hypsometric_tint: bool,
+    /// Slope classes of avalanche terrain computed from elevation datasets.
+    pub slope: bool,
+    /// Renders just the overlays (slope) on a transparent background.
+    pub overlay_only: bool,
+    pub hillshading: HillshadingOptions,
     pub route_types: RouteTypes,
     pub featues: Option<Vec<Feature>>,
+    pub debug: DebugFlags,
 }
 
 impl RenderRequest {
@@ -23,8 +37,14 @@ impl RenderRequest {
             format,
             shading: true,
             contours: true,
+            contour_interval: None,
+            hypsometric_tint: false,
+            slope: false,
+            overlay_only: false,
+            hillshading: HillshadingOptions::default(),
             route_types: RouteTypes::all(),
             featues: None,
+            debug: DebugFlags::empty(),
         }
     }
 }
@@ -1,7 +1,11 @@
 use cairo::{Content, RecordingSurface, Rectangle};
 use gio::glib::{self};
 use rsvg::LoadingError;
-use std::{collections::HashMap, fs::read_to_string, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
 use xmltree::{Element, EmitterConfig, XMLNode};
 
 pub struct SvgRepo {
@@ -57,6 +61,11 @@ impl SvgRepo {
         self.svg_map.clear();
     }
 
+    /// Checks that the SVG `name` exists and has an `<svg>` root element.
+    pub fn check(&self, name: &str) -> Result<(), SvgRepoError> {
+        load_element(&self.base, name).map(|_| ())
+    }
+
     pub fn get(&mut self, key: &str) -> Result<&RecordingSurface, SvgRepoError> {
         self.get_extra::<fn() -> Options>(key, None)
     }
@@ -82,25 +91,7 @@ impl SvgRepo {
             let mut main_svg: Option<Element> = None;
 
             for ref name in options.names {
-                let full_path = self.base.join(format!("{name}.svg"));
-
-                let input = read_to_string(full_path).map_err(|err| SvgRepoError {
-                    msg: format!("Error loading SVG ({name})"),
-                    source: Some(err.into()),
-                })?;
-
-                let mut svg_element =
-                    Element::parse(input.as_bytes()).map_err(|err| SvgRepoError {
-                        msg: format!("XML parsing error ({name})"),
-                        source: Some(err.into()),
-                    })?;
-
-                if svg_element.name.split(':').last() != Some("svg") {
-                    return Err(SvgRepoError {
-                        msg: "Expected single <svg> root element".into(),
-                        source: None,
-                    });
-                }
+                let mut svg_element = load_element(&self.base, name)?;
 
                 if let Some(target) = &mut main_svg {
                     target.children.append(&mut svg_element.children);
@@ -235,3 +226,26 @@ impl SvgRepo {
         Ok(svg_map.get(key).expect("svg from map"))
     }
 }
+
+fn load_element(base: &Path, name: &str) -> Result<Element, SvgRepoError> {
+    let full_path = base.join(format!("{name}.svg"));
+
+    let input = read_to_string(full_path).map_err(|err| SvgRepoError {
+        msg: format!("Error loading SVG ({name})"),
+        source: Some(err.into()),
+    })?;
+
+    let svg_element = Element::parse(input.as_bytes()).map_err(|err| SvgRepoError {
+        msg: format!("XML parsing error ({name})"),
+        source: Some(err.into()),
+    })?;
+
+    if svg_element.name.split(':').last() != Some("svg") {
+        return Err(SvgRepoError {
+            msg: format!("Expected single <svg> root element ({name})"),
+            source: None,
+        });
+    }
+
+    Ok(svg_element)
+}
@@ -0,0 +1,253 @@
+use crate::layer_render_error::LayerRenderError;
+use crate::layers::dem::{Elevation, read_elevation_grid};
+use crate::layers::hillshading_datasets::{DatasetKind, HillshadingDatasets};
+use geo::Rect;
+use image::codecs::png::PngEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ExtendedColorType, ImageEncoder};
+use napi_derive::napi;
+use std::collections::HashMap;
+
+/// Passes spreading elevation into missing samples next to data, so that
+/// terrain doesn't drop to the sea level at dataset borders.
+const FILL_PASSES: usize = 8;
+
+/// Elevation where no dataset has data.
+const NO_DATA_ELEVATION: f32 = 0.0;
+
+/// Encoding of elevation to the RGB channels of a terrain tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum TerrainEncoding {
+    /// Mapbox Terrain-RGB, 0.1 m steps from -10000 m.
+    TerrainRgb,
+    /// Terrarium, 1/256 m steps from -32768 m.
+    Terrarium,
+}
+
+impl TerrainEncoding {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input {
+            "terrain-rgb" | "mapbox" => Some(Self::TerrainRgb),
+            "terrarium" => Some(Self::Terrarium),
+            _ => None,
+        }
+    }
+
+    fn encode(self, elevation: f32) -> [u8; 3] {
+        match self {
+            Self::TerrainRgb => {
+                let value = ((elevation as f64 + 10000.0) * 10.0)
+                    .round()
+                    .clamp(0.0, 16_777_215.0) as u32;
+
+                [(value >> 16) as u8, (value >> 8) as u8, value as u8]
+            }
+            Self::Terrarium => {
+                let value = (elevation as f64 + 32768.0).clamp(0.0, 65535.996);
+
+                [
+                    (value / 256.0) as u8,
+                    (value as u32 % 256) as u8,
+                    (value.fract() * 256.0) as u8,
+                ]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[napi(string_enum)]
+pub enum TerrainFormat {
+    Png,
+    /// Lossless WebP; lossy compression would corrupt the elevation.
+    Webp,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TerrainError {
+    #[error("Error reading elevation: {0}")]
+    Read(#[from] LayerRenderError),
+
+    #[error("Error encoding image: {0}")]
+    Encoding(#[from] image::ImageError),
+}
+
+/// Renders a `size` × `size` terrain tile of `bbox` from the elevation
+/// datasets, composited like the shading: datasets of higher priority cover
+/// the lower ones and datasets replace those they are better than where both
+/// have data.
+pub fn render_terrain(
+    datasets: &mut HillshadingDatasets,
+    bbox: Rect,
+    size: u32,
+    encoding: TerrainEncoding,
+    format: TerrainFormat,
+) -> Result<Vec<u8>, TerrainError> {
+    let _span = tracy_client::span!("render_terrain");
+
+    let side = size as usize;
+
+    let layers: Vec<_> = datasets
+        .fallback(bbox)
+        .into_iter()
+        .chain(datasets.covering(bbox))
+        .filter(|dataset| dataset.kind == DatasetKind::Elevation)
+        .collect();
+
+    let mut grids: HashMap<&str, Elevation> = HashMap::new();
+
+    for dataset in &layers {
+        let Some(gdal_dataset) = datasets.get(&dataset.name) else {
+            continue;
+        };
+
+        if let Some(elevation) = read_elevation_grid(gdal_dataset, bbox, side, side, 0)? {
+            grids.insert(&dataset.name, elevation);
+        }
+    }
+
+    let mut data = vec![f32::NAN; side * side];
+
+    for dataset in &layers {
+        let Some(grid) = grids.get(dataset.name.as_str()) else {
+            continue;
+        };
+
+        let replacers: Vec<_> = dataset
+            .replaced_by
+            .iter()
+            .filter_map(|name| grids.get(name.as_str()))
+            .collect();
+
+        for (index, value) in data.iter_mut().enumerate() {
+            let elevation = grid.data[index];
+
+            if !elevation.is_nan()
+                && replacers
+                    .iter()
+                    .all(|replacer| replacer.data[index].is_nan())
+            {
+                *value = elevation;
+            }
+        }
+    }
+
+    fill_no_data(&mut data, side);
+
+    let mut rgb = Vec::with_capacity(side * side * 3);
+
+    for elevation in data {
+        rgb.extend_from_slice(&encoding.encode(elevation));
+    }
+
+    let mut buffer = Vec::new();
+
+    match format {
+        TerrainFormat::Png => {
+            PngEncoder::new(&mut buffer).write_image(&rgb, size, size, ExtendedColorType::Rgb8)?
+        }
+        TerrainFormat::Webp => WebPEncoder::new_lossless(&mut buffer).write_image(
+            &rgb,
+            size,
+            size,
+            ExtendedColorType::Rgb8,
+        )?,
+    }
+
+    Ok(buffer)
+}
+
+/// Extends the data into missing samples by the mean of their neighbours with
+/// data, then sets the rest to [`NO_DATA_ELEVATION`].
+fn fill_no_data(data: &mut [f32], side: usize) {
+    for _ in 0..FILL_PASSES {
+        let source = data.to_vec();
+
+        let mut filled = false;
+
+        for y in 0..side {
+            for x in 0..side {
+                if !source[y * side + x].is_nan() {
+                    continue;
+                }
+
+                let mut sum = 0.0;
+                let mut count = 0;
+
+                for ny in y.saturating_sub(1)..(y + 2).min(side) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(side) {
+                        let value = source[ny * side + nx];
+
+                        if !value.is_nan() {
+                            sum += value;
+                            count += 1;
+                        }
+                    }
+                }
+
+                if count > 0 {
+                    data[y * side + x] = sum / count as f32;
+                    filled = true;
+                }
+            }
+        }
+
+        if !filled {
+            break;
+        }
+    }
+
+    for value in data {
+        if value.is_nan() {
+            *value = NO_DATA_ELEVATION;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(encoding: TerrainEncoding, [r, g, b]: [u8; 3]) -> f64 {
+        let (r, g, b) = (f64::from(r), f64::from(g), f64::from(b));
+
+        match encoding {
+            TerrainEncoding::TerrainRgb => -10000.0 + (r * 65536.0 + g * 256.0 + b) * 0.1,
+            TerrainEncoding::Terrarium => r * 256.0 + g + b / 256.0 - 32768.0,
+        }
+    }
+
+    #[test]
+    fn known_values() {
+        assert_eq!(TerrainEncoding::TerrainRgb.encode(0.0), [1, 134, 160]);
+        assert_eq!(TerrainEncoding::Terrarium.encode(0.0), [128, 0, 0]);
+        assert_eq!(TerrainEncoding::Terrarium.encode(-0.5), [127, 255, 128]);
+        assert_eq!(TerrainEncoding::Terrarium.encode(2654.25), [138, 94, 64]);
+    }
+
+    #[test]
+    fn round_trip() {
+        for (encoding, precision) in [
+            (TerrainEncoding::TerrainRgb, 0.05),
+            (TerrainEncoding::Terrarium, 1.0 / 256.0),
+        ] {
+            for elevation in [-428.3, -12.5, -0.1, 0.0, 0.05, 1.0, 133.7, 2654.9, 8848.86] {
+                let decoded = decode(encoding, encoding.encode(elevation));
+
+                assert!(
+                    (decoded - f64::from(elevation)).abs() <= precision + 1e-3,
+                    "{encoding:?} {elevation} decoded as {decoded}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn out_of_range_clamped() {
+        assert_eq!(TerrainEncoding::TerrainRgb.encode(-20000.0), [0, 0, 0]);
+        assert_eq!(TerrainEncoding::TerrainRgb.encode(1e7), [255, 255, 255]);
+        assert_eq!(TerrainEncoding::Terrarium.encode(-40000.0), [0, 0, 0]);
+        assert_eq!(TerrainEncoding::Terrarium.encode(40000.0), [255, 255, 254]);
+    }
+}
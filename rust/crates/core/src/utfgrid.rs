@@ -0,0 +1,219 @@
+use crate::draw::path_geom::{path_geometry, walk_geometry_points};
+use crate::size::Size;
+use cairo::{Antialias, Context, FillRule, Format, ImageSurface, LineCap, LineJoin};
+use geo::Geometry;
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+
+/// Size of a grid cell in pixels.
+pub const RESOLUTION: u32 = 4;
+
+/// Width of the hit area of lines in pixels.
+const LINE_WIDTH: f64 = 8.0;
+
+/// Size of the hit area of points in pixels.
+const POINT_SIZE: f64 = 8.0;
+
+/// Index of a feature registered in a [`UtfGrid`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureId(u32);
+
+/// Grid of feature hit areas encoded as [UTFGrid](https://github.com/mapbox/utfgrid-spec).
+///
+/// Hit areas are painted with ID of the feature encoded as opaque colour
+/// (without antialiasing) so that features painted later cover earlier ones
+/// the same way as in the rendered tile.
+pub struct UtfGrid {
+    surface: ImageSurface,
+    context: Context,
+    keys: Vec<String>,
+    data: Vec<Map<String, Value>>,
+    index: HashMap<String, FeatureId>,
+}
+
+impl UtfGrid {
+    pub fn new(size: Size<u32>) -> cairo::Result<Self> {
+        let surface = ImageSurface::create(
+            Format::Rgb24,
+            size.width.div_ceil(RESOLUTION) as i32,
+            size.height.div_ceil(RESOLUTION) as i32,
+        )?;
+
+        let context = Context::new(&surface)?;
+
+        context.scale(1.0 / RESOLUTION as f64, 1.0 / RESOLUTION as f64);
+        context.set_antialias(Antialias::None);
+        context.set_line_join(LineJoin::Round);
+        context.set_line_cap(LineCap::Round);
+
+        Ok(Self {
+            surface,
+            context,
+            keys: Vec::new(),
+            data: Vec::new(),
+            index: HashMap::new(),
+        })
+    }
+
+    /// Registers feature `osm_id` of `layer`. Features registered repeatedly
+    /// (e.g. both an icon and a label) share the ID and the first attributes.
+    pub fn add_feature(
+        &mut self,
+        layer: &str,
+        osm_id: i64,
+        attributes: &[(&str, &str)],
+    ) -> FeatureId {
+        let key = format!("{layer}/{osm_id}");
+
+        if let Some(&id) = self.index.get(&key) {
+            return id;
+        }
+
+        let mut data = Map::new();
+
+        data.insert("layer".into(), json!(layer));
+        data.insert("osm_id".into(), json!(osm_id));
+
+        for &(name, value) in attributes {
+            data.insert(name.into(), json!(value));
+        }
+
+        let id = FeatureId(self.keys.len() as u32);
+
+        self.index.insert(key.clone(), id);
+        self.keys.push(key);
+        self.data.push(data);
+
+        id
+    }
+
+    /// Fills the hit area of a geometry already projected to the tile.
+    pub fn fill_geometry(&self, feature: FeatureId, geom: &Geometry) -> cairo::Result<()> {
+        let context = &self.context;
+
+        self.set_source(feature);
+
+        match geom {
+            Geometry::GeometryCollection(gc) => {
+                for geom in gc {
+                    self.fill_geometry(feature, geom)?;
+                }
+            }
+            Geometry::Point(_) | Geometry::MultiPoint(_) => {
+                walk_geometry_points(geom, &mut |point| {
+                    context.rectangle(
+                        point.x() - POINT_SIZE / 2.0,
+                        point.y() - POINT_SIZE / 2.0,
+                        POINT_SIZE,
+                        POINT_SIZE,
+                    );
+
+                    context.fill()
+                })?;
+            }
+            Geometry::Polygon(_)
+            | Geometry::MultiPolygon(_)
+            | Geometry::Rect(_)
+            | Geometry::Triangle(_) => {
+                path_geometry(context, geom);
+
+                context.set_fill_rule(FillRule::EvenOdd);
+                context.fill()?;
+            }
+            Geometry::LineString(_) | Geometry::MultiLineString(_) | Geometry::Line(_) => {
+                path_geometry(context, geom);
+
+                context.set_line_width(LINE_WIDTH);
+                context.stroke()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn set_source(&self, feature: FeatureId) {
+        // 0 is reserved for no feature
+        let value = feature.0 + 1;
+
+        self.context.set_source_rgb(
+            ((value >> 16) & 0xff) as f64 / 255.0,
+            ((value >> 8) & 0xff) as f64 / 255.0,
+            (value & 0xff) as f64 / 255.0,
+        );
+    }
+
+    /// Encodes the grid as UTFGrid JSON.
+    pub fn into_json(self) -> String {
+        let _span = tracy_client::span!("utfgrid::into_json");
+
+        let Self {
+            mut surface,
+            context,
+            keys,
+            data,
+            ..
+        } = self;
+
+        drop(context);
+
+        surface.flush();
+
+        let width = surface.width() as usize;
+        let height = surface.height() as usize;
+        let stride = surface.stride() as usize;
+        let pixels = surface.data().expect("surface data");
+
+        let mut grid = Vec::with_capacity(height);
+
+        for y in 0..height {
+            let row = &pixels[y * stride..y * stride + width * 4];
+
+            grid.push(
+                row.chunks(4)
+                    .map(|chunk| {
+                        let value =
+                            u32::from_ne_bytes(chunk.try_into().expect("pixel")) & 0x00ff_ffff;
+
+                        encode_id(value)
+                    })
+                    .collect::<String>(),
+            );
+        }
+
+        let mut all_keys = Vec::with_capacity(keys.len() + 1);
+
+        all_keys.push(String::new());
+
+        let data = keys
+            .iter()
+            .cloned()
+            .zip(data)
+            .map(|(key, data)| (key, Value::Object(data)))
+            .collect::<Map<_, _>>();
+
+        all_keys.extend(keys);
+
+        json!({
+            "grid": grid,
+            "keys": all_keys,
+            "data": data,
+        })
+        .to_string()
+    }
+}
+
+/// Encodes index to `keys` as a grid character, skipping `"` and `\`.
+fn encode_id(id: u32) -> char {
+    let mut code = id + 32;
+
+    if code >= 34 {
+        code += 1;
+    }
+
+    if code >= 92 {
+        code += 1;
+    }
+
+    // NOTE IDs mapping to surrogates are not representable; treat them as no feature
+    char::from_u32(code).unwrap_or(' ')
+}
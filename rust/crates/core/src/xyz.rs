@@ -1,5 +1,5 @@
 use crate::size::Size;
-use geo::Rect;
+use geo::{Coord, Rect};
 
 const EARTH_RADIUS: f64 = 6_378_137.0; // Equatorial radius of the Earth in meters (WGS 84)
 
@@ -18,6 +18,30 @@ pub fn tile_bounds_to_epsg3857(x: u32, y: u32, z: u32, tile_size: u32) -> Rect<f
     Rect::new((min_x, min_y), (max_x, max_y))
 }
 
+/// Projects WGS 84 longitude and latitude in degrees to EPSG:3857.
+pub fn lon_lat_to_epsg3857(lon: f64, lat: f64) -> Coord {
+    Coord {
+        x: EARTH_RADIUS * lon.to_radians(),
+        y: EARTH_RADIUS
+            * (std::f64::consts::FRAC_PI_4 + lat.to_radians() / 2.0)
+                .tan()
+                .ln(),
+    }
+}
+
+/// Projects EPSG:3857 to WGS 84 longitude and latitude in degrees.
+pub fn epsg3857_to_lon_lat(coord: Coord) -> Coord {
+    Coord {
+        x: (coord.x / EARTH_RADIUS).to_degrees(),
+        y: (2.0 * (coord.y / EARTH_RADIUS).exp().atan() - std::f64::consts::FRAC_PI_2).to_degrees(),
+    }
+}
+
+/// Ground distance per EPSG:3857 unit at northing `y`.
+pub fn ground_scale(y: f64) -> f64 {
+    1.0 / (y / EARTH_RADIUS).cosh()
+}
+
 pub fn bbox_size_in_pixels(bbox: Rect<f64>, zoom: f64) -> Size<u32> {
     let resolution = 2.0 * HALF_CIRCUMFERENCE / (256.0 * zoom.exp2());
 
@@ -27,6 +51,18 @@ pub fn bbox_size_in_pixels(bbox: Rect<f64>, zoom: f64) -> Size<u32> {
     )
 }
 
+/// Bounding box of `size` pixels at `zoom` centred at `center` (EPSG:3857).
+pub fn bbox_around(center: Coord, zoom: u32, size: Size<u32>) -> Rect<f64> {
+    let resolution = 2.0 * HALF_CIRCUMFERENCE / (256.0 * (zoom as f64).exp2());
+
+    let half = Coord {
+        x: size.width as f64 * resolution / 2.0,
+        y: size.height as f64 * resolution / 2.0,
+    };
+
+    Rect::new(center - half, center + half)
+}
+
 pub fn to_absolute_pixel_coords(x: f64, y: f64, zoom: u8) -> (f64, f64) {
     // Tile size in pixels (usually 256 or 512)
     let tile_size: f64 = 256.0;
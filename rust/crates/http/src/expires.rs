@@ -0,0 +1,260 @@
+use crate::{
+    tile::Tile,
+    tile_cache::{TileCache, TileFile},
+};
+use geo::{Geometry, Intersects};
+use maprender_core::ImageFormat;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs, io,
+    path::PathBuf,
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Processing of the tile expire lists imposm writes on data updates
+/// (`-expiretiles-dir`): tiles of every zoom containing or contained by an
+/// expired tile are marked dirty, to be re-rendered, if pre-rendered, or else
+/// deleted, to be rendered on demand.
+pub struct Expires {
+    /// Directory of the expire lists, `{date}/{time}.tiles`.
+    pub dir: PathBuf,
+    /// Zoom of the tiles in the expire lists.
+    pub zoom: u32,
+    /// Highest zoom the expired tiles are expanded to; the tiles above are
+    /// expired through the `.index` files of the cache.
+    pub max_zoom: u32,
+    /// Expire lists processed at once are limited to those reaching this
+    /// many tiles; `None` processes all pending lists.
+    pub min_batch_size: Option<usize>,
+}
+
+/// Tiles kept rendered in the cache: re-rendered in the background when they
+/// expire, instead of deleted.
+pub struct Prerender {
+    /// EPSG:3857 area to pre-render.
+    pub polygon: Geometry,
+    pub min_zoom: u32,
+    pub max_zoom: u32,
+    pub scales: Vec<f64>,
+    pub extension: String,
+    pub format: ImageFormat,
+}
+
+impl Prerender {
+    pub fn covers(&self, tile: Tile) -> bool {
+        (self.min_zoom..=self.max_zoom).contains(&tile.zoom) && self.overlaps(tile)
+    }
+
+    pub fn overlaps(&self, tile: Tile) -> bool {
+        self.polygon.intersects(&tile.bbox())
+    }
+
+    pub fn files(&self, tile: Tile) -> impl Iterator<Item = TileFile> {
+        self.scales.iter().map(move |&scale| TileFile {
+            tile,
+            scale,
+            extension: self.extension.clone(),
+        })
+    }
+}
+
+/// Delay of the first retry of a failed re-render, doubled with every further
+/// failure of the tile up to [`MAX_RETRY_DELAY`].
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+/// Tiles waiting for re-rendering.
+#[derive(Default)]
+pub struct DirtyTiles {
+    pending: Mutex<Pending>,
+    cv: Condvar,
+}
+
+#[derive(Default)]
+struct Pending {
+    tiles: BTreeSet<Tile>,
+    /// Failed tiles by the time of their next attempt.
+    retries: BTreeSet<(Instant, Tile)>,
+    /// Consecutive failures of the tiles.
+    failures: HashMap<Tile, u32>,
+}
+
+impl DirtyTiles {
+    pub fn insert(&self, tile: Tile) {
+        self.pending.lock().unwrap().tiles.insert(tile);
+
+        self.cv.notify_one();
+    }
+
+    /// Waits for a dirty tile and takes it; lower zooms first, as they cover
+    /// more of the map, and failed tiles once their retry is due.
+    pub fn take(&self) -> Tile {
+        let mut pending = self.pending.lock().unwrap();
+
+        loop {
+            let now = Instant::now();
+
+            while let Some(&(due, tile)) = pending.retries.first()
+                && due <= now
+            {
+                pending.retries.pop_first();
+
+                pending.tiles.insert(tile);
+            }
+
+            if let Some(tile) = pending.tiles.pop_first() {
+                return tile;
+            }
+
+            pending = match pending.retries.first() {
+                Some(&(due, _)) => self.cv.wait_timeout(pending, due - now).unwrap().0,
+                None => self.cv.wait(pending).unwrap(),
+            };
+        }
+    }
+
+    /// Schedules a retry of `tile` after its re-render failed, as it stays
+    /// marked dirty.
+    pub fn retry(&self, tile: Tile) {
+        let mut pending = self.pending.lock().unwrap();
+
+        let failures = pending.failures.entry(tile).or_default();
+
+        let delay = RETRY_DELAY
+            .saturating_mul(1 << (*failures).min(16))
+            .min(MAX_RETRY_DELAY);
+
+        *failures += 1;
+
+        pending.retries.insert((Instant::now() + delay, tile));
+
+        // a waiting worker picks the new deadline
+        self.cv.notify_one();
+    }
+
+    /// Forgets the failures of `tile` once re-rendered.
+    pub fn rendered(&self, tile: Tile) {
+        let mut pending = self.pending.lock().unwrap();
+
+        if pending.failures.remove(&tile).is_some() {
+            pending.retries.retain(|&(_, retried)| retried != tile);
+        }
+    }
+}
+
+impl Expires {
+    /// Processes the pending expire lists, or a batch of them, and deletes
+    /// them. Returns whether some lists were left for the next batch.
+    pub fn process(
+        &self,
+        tile_cache: &TileCache,
+        prerender: Option<&Prerender>,
+        dirty_tiles: &DirtyTiles,
+    ) -> io::Result<bool> {
+        let mut files = Vec::new();
+
+        for dir in fs::read_dir(&self.dir)? {
+            let dir = dir?.path();
+
+            if dir.is_dir() {
+                for file in fs::read_dir(&dir)? {
+                    files.push(file?.path());
+                }
+            }
+        }
+
+        if files.is_empty() {
+            return Ok(false);
+        }
+
+        files.sort();
+
+        let pending = files.len();
+
+        let mut expired = HashSet::new();
+
+        let mut count = 0;
+
+        for file in &files {
+            count += 1;
+
+            expired.extend(
+                fs::read_to_string(file)?
+                    .lines()
+                    .filter_map(|line| Tile::parse(line.trim()))
+                    .filter(|&tile| prerender.is_none_or(|prerender| prerender.overlaps(tile))),
+            );
+
+            if self
+                .min_batch_size
+                .is_some_and(|min_batch_size| expired.len() >= min_batch_size)
+            {
+                break;
+            }
+        }
+
+        files.truncate(count);
+
+        let mut tiles = BTreeSet::new();
+
+        for tile in expired {
+            let mut parent = Some(tile);
+
+            while let Some(tile) = parent {
+                tiles.insert(tile);
+
+                parent = tile.parent();
+            }
+
+            self.collect_children(tile, &mut tiles);
+        }
+
+        eprintln!("Processing {} tiles of {count} expire files.", tiles.len());
+
+        let mut dirty = 0;
+
+        // sequentially, not to choke the IO
+        for &tile in &tiles {
+            match prerender {
+                Some(prerender) if prerender.covers(tile) => {
+                    if prerender.files(tile).any(|file| tile_cache.exists(&file)) {
+                        tile_cache.mark_dirty(tile)?;
+
+                        dirty_tiles.insert(tile);
+
+                        dirty += 1;
+                    }
+                }
+                _ => tile_cache.remove(tile)?,
+            }
+
+            if tile.zoom == self.zoom {
+                tile_cache.expire_index(tile)?;
+            }
+        }
+
+        for file in &files {
+            fs::remove_file(file)?;
+        }
+
+        eprintln!(
+            "Finished processing expire files ({count} of {pending}), {dirty} tiles to re-render."
+        );
+
+        Ok(count < pending)
+    }
+
+    fn collect_children(&self, tile: Tile, tiles: &mut BTreeSet<Tile>) {
+        if tile.zoom >= self.max_zoom {
+            return;
+        }
+
+        for child in tile.children() {
+            tiles.insert(child);
+
+            self.collect_children(child, tiles);
+        }
+    }
+}
@@ -1,9 +1,18 @@
-use clap::Parser;
+mod expires;
+mod seed;
+mod tile;
+mod tile_cache;
+
+use clap::{Parser, Subcommand};
 use dotenvy::dotenv;
-use geo::Geometry;
+use expires::{DirtyTiles, Expires, Prerender};
+use geo::{Coord, Geometry, Rect};
 use maprender_core::{
-    ImageFormat, RenderError, RenderRequest, SvgRepo, load_geometry_from_geojson,
-    load_hillshading_datasets, render, tile_bounds_to_epsg3857,
+    Abbreviations, ContourExportError, ContourInterval, DebugFlags, ElevationError,
+    HillshadingDatasets, HillshadingManifest, HillshadingPool, ImageFormat, Legend, LegendError,
+    Pois, RenderError, RenderRequest, SvgRepo, TerrainEncoding, TerrainFormat, elevation_profile,
+    elevations, export_contours, load_geometry_from_geojson, parse_gpx, render, render_terrain,
+    tile_bounds_to_epsg3857,
 };
 use oxhttp::{
     Server,
@@ -13,8 +22,11 @@ use postgres::{Config, NoTls};
 use r2d2_postgres::PostgresConnectionManager;
 use regex::Regex;
 use std::{
+    borrow::Cow,
     collections::VecDeque,
+    io::Read,
     net::Ipv4Addr,
+    path::{Path, PathBuf},
     str::FromStr,
     sync::{
         Arc, Condvar, LazyLock, Mutex,
@@ -22,18 +34,28 @@ use std::{
     },
     time::Duration,
 };
+use tile::Tile;
+use tile_cache::{ExpireIndex, TileCache, TileFile};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to the directory with symbol SVGs.
     #[arg(long, env = "MAPRENDER_SVG_BASE_PATH")]
     svg_base_path: String,
 
-    /// Path to hillshading datasets.
+    /// Path to hillshading datasets, declared by its `datasets.yaml` (the
+    /// built-in manifest is used if there is none).
     #[arg(long, env = "MAPRENDER_HILLSHADING_BASE_PATH")]
     hillshading_base_path: String,
 
+    /// GDAL block cache budget in megabytes, shared by all workers.
+    #[arg(long, env = "MAPRENDER_GDAL_CACHE_MB", default_value_t = 512)]
+    gdal_cache_mb: usize,
+
     /// Number of rendering worker threads.
     #[arg(long, env = "MAPRENDER_WORKER_COUNT", default_value_t = 24)]
     worker_count: usize,
@@ -50,6 +72,10 @@ struct Cli {
     #[arg(long, env = "MAPRENDER_PORT", default_value_t = 3050)]
     port: u16,
 
+    /// Highest zoom of the served tiles.
+    #[arg(long, env = "MAPRENDER_MAX_ZOOM", default_value_t = 20)]
+    max_zoom: u32,
+
     /// Maximum concurrent HTTP connections.
     #[arg(
         long,
@@ -69,10 +95,115 @@ struct Cli {
     /// Mask geojson polygon file
     #[arg(long, env = "MAPRENDER_MASK_GEOJSON")]
     mask_geojson: Option<String>,
+
+    /// Label abbreviation rules (YAML); built-in rules are used if not set.
+    #[arg(long, env = "MAPRENDER_ABBREVIATIONS")]
+    abbreviations: Option<String>,
+
+    /// POI style (YAML); built-in style is used if not set.
+    #[arg(long, env = "MAPRENDER_POI_STYLE")]
+    poi_style: Option<String>,
+
+    /// Directory to cache rendered tiles in, in the layout of the mapserver;
+    /// tiles are not cached if not set.
+    #[arg(long, env = "MAPRENDER_TILE_CACHE_DIR")]
+    tile_cache_dir: Option<PathBuf>,
+
+    /// Directory imposm writes tile expire lists to (`-expiretiles-dir`);
+    /// requires the tile cache.
+    #[arg(long, env = "MAPRENDER_EXPIRES_DIR", requires = "tile_cache_dir")]
+    expires_dir: Option<PathBuf>,
+
+    /// Zoom of the tiles in the expire lists (`-expiretiles-zoom`).
+    #[arg(long, env = "MAPRENDER_EXPIRES_ZOOM", default_value_t = 14)]
+    expires_zoom: u32,
+
+    /// Expire lists processed at once are limited to those reaching this many
+    /// tiles; all pending lists are processed if not set.
+    #[arg(long, env = "MAPRENDER_MIN_EXPIRED_BATCH_SIZE")]
+    min_expired_batch_size: Option<usize>,
+
+    /// GeoJSON polygon of the area whose tiles are re-rendered in the
+    /// background when they expire; expired tiles are just deleted if not set.
+    #[arg(long, env = "MAPRENDER_PRERENDER_POLYGON", requires = "tile_cache_dir")]
+    prerender_polygon: Option<PathBuf>,
+
+    /// Lowest zoom of the pre-rendered tiles.
+    #[arg(long, env = "MAPRENDER_PRERENDER_MIN_ZOOM", default_value_t = 0)]
+    prerender_min_zoom: u32,
+
+    /// Highest zoom of the pre-rendered tiles; cached tiles above it are
+    /// expired through index files of the expire zoom tiles.
+    #[arg(long, env = "MAPRENDER_PRERENDER_MAX_ZOOM", default_value_t = 14)]
+    prerender_max_zoom: u32,
+
+    /// Scales of the pre-rendered tiles.
+    #[arg(
+        long,
+        env = "MAPRENDER_PRERENDER_SCALES",
+        value_delimiter = ',',
+        default_value = "1"
+    )]
+    prerender_scales: Vec<f64>,
+
+    /// File extension, and so the format, of the pre-rendered tiles.
+    #[arg(long, env = "MAPRENDER_PRERENDER_EXTENSION", default_value = "png")]
+    prerender_extension: String,
+
+    /// Number of tiles re-rendered concurrently in the background.
+    #[arg(long, env = "MAPRENDER_PRERENDER_WORKERS", default_value_t = 4)]
+    prerender_workers: usize,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Renders the tiles of an area to the tile cache and exits.
+    Seed {
+        /// Directory of the tile cache to fill, as `--tile-cache-dir` of the
+        /// server.
+        #[arg(long, env = "MAPRENDER_TILE_CACHE_DIR")]
+        tile_cache_dir: PathBuf,
+
+        /// GeoJSON polygon of the area.
+        #[arg(long)]
+        polygon: PathBuf,
+
+        /// Lowest zoom to render.
+        #[arg(long, default_value_t = 0)]
+        min_zoom: u32,
+
+        /// Highest zoom to render.
+        #[arg(long)]
+        max_zoom: u32,
+
+        /// Scales to render.
+        #[arg(long, value_delimiter = ',', default_value = "1")]
+        scales: Vec<f64>,
+
+        /// File extension, and so the format, of the tiles.
+        #[arg(long, default_value = "png")]
+        extension: String,
+
+        /// Renders also the tiles already cached.
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+enum Job {
+    Tile(RenderRequest),
+    LegendItem {
+        id: String,
+        format: ImageFormat,
+        scale: f64,
+    },
+    /// Reads of the hillshading datasets (elevation queries, terrain tiles,
+    /// contour export), which send their own result.
+    Datasets(Box<dyn FnOnce(&mut HillshadingDatasets) + Send>),
 }
 
 struct RenderTask {
-    request: RenderRequest,
+    job: Job,
     resp_tx: mpsc::Sender<Result<Vec<Vec<u8>>, ReError>>,
 }
 
@@ -86,11 +217,20 @@ enum ReError {
     #[error(transparent)]
     RenderError(#[from] RenderError),
 
+    #[error(transparent)]
+    LegendError(#[from] LegendError),
+
     #[error(transparent)]
     ConnectionPoolError(#[from] r2d2::Error),
 
     #[error("worker closed: {0}")]
     RecvError(#[from] RecvError),
+
+    #[error("tile cache: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("empty render result")]
+    EmptyResult,
 }
 
 impl RenderWorkerPool {
@@ -98,8 +238,11 @@ impl RenderWorkerPool {
         pool: r2d2::Pool<PostgresConnectionManager<NoTls>>,
         worker_count: usize,
         svg_base_path: Arc<str>,
-        hillshading_base_path: Arc<str>,
+        hillshading_pool: Arc<HillshadingPool>,
         mask_geometry: Option<Geometry>,
+        abbreviations: Arc<Abbreviations>,
+        pois: Arc<Pois>,
+        legend: Arc<Legend>,
     ) -> Self {
         let tasks = Arc::new(Mutex::new(VecDeque::new()));
         let cv = Arc::new(Condvar::new());
@@ -109,19 +252,21 @@ impl RenderWorkerPool {
             let cv = cv.clone();
             let pool = pool.clone();
             let svg_base_path = svg_base_path.clone();
-            let hillshading_base_path = hillshading_base_path.clone();
+            let hillshading_pool = hillshading_pool.clone();
             let mask_geometry = mask_geometry.clone();
+            let abbreviations = abbreviations.clone();
+            let pois = pois.clone();
+            let legend = legend.clone();
 
             std::thread::Builder::new()
                 .name(format!("render-worker-{worker_id}"))
                 .spawn(move || {
                     let mut svg_repo = SvgRepo::new(&*svg_base_path);
 
-                    let mut hillshading_datasets =
-                        Some(load_hillshading_datasets(&*hillshading_base_path));
+                    let mut hillshading_datasets = Some(HillshadingDatasets::new(hillshading_pool));
 
                     loop {
-                        let RenderTask { request, resp_tx } = {
+                        let RenderTask { job, resp_tx } = {
                             let mut guard = tasks.lock().unwrap();
                             while guard.is_empty() {
                                 guard = cv.wait(guard).unwrap();
@@ -129,16 +274,35 @@ impl RenderWorkerPool {
                             guard.pop_front().unwrap()
                         };
 
-                        let result = pool.get().map_err(ReError::from).and_then(|mut client| {
-                            render(
-                                &request,
-                                &mut client,
-                                &mut svg_repo,
-                                &mut hillshading_datasets,
-                                mask_geometry.as_ref(),
-                            )
-                            .map_err(ReError::from)
-                        });
+                        let result = match job {
+                            Job::Tile(request) => {
+                                pool.get().map_err(ReError::from).and_then(|mut client| {
+                                    render(
+                                        &request,
+                                        &mut client,
+                                        &mut svg_repo,
+                                        &abbreviations,
+                                        &pois,
+                                        &mut hillshading_datasets,
+                                        mask_geometry.as_ref(),
+                                    )
+                                    .map_err(ReError::from)
+                                })
+                            }
+                            Job::LegendItem { id, format, scale } => legend
+                                .render_item(&id, &mut svg_repo, &pois, format, scale)
+                                .map(|image| vec![image])
+                                .map_err(ReError::from),
+                            Job::Datasets(work) => {
+                                if let Some(datasets) = hillshading_datasets.as_mut() {
+                                    work(datasets);
+
+                                    datasets.release();
+                                }
+
+                                Ok(Vec::new())
+                            }
+                        };
 
                         // Ignore send errors (client dropped).
                         let _ = resp_tx.send(result);
@@ -151,11 +315,39 @@ impl RenderWorkerPool {
     }
 
     fn render(&self, request: RenderRequest) -> Result<Vec<Vec<u8>>, ReError> {
+        self.submit(Job::Tile(request))
+    }
+
+    fn render_legend_item(
+        &self,
+        id: String,
+        format: ImageFormat,
+        scale: f64,
+    ) -> Result<Vec<Vec<u8>>, ReError> {
+        self.submit(Job::LegendItem { id, format, scale })
+    }
+
+    /// Runs `work` on a worker, so that dataset reads are limited by the
+    /// number of workers instead of blocking the connection threads.
+    fn run<T: Send + 'static>(
+        &self,
+        work: impl FnOnce(&mut HillshadingDatasets) -> T + Send + 'static,
+    ) -> Result<T, ReError> {
+        let (tx, rx) = mpsc::channel();
+
+        self.submit(Job::Datasets(Box::new(move |datasets| {
+            let _ = tx.send(work(datasets));
+        })))?;
+
+        Ok(rx.recv()?)
+    }
+
+    fn submit(&self, job: Job) -> Result<Vec<Vec<u8>>, ReError> {
         let (resp_tx, resp_rx) = mpsc::channel();
 
         {
             let mut guard = self.tasks.lock().unwrap();
-            guard.push_back(RenderTask { request, resp_tx });
+            guard.push_back(RenderTask { job, resp_tx });
             self.cv.notify_one();
         }
 
@@ -186,69 +378,725 @@ fn main() {
                 Err(err) => panic!("failed to load mask geojson {path}: {err}"),
             });
 
+    let abbreviations = cli
+        .abbreviations
+        .map_or_else(
+            || Ok(Abbreviations::default()),
+            |path| Abbreviations::load(&path),
+        )
+        .unwrap_or_else(|err| panic!("failed to load abbreviations: {err}"));
+
+    let pois = cli
+        .poi_style
+        .map_or_else(|| Ok(Pois::default()), |path| Pois::load(&path))
+        .and_then(|pois| {
+            pois.validate(&SvgRepo::new(&cli.svg_base_path))?;
+
+            Ok(pois)
+        })
+        .unwrap_or_else(|err| panic!("invalid POI style: {err}"));
+
+    let hillshading_manifest = HillshadingManifest::load(&cli.hillshading_base_path)
+        .unwrap_or_else(|err| panic!("invalid hillshading manifest: {err}"));
+
+    HillshadingPool::set_cache_budget(cli.gdal_cache_mb);
+
+    let hillshading_pool = Arc::new(HillshadingPool::new(hillshading_manifest));
+
+    let legend = Arc::new(Legend::new(&pois));
+
+    let open_tile_cache = |dir| {
+        let tile_cache = TileCache::new(dir);
+
+        if cli.expires_dir.is_some() {
+            tile_cache.with_expire_index(ExpireIndex {
+                expires_zoom: cli.expires_zoom,
+                max_zoom: cli.prerender_max_zoom,
+            })
+        } else {
+            tile_cache
+        }
+    };
+
+    let tile_cache = cli.tile_cache_dir.map(|dir| Arc::new(open_tile_cache(dir)));
+
+    let prerender = cli.prerender_polygon.map(|path| {
+        Arc::new(load_prerender(
+            &path,
+            cli.prerender_min_zoom,
+            cli.prerender_max_zoom,
+            cli.prerender_scales,
+            cli.prerender_extension,
+        ))
+    });
+
     let worker_pool = Arc::new(RenderWorkerPool::new(
-        connection_pool,
+        connection_pool.clone(),
         cli.worker_count,
         Arc::from(cli.svg_base_path.as_str()),
-        Arc::from(cli.hillshading_base_path.as_str()),
+        hillshading_pool.clone(),
         mask_geometry,
+        Arc::new(abbreviations),
+        Arc::new(pois),
+        legend.clone(),
     ));
 
-    Server::new(move |request| render_response(request, worker_pool.clone()))
-        .with_max_concurrent_connections(cli.max_concurrent_connections)
-        .with_global_timeout(Duration::from_secs(cli.global_timeout_secs))
-        .bind((cli.host, cli.port))
-        .spawn()
-        .expect("server spawned")
-        .join()
-        .expect("server joined");
+    if let Some(Command::Seed {
+        tile_cache_dir,
+        polygon,
+        min_zoom,
+        max_zoom,
+        scales,
+        extension,
+        force,
+    }) = cli.command
+    {
+        let tile_cache = open_tile_cache(tile_cache_dir);
+
+        let prerender = load_prerender(&polygon, min_zoom, max_zoom, scales, extension);
+
+        let failed = seed::seed(
+            &worker_pool,
+            &tile_cache,
+            &prerender,
+            cli.worker_count,
+            force,
+        );
+
+        std::process::exit(if failed > 0 { 1 } else { 0 });
+    }
+
+    if let Some(tile_cache) = &tile_cache {
+        let dirty_tiles = Arc::new(DirtyTiles::default());
+
+        if let Some(prerender) = &prerender {
+            let tiles = tile_cache
+                .dirty_tiles(prerender.max_zoom)
+                .unwrap_or_else(|err| panic!("failed to scan dirty tiles: {err}"));
+
+            for tile in tiles {
+                dirty_tiles.insert(tile);
+            }
+
+            for worker_id in 0..cli.prerender_workers {
+                let worker_pool = worker_pool.clone();
+                let tile_cache = tile_cache.clone();
+                let prerender = prerender.clone();
+                let dirty_tiles = dirty_tiles.clone();
+
+                std::thread::Builder::new()
+                    .name(format!("prerender-worker-{worker_id}"))
+                    .spawn(move || {
+                        rerender_dirty_tiles(&worker_pool, &tile_cache, &prerender, &dirty_tiles)
+                    })
+                    .expect("prerender worker spawn");
+            }
+        }
+
+        if let Some(dir) = cli.expires_dir {
+            let expires = Expires {
+                dir,
+                zoom: cli.expires_zoom,
+                max_zoom: cli.prerender_max_zoom,
+                min_batch_size: cli.min_expired_batch_size,
+            };
+
+            let tile_cache = tile_cache.clone();
+            let prerender = prerender.clone();
+
+            std::thread::Builder::new()
+                .name("expires".into())
+                .spawn(move || {
+                    process_expire_files(&expires, &tile_cache, prerender.as_deref(), &dirty_tiles)
+                })
+                .expect("expires thread spawn");
+        }
+    }
+
+    Server::new(move |request| {
+        let path = request.uri().path();
+
+        if path == "/metrics" {
+            metrics_response(&hillshading_pool)
+        } else if path == "/elevation" {
+            elevation_response(request, &worker_pool)
+        } else if path == "/elevation/profile" {
+            elevation_profile_response(request, &worker_pool)
+        } else if let Some(tile) = path.strip_prefix("/terrain/") {
+            terrain_response(tile, &worker_pool)
+        } else if path == "/contours.geojson" {
+            contours_response(request, &connection_pool, &worker_pool)
+        } else if path == "/legend" {
+            legend_response(&legend)
+        } else if let Some(item) = path.strip_prefix("/legend/") {
+            legend_item_response(item, &worker_pool)
+        } else {
+            render_response(
+                request,
+                worker_pool.clone(),
+                tile_cache.as_deref(),
+                cli.max_zoom,
+            )
+        }
+    })
+    .with_max_concurrent_connections(cli.max_concurrent_connections)
+    .with_global_timeout(Duration::from_secs(cli.global_timeout_secs))
+    .bind((cli.host, cli.port))
+    .spawn()
+    .expect("server spawned")
+    .join()
+    .expect("server joined");
+}
+
+/// Percent-decoded value of the query parameter `name`.
+fn query_param<'a>(request: &'a Request<Body>, name: &str) -> Option<Cow<'a, str>> {
+    form_urlencoded::parse(request.uri().query()?.as_bytes())
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value)
+}
+
+fn json_response(status: StatusCode, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .header("Access-Control-Allow-Origin", "*")
+        .body(Body::from(body))
+        .expect("body should be built")
+}
+
+fn bad_request(message: impl Into<String>) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::BAD_REQUEST)
+        .body(Body::from(message.into()))
+        .expect("body should be built")
+}
+
+fn worker_error_response(what: &str, err: ReError) -> Response<Body> {
+    eprintln!("{what} failed: {err}");
+
+    Response::builder()
+        .status(StatusCode::INTERNAL_SERVER_ERROR)
+        .body(Body::from("worker error"))
+        .expect("body should be built")
+}
+
+fn elevation_error_response(err: ElevationError) -> Response<Body> {
+    match err {
+        ElevationError::Gdal(err) => {
+            eprintln!("elevation lookup failed: {err}");
+
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("elevation error"))
+                .expect("body should be built")
+        }
+        err => bad_request(err.to_string()),
+    }
+}
+
+/// Elevations of `points=lon,lat;lon,lat;…` as a JSON array, `null` where
+/// there is no data.
+fn elevation_response(request: &Request<Body>, worker_pool: &RenderWorkerPool) -> Response<Body> {
+    const MAX_POINTS: usize = 1000;
+
+    let Some(points) = query_param(request, "points") else {
+        return bad_request("missing points");
+    };
+
+    let coords: Option<Vec<_>> = points
+        .split(';')
+        .map(|point| {
+            let (lon, lat) = point.split_once(',')?;
+
+            Some(Coord {
+                x: lon.parse().ok()?,
+                y: lat.parse().ok()?,
+            })
+        })
+        .collect();
+
+    let Some(coords) = coords else {
+        return bad_request("invalid points");
+    };
+
+    if coords.len() > MAX_POINTS {
+        return bad_request(format!("more than {MAX_POINTS} points"));
+    }
+
+    match worker_pool.run(move |datasets| elevations(datasets, &coords)) {
+        Ok(Ok(elevations)) => json_response(
+            StatusCode::OK,
+            serde_json::to_string(&elevations).expect("serialized elevations"),
+        ),
+        Ok(Err(err)) => elevation_error_response(err),
+        Err(err) => worker_error_response("elevation lookup", err),
+    }
+}
+
+/// Elevation profile of a posted GeoJSON LineString (geometry or feature) or
+/// GPX track, sampled every `spacing` metres (default 20).
+fn elevation_profile_response(
+    request: &mut Request<Body>,
+    worker_pool: &RenderWorkerPool,
+) -> Response<Body> {
+    const MAX_BODY_SIZE: u64 = 16 * 1024 * 1024;
+
+    let spacing = query_param(request, "spacing")
+        .and_then(|spacing| spacing.parse::<f64>().ok())
+        .filter(|spacing| *spacing >= 1.0)
+        .unwrap_or(20.0);
+
+    let mut body = Vec::new();
+
+    if let Err(err) = request
+        .body_mut()
+        .take(MAX_BODY_SIZE)
+        .read_to_end(&mut body)
+    {
+        return bad_request(format!("error reading body: {err}"));
+    }
+
+    let line = if body.trim_ascii_start().starts_with(b"<") {
+        match parse_gpx(&body) {
+            Ok(line) => line,
+            Err(err) => return bad_request(err.to_string()),
+        }
+    } else {
+        let geometry = serde_json::from_slice::<geojson::GeoJson>(&body)
+            .map_err(|err| err.to_string())
+            .and_then(|geojson| Geometry::try_from(geojson).map_err(|err| err.to_string()));
+
+        match geometry {
+            Ok(Geometry::LineString(line)) => line,
+            Ok(_) => return bad_request("expected a LineString"),
+            Err(err) => return bad_request(format!("invalid GeoJSON: {err}")),
+        }
+    };
+
+    match worker_pool.run(move |datasets| elevation_profile(datasets, &line, spacing)) {
+        Ok(Ok(profile)) => json_response(StatusCode::OK, profile.to_json()),
+        Ok(Err(err)) => elevation_error_response(err),
+        Err(err) => worker_error_response("elevation profile", err),
+    }
 }
 
-fn render_response(request: &Request<Body>, worker_pool: Arc<RenderWorkerPool>) -> Response<Body> {
-    let Some(tile_request) = parse_tile_path(request.uri().path()) else {
+/// Terrain tile `{encoding}/{zoom}/{x}/{y}[@2x].{png|webp}`, 512 pixels
+/// wide at `@2x`.
+fn terrain_response(tile: &str, worker_pool: &RenderWorkerPool) -> Response<Body> {
+    static TILE_REGEXP: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?P<encoding>[a-z-]+)/(?P<zoom>\d+)/(?P<x>\d+)/(?P<y>\d+)(?P<retina>@2x)?\.(?P<ext>png|webp)$")
+            .unwrap()
+    });
+
+    let Some(captures) = TILE_REGEXP.captures(tile) else {
+        return bad_request("invalid terrain tile path");
+    };
+
+    let Some(encoding) = TerrainEncoding::parse(&captures["encoding"]) else {
+        return bad_request("unknown terrain encoding");
+    };
+
+    let (Ok(zoom), Ok(x), Ok(y)) = (
+        captures["zoom"].parse::<u32>(),
+        captures["x"].parse::<u32>(),
+        captures["y"].parse::<u32>(),
+    ) else {
+        return bad_request("invalid terrain tile path");
+    };
+
+    let (format, content_type) = match &captures["ext"] {
+        "webp" => (TerrainFormat::Webp, "image/webp"),
+        _ => (TerrainFormat::Png, "image/png"),
+    };
+
+    let size = if captures.name("retina").is_some() {
+        512
+    } else {
+        256
+    };
+
+    let bbox = tile_bounds_to_epsg3857(x, y, zoom, 256);
+
+    match worker_pool
+        .run(move |datasets| render_terrain(datasets, bbox, size, encoding, format))
+        .map_err(|err| err.to_string())
+        .and_then(|result| result.map_err(|err| err.to_string()))
+    {
+        Ok(tile) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", content_type)
+            .header("Access-Control-Allow-Origin", "*")
+            .body(Body::from(tile)),
+        Err(err) => {
+            eprintln!("terrain render failed: {err}");
+
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("render error"))
+        }
+    }
+    .expect("body should be built")
+}
+
+/// Contour lines of `bbox=min_lon,min_lat,max_lon,max_lat` as drawn at
+/// `zoom` (default 14).
+fn contours_response(
+    request: &Request<Body>,
+    connection_pool: &r2d2::Pool<PostgresConnectionManager<NoTls>>,
+    worker_pool: &RenderWorkerPool,
+) -> Response<Body> {
+    let Some(bbox) = query_param(request, "bbox") else {
+        return bad_request("missing bbox");
+    };
+
+    let bbox: Option<Vec<f64>> = bbox.split(',').map(|value| value.parse().ok()).collect();
+
+    let Some(&[min_lon, min_lat, max_lon, max_lat]) = bbox.as_deref() else {
+        return bad_request("invalid bbox");
+    };
+
+    let Some(zoom) = query_param(request, "zoom").map_or(Some(14), |zoom| zoom.parse().ok()) else {
+        return bad_request("invalid zoom");
+    };
+
+    let mut client = match connection_pool.get() {
+        Ok(client) => client,
+        Err(err) => {
+            eprintln!("contour export failed: {err}");
+
+            return Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(Body::from("database unavailable"))
+                .expect("body should be built");
+        }
+    };
+
+    let bbox = Rect::new((min_lon, min_lat), (max_lon, max_lat));
+
+    match worker_pool
+        .run(move |datasets| export_contours(&mut client, Some(&*datasets), bbox, zoom))
+    {
+        Ok(Ok(geojson)) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "application/geo+json")
+            .header("Access-Control-Allow-Origin", "*")
+            .body(Body::from(geojson))
+            .expect("body should be built"),
+        Ok(Err(ContourExportError::Postgres(err))) => {
+            eprintln!("contour export failed: {err}");
+
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("export error"))
+                .expect("body should be built")
+        }
+        Ok(Err(err)) => bad_request(err.to_string()),
+        Err(err) => worker_error_response("contour export", err),
+    }
+}
+
+fn render_response(
+    request: &Request<Body>,
+    worker_pool: Arc<RenderWorkerPool>,
+    tile_cache: Option<&TileCache>,
+    max_zoom: u32,
+) -> Response<Body> {
+    let Some((mut tile_request, tile_file)) = parse_tile_path(request.uri().path(), max_zoom)
+    else {
         return Response::builder()
             .status(StatusCode::BAD_REQUEST)
             .body(Body::empty())
             .expect("body should be built");
     };
 
+    let query_param = |name: &str| query_param(request, name);
+
+    if let Some(debug) = query_param("debug") {
+        tile_request.debug = DebugFlags::parse(&debug);
+    }
+
+    let hillshading = &mut tile_request.hillshading;
+
+    for (name, value) in [
+        ("azimuth", &mut hillshading.azimuth),
+        ("altitude", &mut hillshading.altitude),
+        ("exaggeration", &mut hillshading.exaggeration),
+    ] {
+        if let Some(parsed) = query_param(name).and_then(|param| param.parse().ok()) {
+            *value = parsed;
+        }
+    }
+
+    let query_flag =
+        |name: &str| query_param(name).map(|value| matches!(value.as_ref(), "1" | "true"));
+
+    if let Some(multidirectional) = query_flag("multidirectional") {
+        hillshading.multidirectional = multidirectional;
+    }
+
+    if let Some(aerial_perspective) = query_flag("aerial_perspective") {
+        hillshading.aerial_perspective = aerial_perspective;
+    }
+
+    if let Some(shadows) = query_flag("shadows") {
+        hillshading.shadows = shadows;
+    }
+
+    if let Some(sky_view) = query_flag("sky_view") {
+        hillshading.sky_view = sky_view;
+    }
+
+    if let Some(tint) = query_flag("tint") {
+        tile_request.hypsometric_tint = tint;
+    }
+
+    if let Some(slope) = query_flag("slope") {
+        tile_request.slope = slope;
+    }
+
+    if let Some(overlay_only) = query_flag("overlay") {
+        tile_request.overlay_only = overlay_only;
+    }
+
+    if let Some(contour_interval) = query_param("contour_interval") {
+        tile_request.contour_interval = ContourInterval::parse(&contour_interval);
+    }
+
     let format = tile_request.format;
 
-    let rendered = match worker_pool.render(tile_request) {
-        Ok(rendered) => rendered,
+    let render = || -> Result<Vec<u8>, ReError> {
+        worker_pool
+            .render(tile_request)?
+            .into_iter()
+            .next()
+            .ok_or(ReError::EmptyResult)
+    };
+
+    // tiles customized by query parameters are not cached
+    let result = match tile_cache {
+        Some(tile_cache) if request.uri().query().is_none_or(str::is_empty) => {
+            tile_cache.get_or_render(&tile_file, render)
+        }
+        _ => render(),
+    };
+
+    match result {
+        Ok(tile) => {
+            let content_type = match format {
+                ImageFormat::Svg => "image/svg+xml",
+                ImageFormat::Pdf => "application/pdf",
+                ImageFormat::Jpeg => "image/jpeg",
+                ImageFormat::Png => "image/png",
+                ImageFormat::UtfGrid => "application/json",
+                ImageFormat::Mvt => "application/vnd.mapbox-vector-tile",
+            };
+            Response::builder()
+                .status(StatusCode::OK)
+                .header("Content-Type", content_type)
+                .header("Access-Control-Allow-Origin", "*")
+                .body(Body::from(tile))
+        }
+        Err(err @ ReError::EmptyResult) => Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body(Body::from(err.to_string())),
         Err(err) => {
             eprintln!("render failed: {err}");
 
-            return Response::builder()
+            Response::builder()
                 .status(StatusCode::INTERNAL_SERVER_ERROR)
                 .body(Body::from("render error"))
-                .expect("body should be built");
         }
+    }
+    .expect("body should be built")
+}
+
+fn legend_response(legend: &Legend) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/json")
+        .header("Access-Control-Allow-Origin", "*")
+        .body(Body::from(legend.to_json()))
+        .expect("body should be built")
+}
+
+fn metrics_response(hillshading_pool: &HillshadingPool) -> Response<Body> {
+    let metrics = hillshading_pool.metrics();
+
+    let body = [
+        (
+            "maprender_hillshading_dataset_hits_total",
+            "counter",
+            "Hillshading datasets served by an idle handle.",
+            metrics.hits,
+        ),
+        (
+            "maprender_hillshading_dataset_opens_total",
+            "counter",
+            "Hillshading datasets opened.",
+            metrics.opens,
+        ),
+        (
+            "maprender_hillshading_dataset_evictions_total",
+            "counter",
+            "Idle hillshading dataset handles closed.",
+            metrics.evictions,
+        ),
+        (
+            "maprender_hillshading_datasets_idle",
+            "gauge",
+            "Idle hillshading dataset handles.",
+            metrics.idle,
+        ),
+    ]
+    .iter()
+    .map(|(name, kind, help, value)| {
+        format!("# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n")
+    })
+    .collect::<String>();
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "text/plain; version=0.0.4")
+        .body(Body::from(body))
+        .expect("body should be built")
+}
+
+fn legend_item_response(item: &str, worker_pool: &RenderWorkerPool) -> Response<Body> {
+    static ITEM_REGEXP: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^(?P<id>.+?)(?:@(?P<scale>\d+(?:\.\d*)?)x)?\.(?P<ext>png|svg)$").unwrap()
+    });
+
+    let Some(captures) = ITEM_REGEXP.captures(item) else {
+        return Response::builder()
+            .status(StatusCode::BAD_REQUEST)
+            .body(Body::empty())
+            .expect("body should be built");
     };
 
-    if let Some(tile) = rendered.into_iter().next() {
-        let content_type = match format {
-            ImageFormat::Svg => "image/svg+xml",
-            ImageFormat::Pdf => "application/pdf",
-            ImageFormat::Jpeg => "image/jpeg",
-            ImageFormat::Png => "image/png",
-        };
-        Response::builder()
+    let scale = captures
+        .name("scale")
+        .and_then(|m| m.as_str().parse::<f64>().ok())
+        .unwrap_or(1.0);
+
+    let (format, content_type) = match &captures["ext"] {
+        "svg" => (ImageFormat::Svg, "image/svg+xml"),
+        _ => (ImageFormat::Png, "image/png"),
+    };
+
+    match worker_pool.render_legend_item(captures["id"].to_string(), format, scale) {
+        Ok(rendered) => Response::builder()
             .status(StatusCode::OK)
             .header("Content-Type", content_type)
             .header("Access-Control-Allow-Origin", "*")
-            .body(Body::from(tile))
-    } else {
-        Response::builder()
-            .status(StatusCode::INTERNAL_SERVER_ERROR)
-            .body(Body::from("empty render result"))
+            .body(Body::from(rendered.into_iter().next().unwrap_or_default())),
+        Err(ReError::LegendError(err @ LegendError::UnknownItem(_))) => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from(err.to_string())),
+        Err(err) => {
+            eprintln!("legend render failed: {err}");
+
+            Response::builder()
+                .status(StatusCode::INTERNAL_SERVER_ERROR)
+                .body(Body::from("render error"))
+        }
     }
     .expect("body should be built")
 }
 
-fn parse_tile_path(path: &str) -> Option<RenderRequest> {
+/// Polls the expires directory for expire lists of imposm.
+fn process_expire_files(
+    expires: &Expires,
+    tile_cache: &TileCache,
+    prerender: Option<&Prerender>,
+    dirty_tiles: &DirtyTiles,
+) -> ! {
+    const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+    loop {
+        match expires.process(tile_cache, prerender, dirty_tiles) {
+            // more lists left for the next batch
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(err) => eprintln!("expire file processing failed: {err}"),
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Re-renders dirty pre-rendered tiles.
+fn rerender_dirty_tiles(
+    worker_pool: &RenderWorkerPool,
+    tile_cache: &TileCache,
+    prerender: &Prerender,
+    dirty_tiles: &DirtyTiles,
+) -> ! {
+    loop {
+        let tile = dirty_tiles.take();
+
+        match prerender_tile(worker_pool, tile_cache, prerender, tile) {
+            Ok(()) => dirty_tiles.rendered(tile),
+            Err(err) => {
+                eprintln!("re-render of tile {tile} failed: {err}");
+
+                dirty_tiles.retry(tile);
+            }
+        }
+    }
+}
+
+/// Renders `tile` to the cache in all the pre-rendered scales.
+fn prerender_tile(
+    worker_pool: &RenderWorkerPool,
+    tile_cache: &TileCache,
+    prerender: &Prerender,
+    tile: Tile,
+) -> Result<(), ReError> {
+    let request = RenderRequest::new(
+        tile.bbox(),
+        tile.zoom,
+        prerender.scales.clone(),
+        prerender.format,
+    );
+
+    for (file, image) in prerender.files(tile).zip(worker_pool.render(request)?) {
+        tile_cache.store(&file, &image)?;
+    }
+
+    tile_cache.clear_dirty(tile)?;
+
+    Ok(())
+}
+
+fn load_prerender(
+    polygon: &Path,
+    min_zoom: u32,
+    max_zoom: u32,
+    scales: Vec<f64>,
+    extension: String,
+) -> Prerender {
+    let polygon = load_geometry_from_geojson(polygon)
+        .unwrap_or_else(|err| panic!("failed to load prerender polygon: {err}"));
+
+    Prerender {
+        polygon,
+        min_zoom,
+        max_zoom,
+        scales,
+        format: image_format(&extension),
+        extension,
+    }
+}
+
+/// Scales tiles are served in.
+const SCALES: [f64; 5] = [1.0, 1.5, 2.0, 3.0, 4.0];
+
+/// Render request of a tile path and the file of the tile in the cache;
+/// `None` for tiles zoomed in beyond `max_zoom`, outside the world or of other
+/// scales than [`SCALES`].
+fn parse_tile_path(path: &str, max_zoom: u32) -> Option<(RenderRequest, TileFile)> {
     static URL_PATH_REGEXP: LazyLock<Regex> = LazyLock::new(|| {
-        Regex::new(r"/(?P<zoom>\d+)/(?P<x>\d+)/(?P<y>\d+)(?:@(?P<scale>\d+(?:\.\d*)?)x)?(?:\.(?P<ext>jpg|jpeg|png|svg|pdf))?")
+        Regex::new(r"/(?P<zoom>\d+)/(?P<x>\d+)/(?P<y>\d+)(?:@(?P<scale>\d+(?:\.\d*)?)x)?(?:\.(?P<ext>jpg|jpeg|png|svg|pdf|grid\.json|mvt|pbf))?")
             .unwrap()
     });
 
@@ -273,14 +1121,29 @@ fn parse_tile_path(path: &str) -> Option<RenderRequest> {
 
     let ext = captures.name("ext").map(|m| m.as_str()).unwrap_or("png");
 
-    let format = match ext {
+    let tile = Tile { zoom, x, y };
+
+    if zoom > max_zoom || !tile.is_valid() || !SCALES.contains(&scale) {
+        return None;
+    }
+
+    Some((
+        RenderRequest::new(tile.bbox(), zoom, vec![scale], image_format(ext)),
+        TileFile {
+            tile,
+            scale,
+            extension: ext.to_string(),
+        },
+    ))
+}
+
+fn image_format(extension: &str) -> ImageFormat {
+    match extension {
         "svg" => ImageFormat::Svg,
         "pdf" => ImageFormat::Pdf,
         "jpg" | "jpeg" => ImageFormat::Jpeg,
+        "grid.json" => ImageFormat::UtfGrid,
+        "mvt" | "pbf" => ImageFormat::Mvt,
         _ => ImageFormat::Png,
-    };
-
-    let bbox = tile_bounds_to_epsg3857(x, y, zoom, 256);
-
-    Some(RenderRequest::new(bbox, zoom, vec![scale], format))
+    }
 }
@@ -0,0 +1,161 @@
+use crate::{
+    RenderWorkerPool, expires::Prerender, prerender_tile, tile::Tile, tile_cache::TileCache,
+};
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Side of the blocks of tiles rendered together, so that consecutive renders
+/// read neighbouring data from the database and the raster caches.
+const METATILE_SIZE: u32 = 8;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Tiles of the pre-rendered area and zooms, by zoom and then in metatile
+/// blocks.
+fn tiles(prerender: &Prerender) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+
+    collect_tiles(
+        prerender,
+        Tile {
+            zoom: 0,
+            x: 0,
+            y: 0,
+        },
+        &mut tiles,
+    );
+
+    tiles.sort_by_key(|tile| {
+        (
+            tile.zoom,
+            tile.y / METATILE_SIZE,
+            tile.x / METATILE_SIZE,
+            tile.y,
+            tile.x,
+        )
+    });
+
+    tiles
+}
+
+fn collect_tiles(prerender: &Prerender, tile: Tile, tiles: &mut Vec<Tile>) {
+    if !prerender.overlaps(tile) {
+        return;
+    }
+
+    if tile.zoom >= prerender.min_zoom {
+        tiles.push(tile);
+    }
+
+    if tile.zoom < prerender.max_zoom {
+        for child in tile.children() {
+            collect_tiles(prerender, child, tiles);
+        }
+    }
+}
+
+/// Renders the tiles of the pre-rendered area to the cache by `threads`
+/// concurrent requests to the worker pool. Tiles cached in all scales and not
+/// dirty are skipped unless `force`, so that an interrupted seed resumes
+/// where it stopped. Returns the number of tiles failed to render.
+pub fn seed(
+    worker_pool: &RenderWorkerPool,
+    tile_cache: &TileCache,
+    prerender: &Prerender,
+    threads: usize,
+    force: bool,
+) -> usize {
+    let tiles = tiles(prerender);
+
+    let total = tiles.len();
+
+    eprintln!(
+        "Seeding {total} tiles of zooms {}–{}.",
+        prerender.min_zoom, prerender.max_zoom
+    );
+
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let skipped = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            scope.spawn(|| {
+                while let Some(&tile) = tiles.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let cached = !force
+                        && !tile_cache.is_dirty(tile)
+                        && prerender.files(tile).all(|file| tile_cache.exists(&file));
+
+                    if cached {
+                        skipped.fetch_add(1, Ordering::Relaxed);
+                    } else if let Err(err) =
+                        prerender_tile(worker_pool, tile_cache, prerender, tile)
+                    {
+                        eprintln!("rendering of tile {tile} failed: {err}");
+
+                        failed.fetch_add(1, Ordering::Relaxed);
+                    }
+
+                    done.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+
+        let mut reported = Instant::now();
+
+        while done.load(Ordering::Relaxed) < total {
+            thread::sleep(Duration::from_secs(1));
+
+            if reported.elapsed() < PROGRESS_INTERVAL {
+                continue;
+            }
+
+            reported = Instant::now();
+
+            let done = done.load(Ordering::Relaxed);
+
+            let rendered = done - skipped.load(Ordering::Relaxed);
+
+            let rate = rendered as f64 / start.elapsed().as_secs_f64();
+
+            let zoom = tiles.get(done).map_or(prerender.max_zoom, |tile| tile.zoom);
+
+            eprintln!(
+                "Seeded {done} of {total} tiles ({:.1} %), zoom {zoom}, {rate:.1} tiles/s, {} left.",
+                done as f64 / total as f64 * 100.0,
+                format_duration((total - done) as f64 / rate)
+            );
+        }
+    });
+
+    let failed = failed.into_inner();
+
+    eprintln!(
+        "Seeded {total} tiles in {}: {} already cached, {failed} failed.",
+        format_duration(start.elapsed().as_secs_f64()),
+        skipped.into_inner(),
+    );
+
+    failed
+}
+
+fn format_duration(seconds: f64) -> String {
+    if !seconds.is_finite() {
+        return "?".into();
+    }
+
+    let seconds = seconds.round() as u64;
+
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
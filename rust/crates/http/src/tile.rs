@@ -0,0 +1,68 @@
+use geo::Rect;
+use maprender_core::tile_bounds_to_epsg3857;
+use std::fmt;
+
+/// Map tile; ordered by zoom first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tile {
+    pub zoom: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl Tile {
+    /// Parses `{zoom}/{x}/{y}`.
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut parts = input.split('/').map(|part| part.parse::<u32>().ok());
+
+        let tile = Self {
+            zoom: parts.next()??,
+            x: parts.next()??,
+            y: parts.next()??,
+        };
+
+        parts.next().is_none().then_some(tile)
+    }
+
+    /// Whether the tile is within the world at its zoom.
+    pub const fn is_valid(self) -> bool {
+        self.zoom < u32::BITS && self.x >> self.zoom == 0 && self.y >> self.zoom == 0
+    }
+
+    pub fn bbox(self) -> Rect<f64> {
+        tile_bounds_to_epsg3857(self.x, self.y, self.zoom, 256)
+    }
+
+    pub const fn parent(self) -> Option<Self> {
+        if self.zoom == 0 {
+            return None;
+        }
+
+        Some(Self {
+            zoom: self.zoom - 1,
+            x: self.x / 2,
+            y: self.y / 2,
+        })
+    }
+
+    pub const fn children(self) -> [Self; 4] {
+        let (zoom, x, y) = (self.zoom + 1, self.x * 2, self.y * 2);
+
+        [
+            Self { zoom, x, y },
+            Self { zoom, x: x + 1, y },
+            Self { zoom, x, y: y + 1 },
+            Self {
+                zoom,
+                x: x + 1,
+                y: y + 1,
+            },
+        ]
+    }
+}
+
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.zoom, self.x, self.y)
+    }
+}
@@ -0,0 +1,355 @@
+use crate::tile::Tile;
+use std::{
+    collections::HashSet,
+    fmt,
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Image of a tile in the cache, `{zoom}/{x}/{y}[@{scale}x].{extension}`.
+#[derive(Debug, Clone)]
+pub struct TileFile {
+    pub tile: Tile,
+    pub scale: f64,
+    pub extension: String,
+}
+
+impl fmt::Display for TileFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}.{}",
+            self.tile,
+            scale_spec(self.scale),
+            self.extension
+        )
+    }
+}
+
+fn scale_spec(scale: f64) -> String {
+    if scale == 1.0 {
+        String::new()
+    } else {
+        format!("@{scale}x")
+    }
+}
+
+/// Rendered tiles on the drive in the layout of the mapserver, so that both
+/// can share a tiles directory.
+pub struct TileCache {
+    dir: PathBuf,
+    /// Sequence of temporary file names.
+    counter: AtomicU64,
+    expire_index: Option<ExpireIndex>,
+}
+
+/// Tiles zoomed in beyond `max_zoom` are listed in the `.index` file of the
+/// tile at `expires_zoom` containing them, as they are too many to expire by
+/// the zoom, and are deleted when that tile expires.
+#[derive(Debug, Clone, Copy)]
+pub struct ExpireIndex {
+    pub expires_zoom: u32,
+    pub max_zoom: u32,
+}
+
+impl TileCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            counter: AtomicU64::new(0),
+            expire_index: None,
+        }
+    }
+
+    pub const fn with_expire_index(mut self, expire_index: ExpireIndex) -> Self {
+        self.expire_index = Some(expire_index);
+
+        self
+    }
+
+    /// The cached `file`, or else the tile rendered by `render` and stored.
+    /// Rendering holds a lock file next to the tile, so that threads and
+    /// processes requesting the same tile wait for a single render instead of
+    /// repeating it. The lock file is kept, as the mapserver does, since
+    /// removing it would let a waiting holder of the removed file and a new
+    /// holder of a recreated one render at once. A tile failing to be stored
+    /// is still returned.
+    pub fn get_or_render<E: From<io::Error>>(
+        &self,
+        file: &TileFile,
+        render: impl FnOnce() -> Result<Vec<u8>, E>,
+    ) -> Result<Vec<u8>, E> {
+        let path = self.dir.join(file.to_string());
+
+        if let Some(data) = read_existing(&path)? {
+            return Ok(data);
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let lock_path = with_suffix(&path, ".lock");
+
+        let lock = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+
+        lock.lock()?;
+
+        // rendered by the previous holder of the lock
+        if let Some(data) = read_existing(&path)? {
+            return Ok(data);
+        }
+
+        let data = render()?;
+
+        // served anyway; waiting holders of the lock render it again
+        if let Err(err) = self.store(file, &data) {
+            eprintln!("error caching tile {file}: {err}");
+        }
+
+        Ok(data)
+    }
+
+    pub fn exists(&self, file: &TileFile) -> bool {
+        self.dir.join(file.to_string()).exists()
+    }
+
+    /// Writes `data` to a temporary file renamed to the tile, so that readers
+    /// never see a partial tile.
+    pub fn store(&self, file: &TileFile, data: &[u8]) -> io::Result<()> {
+        let path = self.dir.join(file.to_string());
+
+        let tile_dir = self.dir.join(format!("{}/{}", file.tile.zoom, file.tile.x));
+
+        fs::create_dir_all(&tile_dir)?;
+
+        // `{y}[@{scale}x]_{n}_tmp.{ext}` like the mapserver
+        let tmp_path = tile_dir.join(format!(
+            "{}{}_{}_{}_tmp.{}",
+            file.tile.y,
+            scale_spec(file.scale),
+            std::process::id(),
+            self.counter.fetch_add(1, Ordering::Relaxed),
+            file.extension
+        ));
+
+        fs::write(&tmp_path, data).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp_path);
+        })?;
+
+        // the index stays locked until the tile appears, so that expiring it
+        // in between does not drop the entry of a tile left behind
+        let index = match self.index(file) {
+            Ok(index) => index,
+            Err(err) => {
+                let _ = fs::remove_file(&tmp_path);
+
+                return Err(err);
+            }
+        };
+
+        let result = fs::rename(&tmp_path, path).inspect_err(|_| {
+            let _ = fs::remove_file(&tmp_path);
+        });
+
+        drop(index);
+
+        result
+    }
+
+    /// Lists `file` in the `.index` file of the expire zoom tile containing
+    /// it, if it is zoomed in beyond the max zoom. Returns the index, locked
+    /// shared.
+    fn index(&self, file: &TileFile) -> io::Result<Option<File>> {
+        let Some(ExpireIndex {
+            expires_zoom,
+            max_zoom,
+        }) = self.expire_index
+        else {
+            return Ok(None);
+        };
+
+        let tile = file.tile;
+
+        if tile.zoom <= max_zoom || tile.zoom < expires_zoom || !tile.is_valid() {
+            return Ok(None);
+        }
+
+        let shift = tile.zoom - expires_zoom;
+
+        let index_dir = self.dir.join(format!("{expires_zoom}/{}", tile.x >> shift));
+
+        fs::create_dir_all(&index_dir)?;
+
+        let mut index = File::options()
+            .create(true)
+            .append(true)
+            .open(index_dir.join(format!("{}.index", tile.y >> shift)))?;
+
+        index.lock_shared()?;
+
+        index.write_all(format!("{tile}{}\n", scale_spec(file.scale)).as_bytes())?;
+
+        Ok(Some(index))
+    }
+
+    /// Deletes the tiles listed in the `.index` file of `tile` and empties
+    /// it. Returns the number of tiles listed.
+    pub fn expire_index(&self, tile: Tile) -> io::Result<usize> {
+        let path = self.dir.join(format!("{tile}.index"));
+
+        let mut index = match File::options().read(true).write(true).open(&path) {
+            Ok(index) => index,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(err) => return Err(err),
+        };
+
+        index.lock()?;
+
+        let mut content = String::new();
+
+        index.read_to_string(&mut content)?;
+
+        // entries are `{zoom}/{x}/{y}[@{scale}x]`, without the extension
+        let tiles: HashSet<_> = content
+            .lines()
+            .filter_map(|line| Tile::parse(line.split_once('@').map_or(line, |(tile, _)| tile)))
+            .collect();
+
+        for &tile in &tiles {
+            if let Err(err) = self.remove(tile) {
+                eprintln!("error deleting on-demand tile {tile}: {err}");
+            }
+        }
+
+        index.set_len(0)?;
+
+        Ok(tiles.len())
+    }
+
+    /// Deletes all images of `tile`, of any scale and format.
+    pub fn remove(&self, tile: Tile) -> io::Result<()> {
+        let dir = self.dir.join(format!("{}/{}", tile.zoom, tile.x));
+
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        let y = tile.y.to_string();
+
+        for entry in entries {
+            let entry = entry?;
+
+            let name = entry.file_name();
+
+            let Some(name) = name.to_str() else {
+                continue;
+            };
+
+            let is_image = name
+                .strip_prefix(&y)
+                .is_some_and(|rest| rest.starts_with(['.', '@']))
+                && !name.contains("_tmp.")
+                && ![".dirty", ".index", ".lock"]
+                    .iter()
+                    .any(|suffix| name.ends_with(suffix));
+
+            if is_image {
+                match fs::remove_file(entry.path()) {
+                    Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Marks `tile` to be re-rendered by a `.dirty` file, kept over restarts.
+    pub fn mark_dirty(&self, tile: Tile) -> io::Result<()> {
+        fs::create_dir_all(self.dir.join(format!("{}/{}", tile.zoom, tile.x)))?;
+
+        File::create(self.dir.join(format!("{tile}.dirty"))).map(|_| ())
+    }
+
+    pub fn is_dirty(&self, tile: Tile) -> bool {
+        self.dir.join(format!("{tile}.dirty")).exists()
+    }
+
+    pub fn clear_dirty(&self, tile: Tile) -> io::Result<()> {
+        match fs::remove_file(self.dir.join(format!("{tile}.dirty"))) {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Tiles marked dirty up to `max_zoom`.
+    pub fn dirty_tiles(&self, max_zoom: u32) -> io::Result<Vec<Tile>> {
+        let mut tiles = Vec::new();
+
+        let zoom_dirs = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(tiles),
+            Err(err) => return Err(err),
+        };
+
+        for zoom_dir in zoom_dirs {
+            let zoom_dir = zoom_dir?;
+
+            let Some(zoom) = parse_name(&zoom_dir.file_name()).filter(|zoom| *zoom <= max_zoom)
+            else {
+                continue;
+            };
+
+            for x_dir in fs::read_dir(zoom_dir.path())? {
+                let x_dir = x_dir?;
+
+                let Some(x) = parse_name(&x_dir.file_name()) else {
+                    continue;
+                };
+
+                for file in fs::read_dir(x_dir.path())? {
+                    let name = file?.file_name();
+
+                    if let Some(y) = name
+                        .to_str()
+                        .and_then(|name| name.strip_suffix(".dirty"))
+                        .and_then(|y| y.parse().ok())
+                    {
+                        tiles.push(Tile { zoom, x, y });
+                    }
+                }
+            }
+        }
+
+        Ok(tiles)
+    }
+}
+
+fn parse_name(name: &std::ffi::OsStr) -> Option<u32> {
+    name.to_str()?.parse().ok()
+}
+
+fn read_existing(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+
+    path.push(suffix);
+
+    path.into()
+}
@@ -1,17 +1,48 @@
-use geo::{Geometry, Rect};
+use geo::{Coord, Geometry, Rect};
 use geojson::FeatureCollection;
 use maprender_core::{
-    HillshadingDatasets, ImageFormat, RenderRequest, RouteTypes, SvgRepo,
-    load_geometry_from_geojson, load_hillshading_datasets, render,
+    Abbreviations, ContourInterval, DebugFlags, HillshadingDatasets, HillshadingManifest,
+    HillshadingPool, ImageFormat, Legend, Pois, RenderRequest, RouteTypes, SvgRepo,
+    TerrainEncoding, TerrainFormat, elevation_profile, elevations, export_contours,
+    load_geometry_from_geojson, parse_gpx, render, render_terrain,
 };
 use napi::{Error, Result, bindgen_prelude::*};
 use napi_derive::napi;
 use postgres::NoTls;
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+/// Hillshading dataset pools by base path, shared by the renderers of the
+/// process.
+static HILLSHADING_POOLS: LazyLock<Mutex<HashMap<String, Arc<HillshadingPool>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+fn hillshading_pool(base: String) -> Result<Arc<HillshadingPool>> {
+    let mut pools = HILLSHADING_POOLS.lock().unwrap();
+
+    if let Some(pool) = pools.get(&base) {
+        return Ok(pool.clone());
+    }
+
+    let manifest = HillshadingManifest::load(&base)
+        .map_err(|err| Error::from_reason(format!("invalid hillshading manifest: {err}")))?;
+
+    let pool = Arc::new(HillshadingPool::new(manifest));
+
+    pools.insert(base, pool.clone());
+
+    Ok(pool)
+}
 
 #[napi]
 pub struct Renderer {
     client: postgres::Client,
     svg_repo: SvgRepo,
+    abbreviations: Abbreviations,
+    pois: Pois,
+    legend: Legend,
     shading_data: Option<HillshadingDatasets>,
     mask_geometry: Option<Geometry>,
 }
@@ -21,11 +52,33 @@ pub struct Renderer {
 pub struct RequestExtra {
     pub shading: Option<bool>,
     pub contours: Option<bool>,
+    /// Interval of contours traced from elevation, e.g. `20`, `20m` or `50ft`.
+    pub contour_interval: Option<String>,
+    /// Slope classes of avalanche terrain.
+    pub slope: Option<bool>,
+    /// Renders just the overlays on a transparent background.
+    pub overlay_only: Option<bool>,
     pub hiking_routes: Option<bool>,
     pub bicycle_routes: Option<bool>,
     pub ski_routes: Option<bool>,
     pub horse_routes: Option<bool>,
     pub feature_collection: Option<String>,
+    /// Comma separated debug overlays: `collision`, `tiles`, `timings`.
+    pub debug: Option<String>,
+    /// Sun azimuth of shading computed from elevation, in degrees.
+    pub azimuth: Option<f64>,
+    /// Sun altitude of shading computed from elevation, in degrees.
+    pub altitude: Option<f64>,
+    pub multidirectional: Option<bool>,
+    pub exaggeration: Option<f64>,
+    /// Lower shading contrast in valleys than at summits.
+    pub aerial_perspective: Option<bool>,
+    /// Colours elevation by the hypsometric tint of the hillshading manifest.
+    pub hypsometric_tint: Option<bool>,
+    /// Shadows cast by the terrain from the sun.
+    pub shadows: Option<bool>,
+    /// Darkens valleys by the share of the sky they see.
+    pub sky_view: Option<bool>,
 }
 
 #[napi]
@@ -37,6 +90,9 @@ impl Renderer {
         svg_base: String,
         db_priority: Option<u8>,
         mask_geojson_path: Option<String>,
+        abbreviations_path: Option<String>,
+        poi_style_path: Option<String>,
+        gdal_cache_mb: Option<u32>,
     ) -> Result<Self> {
         let mut client = postgres::Client::connect(&connection_str, NoTls).map_err(|err| {
             Error::new(
@@ -62,9 +118,40 @@ impl Renderer {
             })
             .transpose()?;
 
+        let abbreviations = abbreviations_path
+            .map_or_else(
+                || Ok(Abbreviations::default()),
+                |path| Abbreviations::load(&path),
+            )
+            .map_err(|err| Error::from_reason(format!("failed to load abbreviations: {err}")))?;
+
+        let svg_repo = SvgRepo::new(svg_base);
+
+        let pois = poi_style_path
+            .map_or_else(|| Ok(Pois::default()), |path| Pois::load(&path))
+            .and_then(|pois| {
+                pois.validate(&svg_repo)?;
+
+                Ok(pois)
+            })
+            .map_err(|err| Error::from_reason(format!("invalid POI style: {err}")))?;
+
+        // the cache is shared by the process, so the last renderer sets it
+        if let Some(gdal_cache_mb) = gdal_cache_mb {
+            HillshadingPool::set_cache_budget(gdal_cache_mb as usize);
+        }
+
+        let shading_data = hillshading_base
+            .map(hillshading_pool)
+            .transpose()?
+            .map(HillshadingDatasets::new);
+
         Ok(Self {
-            svg_repo: SvgRepo::new(svg_base),
-            shading_data: hillshading_base.map(load_hillshading_datasets),
+            svg_repo,
+            abbreviations,
+            legend: Legend::new(&pois),
+            pois,
+            shading_data,
             client,
             mask_geometry,
         })
@@ -86,6 +173,13 @@ impl Renderer {
         if let Some(extra) = extra {
             request.shading = extra.shading.unwrap_or(true);
             request.contours = extra.contours.unwrap_or(true);
+            request.contour_interval = extra
+                .contour_interval
+                .as_deref()
+                .and_then(ContourInterval::parse);
+            request.hypsometric_tint = extra.hypsometric_tint.unwrap_or(false);
+            request.slope = extra.slope.unwrap_or(false);
+            request.overlay_only = extra.overlay_only.unwrap_or(false);
 
             if extra.hiking_routes.is_some()
                 && extra.bicycle_routes.is_some()
@@ -102,6 +196,24 @@ impl Renderer {
                 request.route_types = route_types;
             }
 
+            let hillshading = &mut request.hillshading;
+
+            hillshading.azimuth = extra.azimuth.unwrap_or(hillshading.azimuth);
+            hillshading.altitude = extra.altitude.unwrap_or(hillshading.altitude);
+            hillshading.multidirectional = extra
+                .multidirectional
+                .unwrap_or(hillshading.multidirectional);
+            hillshading.exaggeration = extra.exaggeration.unwrap_or(hillshading.exaggeration);
+            hillshading.aerial_perspective = extra
+                .aerial_perspective
+                .unwrap_or(hillshading.aerial_perspective);
+            hillshading.shadows = extra.shadows.unwrap_or(hillshading.shadows);
+            hillshading.sky_view = extra.sky_view.unwrap_or(hillshading.sky_view);
+
+            if let Some(debug) = extra.debug {
+                request.debug = DebugFlags::parse(&debug);
+            }
+
             if let Some(feature_collection) = extra.feature_collection {
                 let feature_collection: FeatureCollection =
                     serde_json::from_str(&feature_collection)
@@ -115,6 +227,8 @@ impl Renderer {
             &request,
             &mut self.client,
             &mut self.svg_repo,
+            &self.abbreviations,
+            &self.pois,
             &mut self.shading_data,
             self.mask_geometry.as_ref(),
         )
@@ -122,4 +236,114 @@ impl Renderer {
 
         Ok(rendered.into_iter().map(Buffer::from).collect())
     }
+
+    /// Elevations in metres at `[lon, lat]` points, `null` where there is no
+    /// data.
+    #[napi]
+    pub fn elevations(&mut self, points: Vec<(f64, f64)>) -> Result<Vec<Option<f64>>> {
+        let coords: Vec<_> = points.into_iter().map(|(x, y)| Coord { x, y }).collect();
+
+        let datasets = self.shading_datasets()?;
+
+        let elevations = elevations(datasets, &coords);
+
+        datasets.release();
+
+        elevations.map_err(|err| Error::from_reason(err.to_string()))
+    }
+
+    /// Elevation profile of a GeoJSON LineString or GPX track as JSON,
+    /// sampled every `spacing` metres (default 20).
+    #[napi]
+    pub fn elevation_profile(&mut self, input: String, spacing: Option<f64>) -> Result<String> {
+        let line = if input.trim_start().starts_with('<') {
+            parse_gpx(input.as_bytes()).map_err(|err| Error::from_reason(err.to_string()))?
+        } else {
+            let geojson: geojson::GeoJson = input
+                .parse()
+                .map_err(|err| Error::from_reason(format!("parse: {err}")))?;
+
+            match Geometry::try_from(geojson) {
+                Ok(Geometry::LineString(line)) => line,
+                Ok(_) => return Err(Error::from_reason("expected a LineString")),
+                Err(err) => return Err(Error::from_reason(format!("parse: {err}"))),
+            }
+        };
+
+        let datasets = self.shading_datasets()?;
+
+        let profile = elevation_profile(datasets, &line, spacing.unwrap_or(20.0));
+
+        datasets.release();
+
+        profile
+            .map(|profile| profile.to_json())
+            .map_err(|err| Error::from_reason(err.to_string()))
+    }
+
+    /// Terrain tile of `size` pixels encoding the elevation of the datasets.
+    #[napi]
+    pub fn render_terrain(
+        &mut self,
+        bbox: (f64, f64, f64, f64),
+        size: u32,
+        encoding: TerrainEncoding,
+        format: TerrainFormat,
+    ) -> Result<Buffer> {
+        let bbox = Rect::new((bbox.0, bbox.1), (bbox.2, bbox.3));
+
+        let datasets = self.shading_datasets()?;
+
+        let tile = render_terrain(datasets, bbox, size, encoding, format);
+
+        datasets.release();
+
+        tile.map(Buffer::from)
+            .map_err(|err| Error::from_reason(err.to_string()))
+    }
+
+    /// Contour lines of the WGS84 `bbox` as drawn at `zoom`, as GeoJSON.
+    #[napi]
+    pub fn export_contours(&mut self, bbox: (f64, f64, f64, f64), zoom: u32) -> Result<String> {
+        export_contours(
+            &mut self.client,
+            self.shading_data.as_ref(),
+            Rect::new((bbox.0, bbox.1), (bbox.2, bbox.3)),
+            zoom,
+        )
+        .map_err(|err| Error::from_reason(err.to_string()))
+    }
+
+    fn shading_datasets(&mut self) -> Result<&mut HillshadingDatasets> {
+        self.shading_data
+            .as_mut()
+            .ok_or_else(|| Error::from_reason("no hillshading base configured"))
+    }
+
+    /// Legend items as JSON.
+    #[napi]
+    pub fn legend(&self) -> String {
+        self.legend.to_json()
+    }
+
+    #[napi]
+    pub fn render_legend_item(
+        &mut self,
+        id: String,
+        format: ImageFormat,
+        scale: Option<f64>,
+    ) -> Result<Buffer> {
+        let image = self
+            .legend
+            .render_item(
+                &id,
+                &mut self.svg_repo,
+                &self.pois,
+                format,
+                scale.unwrap_or(1.0),
+            )
+            .map_err(|err| Error::from_reason(err.to_string()))?;
+
+        Ok(image.into())
+    }
 }